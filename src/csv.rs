@@ -0,0 +1,91 @@
+use crate::error::RqlError;
+
+// Minimal RFC 4180 style CSV reader used by IMPORT and COPY, and the quoting EXPORT writes with
+
+// One field of a record; quoted fields are always text ("42" stays a string)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub text: String,
+    pub quoted: bool,
+}
+
+// A record together with the 1-based line it starts on
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub line: usize,
+    pub fields: Vec<Field>,
+}
+
+impl Record {
+    // An empty line: a single unquoted empty field, which is how a one-column row holding NULL is
+    // written. Readers of several columns skip such lines.
+    pub fn is_blank(&self) -> bool {
+        matches!(self.fields.as_slice(), [field] if field.text.is_empty() && !field.quoted)
+    }
+}
+
+// Splits CSV text into records. Quoted fields may contain commas, doubled quotes ("")
+// and newlines; an empty line is a blank record (see `Record::is_blank`).
+pub fn parse(text: &str) -> Result<Vec<Record>, RqlError> {
+    parse_delimited(text, ',')
+}
+
+// Like `parse`, with fields separated by `delimiter` (e.g. a tab) instead of commas
+pub fn parse_delimited(text: &str, delimiter: char) -> Result<Vec<Record>, RqlError> {
+    let mut records = vec![];
+    let mut fields = vec![];
+    let mut field = Field { text: String::new(), quoted: false };
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.text.is_empty() && !field.quoted => {
+                field.quoted = true;
+                let start = line;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.text.push('"');
+                        }
+                        Some('"') => break,
+                        Some(ch) => {
+                            if ch == '\n' {
+                                line += 1;
+                            }
+                            field.text.push(ch);
+                        }
+                        None => return Err(RqlError::syntax(format!("line {}: unterminated quoted field", start))),
+                    }
+                }
+            }
+            _ if c == delimiter => {
+                fields.push(std::mem::replace(&mut field, Field { text: String::new(), quoted: false }))
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                fields.push(std::mem::replace(&mut field, Field { text: String::new(), quoted: false }));
+                records.push(Record { line: record_line, fields: std::mem::take(&mut fields) });
+                line += 1;
+                record_line = line;
+            }
+            _ if field.quoted => {
+                return Err(RqlError::syntax(format!("line {}: unexpected character after closing quote", line)));
+            }
+            _ => field.text.push(c),
+        }
+    }
+    if !fields.is_empty() || !field.text.is_empty() || field.quoted {
+        fields.push(field);
+        records.push(Record { line: record_line, fields });
+    }
+
+    Ok(records)
+}
+
+// `text` as a quoted field, which reads back as exactly that text (never a number or NULL)
+pub fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
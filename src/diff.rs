@@ -0,0 +1,162 @@
+use std::fmt;
+
+use crate::executor::{Database, Table};
+use crate::parser::DataType;
+
+// How the schema of one database differs from another's, as `Database::diff` and the
+// `DIFF 'old.bin' 'new.bin'` statement report it. Rows, indexes and constraints are not compared.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SchemaDiff {
+    pub added_tables: Vec<String>,
+    pub removed_tables: Vec<String>,
+    pub changed_tables: Vec<TableDiff>, // tables in both databases whose columns differ
+}
+
+// The column changes of one table found in both databases
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableDiff {
+    pub name: String,
+    pub added_columns: Vec<(String, Option<DataType>)>, // None: an untyped column
+    pub removed_columns: Vec<String>,
+    // (old name, new name): a column that is gone, replaced at the same position by a new one of
+    // the same type
+    pub renamed_columns: Vec<(String, String)>,
+    pub type_changes: Vec<(String, Option<DataType>, Option<DataType>)>, // (column, old, new)
+    // (column, old position, new position), counted from 1: a column in both versions whose place
+    // among the other such columns changed
+    pub moved_columns: Vec<(String, usize, usize)>,
+}
+
+impl SchemaDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_tables.is_empty() && self.removed_tables.is_empty() && self.changed_tables.is_empty()
+    }
+}
+
+impl Database {
+    // How `new`'s schema differs from this database's, with tables and their changes in name order
+    pub fn diff(&self, new: &Database) -> SchemaDiff {
+        let sorted = |names: Vec<&String>| {
+            let mut names: Vec<String> = names.into_iter().cloned().collect();
+            names.sort();
+            names
+        };
+        let common = sorted(self.tables.keys().filter(|name| new.tables.contains_key(*name)).collect());
+        SchemaDiff {
+            added_tables: sorted(new.tables.keys().filter(|name| !self.tables.contains_key(*name)).collect()),
+            removed_tables: sorted(self.tables.keys().filter(|name| !new.tables.contains_key(*name)).collect()),
+            changed_tables: common
+                .into_iter()
+                .filter_map(|name| diff_table(name.clone(), &self.tables[&name], &new.tables[&name]))
+                .collect(),
+        }
+    }
+}
+
+// The column changes between two versions of a table, or None if its columns are the same
+pub(crate) fn diff_table(name: String, old: &Table, new: &Table) -> Option<TableDiff> {
+    let column_type = |table: &Table, idx: usize| table.column_types.get(idx).copied().flatten();
+    let position = |table: &Table, col: &str| table.columns.iter().position(|c| c == col);
+
+    // Positions in `from` of the columns `to` has no column of the same name for
+    let missing = |from: &Table, to: &Table| -> Vec<usize> {
+        (0..from.columns.len()).filter(|&i| position(to, &from.columns[i]).is_none()).collect()
+    };
+    let (mut removed, mut added) = (missing(old, new), missing(new, old));
+    // A removed column whose slot now holds an added column of the same type was renamed
+    let mut renamed_columns = vec![];
+    removed.retain(|&i| {
+        match added.iter().position(|&j| j == i && column_type(old, i) == column_type(new, j)) {
+            Some(found) => {
+                added.remove(found);
+                renamed_columns.push((old.columns[i].clone(), new.columns[i].clone()));
+                false
+            }
+            None => true,
+        }
+    });
+
+    let type_changes: Vec<_> = old
+        .columns
+        .iter()
+        .enumerate()
+        .filter_map(|(i, col)| {
+            let j = position(new, col)?;
+            let (before, after) = (column_type(old, i), column_type(new, j));
+            (before != after).then(|| (col.clone(), before, after))
+        })
+        .collect();
+
+    // (old position, new position) of the columns in both versions, in their old order; a renamed
+    // column keeps its position
+    let kept: Vec<(usize, usize)> = (0..old.columns.len())
+        .filter_map(|i| {
+            let renamed = renamed_columns.iter().any(|(col, _)| *col == old.columns[i]);
+            Some((i, if renamed { i } else { position(new, &old.columns[i])? }))
+        })
+        .collect();
+    let mut new_order: Vec<usize> = kept.iter().map(|&(_, j)| j).collect();
+    new_order.sort_unstable();
+    let moved_columns = kept
+        .iter()
+        .zip(new_order)
+        .filter(|&(&(_, j), slot)| j != slot)
+        .map(|(&(i, j), _)| (new.columns[j].clone(), i + 1, j + 1))
+        .collect();
+
+    let diff = TableDiff {
+        name,
+        added_columns: added.into_iter().map(|j| (new.columns[j].clone(), column_type(new, j))).collect(),
+        removed_columns: removed.into_iter().map(|i| old.columns[i].clone()).collect(),
+        renamed_columns,
+        type_changes,
+        moved_columns,
+    };
+    let unchanged = diff.added_columns.is_empty()
+        && diff.removed_columns.is_empty()
+        && diff.renamed_columns.is_empty()
+        && diff.type_changes.is_empty()
+        && diff.moved_columns.is_empty();
+    (!unchanged).then_some(diff)
+}
+
+// A column's type as written in CREATE TABLE, or "untyped"
+fn type_name(data_type: Option<DataType>) -> String {
+    data_type.map_or_else(|| "untyped".to_string(), |t| t.to_string())
+}
+
+// One line per change, e.g. "Table 'orders' added", with a table's column changes indented under
+// "Table 'users' changed:"
+impl fmt::Display for SchemaDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "No schema differences");
+        }
+        let mut lines = vec![];
+        for name in &self.added_tables {
+            lines.push(format!("Table '{}' added", name));
+        }
+        for name in &self.removed_tables {
+            lines.push(format!("Table '{}' removed", name));
+        }
+        for table in &self.changed_tables {
+            lines.push(format!("Table '{}' changed:", table.name));
+            for (col, data_type) in &table.added_columns {
+                lines.push(format!("  column '{}' added ({})", col, type_name(*data_type)));
+            }
+            for col in &table.removed_columns {
+                lines.push(format!("  column '{}' removed", col));
+            }
+            for (old, new) in &table.renamed_columns {
+                lines.push(format!("  column '{}' renamed to '{}'", old, new));
+            }
+            for (col, before, after) in &table.type_changes {
+                lines.push(format!("  column '{}' changed from {} to {}", col, type_name(*before), type_name(*after)));
+            }
+            for (col, before, after) in &table.moved_columns {
+                lines.push(format!("  column '{}' moved from position {} to {}", col, before, after));
+            }
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
@@ -0,0 +1,69 @@
+use std::fmt;
+
+// Errors produced while tokenizing, parsing or executing a statement
+#[derive(Debug, Clone, PartialEq)]
+pub enum RqlError {
+    TableNotFound(String),
+    ColumnNotFound(String),
+    // An unqualified name that matches a column of more than one joined table; candidates are the
+    // qualified names it could mean, e.g. ["users.name", "pets.name"]
+    AmbiguousColumn { name: String, candidates: Vec<String> },
+    TypeMismatch(String),
+    // pos is the byte offset into the SQL text, when known. token is the index of the offending
+    // token in the statement handed to `parse`, or the statement's length if it ended too soon.
+    SyntaxError { msg: String, pos: Option<usize>, token: Option<usize> },
+    // Input that ends inside a string literal, quoted identifier or block comment opened at byte
+    // offset pos; text is what was read of it, opening quote included. The REPL keeps reading
+    // lines while a statement ends this way.
+    UnterminatedString { text: String, pos: usize },
+    UnterminatedIdentifier { text: String, pos: usize },
+    UnterminatedComment { pos: usize },
+    ConstraintViolation(String),
+    // A statement that would change the database, run on one opened read-only; holds the
+    // statement's name, e.g. "INSERT"
+    ReadOnly(String),
+    // Any other failure while running a statement (division by zero, I/O, ...)
+    ExecutionError(String),
+}
+
+impl RqlError {
+    pub fn syntax(msg: impl Into<String>) -> Self {
+        RqlError::SyntaxError { msg: msg.into(), pos: None, token: None }
+    }
+
+    pub fn execution(msg: impl Into<String>) -> Self {
+        RqlError::ExecutionError(msg.into())
+    }
+
+    // Whether the input ended inside a string, quoted identifier or comment, so more of it may follow
+    pub fn is_unterminated(&self) -> bool {
+        matches!(
+            self,
+            RqlError::UnterminatedString { .. }
+                | RqlError::UnterminatedIdentifier { .. }
+                | RqlError::UnterminatedComment { .. }
+        )
+    }
+}
+
+impl fmt::Display for RqlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RqlError::TableNotFound(name) => write!(f, "Table '{}' not found", name),
+            RqlError::ColumnNotFound(name) => write!(f, "Column '{}' not found", name),
+            RqlError::AmbiguousColumn { name, candidates } => {
+                write!(f, "Column '{}' is ambiguous; qualify it as one of {}", name, candidates.join(", "))
+            }
+            RqlError::UnterminatedString { text, .. } => write!(f, "Unterminated string literal: {}", text),
+            RqlError::UnterminatedIdentifier { text, .. } => write!(f, "Unterminated quoted identifier: {}", text),
+            RqlError::UnterminatedComment { .. } => write!(f, "Unterminated block comment: missing */"),
+            RqlError::ReadOnly(statement) => write!(f, "The database is read-only; {} is not allowed", statement),
+            RqlError::TypeMismatch(msg)
+            | RqlError::SyntaxError { msg, .. }
+            | RqlError::ConstraintViolation(msg)
+            | RqlError::ExecutionError(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for RqlError {}
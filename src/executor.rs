@@ -1,340 +1,3808 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ops::Bound;
+use std::time::{Duration, Instant};
 use serde::{Serialize, Deserialize};
 
-use crate::parser::{self, InsertStatement, Statement, Value};
+use crate::csv;
+use crate::diff::{SchemaDiff, diff_table};
+use crate::error::RqlError;
+use crate::render::to_json;
+use crate::row::FromRow;
+use crate::wal;
+use crate::parser::{
+    self, AggregateFunc, BinaryOp, CompareOp, Condition, CopyStatement, DataType, DumpStatement, Expr,
+    InsertSource, InsertStatement, JoinKind, OnConflict, OnDelete, OrderKey, quote_name,
+    SelectItem, ScalarFunc, Statement, TableConstraint,
+    PreparedStatement, Value, parse_date,
+};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Table {
     pub rows: Vec<Vec<Value>>,
     pub columns: Vec<String>,
+    // Declared type per column (None = untyped), parallel to `columns`
+    pub column_types: Vec<Option<DataType>>,
+    pub foreign_keys: Vec<ForeignKey>,
+    pub indexes: Vec<Index>,
+    pub constraints: Vec<Constraint>,
+}
+
+// A rule every row must satisfy, added with ALTER TABLE ... ADD CONSTRAINT
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Constraint {
+    pub name: String,
+    pub kind: ConstraintKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ConstraintKind {
+    // No two rows share the same values in these columns; a row with a NULL in any of them is exempt
+    Unique(Vec<String>),
+    // The condition as SQL text, parsed when checked. A row fails only if the condition is false;
+    // if one of the values it tests is NULL the outcome is unknown and the row passes.
+    Check(String),
+}
+
+impl Constraint {
+    // Fails if one of the `candidates` (new or changed rows) breaks the constraint, given the
+    // rest of the table's rows, `others`
+    fn check<'a>(
+        &self,
+        schema: &[String],
+        others: impl Iterator<Item = &'a [Value]>,
+        candidates: &[RowRef],
+    ) -> Result<(), RqlError> {
+        match &self.kind {
+            ConstraintKind::Unique(columns) => {
+                let indices: Vec<Option<usize>> = columns.iter().map(|col| resolve_column(col, schema)).collect();
+                let mut seen: HashSet<Vec<Value>> = others.filter_map(|row| unique_key(&indices, row)).collect();
+                for row in candidates {
+                    if let Some(key) = unique_key(&indices, row.values)
+                        && !seen.insert(key.clone())
+                    {
+                        let values: Vec<String> = columns
+                            .iter()
+                            .zip(key)
+                            .map(|(col, value)| format!("{} = {}", col, Expr::Literal(value)))
+                            .collect();
+                        return Err(RqlError::ConstraintViolation(format!(
+                            "UNIQUE constraint '{}' violated: {} appears in more than one row",
+                            self.name,
+                            values.join(", ")
+                        )));
+                    }
+                }
+            }
+            ConstraintKind::Check(sql) => {
+                let cond = parser::parse_condition_text(sql)?;
+                for row in candidates {
+                    if row_matches(&cond, *row, schema, false)? {
+                        continue;
+                    }
+                    let unknown = cond
+                        .operands()
+                        .into_iter()
+                        .any(|operand| matches!(eval_expr(operand, *row, schema, false), Ok(Value::Null)));
+                    if !unknown {
+                        let values: Vec<String> =
+                            row.values.iter().map(|v| Expr::Literal(v.clone()).to_string()).collect();
+                        return Err(RqlError::ConstraintViolation(format!(
+                            "CHECK constraint '{}' ({}) failed for row ({})",
+                            self.name,
+                            sql,
+                            values.join(", ")
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// A row's values in the columns of a UNIQUE constraint, or None if one of them is NULL (such a row
+// never conflicts)
+fn unique_key(indices: &[Option<usize>], row: &[Value]) -> Option<Vec<Value>> {
+    let key: Vec<Value> =
+        indices.iter().map(|idx| idx.and_then(|i| row.get(i)).cloned().unwrap_or(Value::Null)).collect();
+    (!key.contains(&Value::Null)).then_some(key)
+}
+
+// Secondary index on one column, or on a tuple of columns
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Index {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub kind: IndexKind,
+    // Hash index: key (one value per indexed column) -> positions of the rows holding it.
+    // Both maps are rebuilt on load rather than saved.
+    #[serde(skip)]
+    pub entries: HashMap<Vec<Value>, Vec<usize>>,
+    // Sorted index: the single indexed column's value -> positions, in value order
+    #[serde(skip)]
+    pub sorted: BTreeMap<SortKey, Vec<usize>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum IndexKind {
+    Hash,   // equality lookups, on one or more columns
+    Sorted, // equality and range lookups on one column (CREATE INDEX ... USING BTREE)
+}
+
+// A value ordered the way comparisons order it (numbers by value, so 2 and 2.0 share a key)
+#[derive(Debug, Clone)]
+pub struct SortKey(pub Value);
+
+impl Ord for SortKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_values(&self.0, &other.0)
+    }
+}
+
+impl PartialOrd for SortKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for SortKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SortKey {}
+
+impl Index {
+    pub fn new(name: String, columns: Vec<String>, kind: IndexKind) -> Self {
+        Index { name, columns, kind, entries: HashMap::new(), sorted: BTreeMap::new() }
+    }
+
+    // Files a row under its key
+    fn insert(&mut self, row: &[Value], schema: &[String], pos: usize) {
+        let key = self.key(row, schema);
+        match self.kind {
+            IndexKind::Hash => self.entries.entry(key).or_default().push(pos),
+            IndexKind::Sorted => {
+                let value = key.into_iter().next().unwrap_or(Value::Null);
+                self.sorted.entry(SortKey(value)).or_default().push(pos)
+            }
+        }
+    }
+
+    // Number of distinct keys, for diagnostics
+    pub fn distinct_keys(&self) -> usize {
+        match self.kind {
+            IndexKind::Hash => self.entries.len(),
+            IndexKind::Sorted => self.sorted.len(),
+        }
+    }
+
+    // The key a row is filed under
+    fn key(&self, row: &[Value], schema: &[String]) -> Vec<Value> {
+        self.columns
+            .iter()
+            .map(|col| resolve_column(col, schema).and_then(|idx| row.get(idx)).cloned().unwrap_or(Value::Null))
+            .collect()
+    }
+}
+
+// `column REFERENCES parent_table(parent_column) [ON DELETE CASCADE]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForeignKey {
+    pub column: String,
+    pub parent_table: String,
+    pub parent_column: String,
+    pub on_delete: OnDelete,
+}
+
+impl Table {
+    // An untyped table with the given columns and no constraints
+    pub fn new(columns: Vec<String>) -> Self {
+        Table {
+            rows: vec![],
+            column_types: vec![None; columns.len()],
+            columns,
+            foreign_keys: vec![],
+            indexes: vec![],
+            constraints: vec![],
+        }
+    }
+
+    // Checks new or changed rows against every UNIQUE and CHECK constraint, reporting the first
+    // constraint broken and the values that broke it. A candidate whose rowid is that of a stored
+    // row stands in for it; any other candidate is a row about to be appended.
+    fn check_constraints(&self, candidates: &[RowRef]) -> Result<(), RqlError> {
+        if self.constraints.is_empty() {
+            return Ok(());
+        }
+        let replaced: HashSet<usize> = candidates.iter().map(|row| row.rowid - 1).collect();
+        let others = self.rows.iter().enumerate().filter(|(pos, _)| !replaced.contains(pos));
+        for constraint in &self.constraints {
+            constraint.check(&self.columns, others.clone().map(|(_, row)| row.as_slice()), candidates)?;
+        }
+        Ok(())
+    }
+
+    // Appends rows from another database. An incoming row whose UNIQUE key is held by a row
+    // already in the table (or by an earlier incoming one) is settled by `on_conflict`. Returns the
+    // positions of the rows added or replaced; CHECK constraints and foreign keys are left to the
+    // caller, and so are the indexes.
+    fn merge_rows(
+        &mut self,
+        name: &str,
+        rows: Vec<Vec<Value>>,
+        on_conflict: OnConflict,
+        report: &mut MergeReport,
+    ) -> Result<BTreeSet<usize>, RqlError> {
+        // Per UNIQUE constraint: its name, its column positions and the row holding each key
+        let uniques: Vec<(&str, Vec<Option<usize>>)> = self
+            .constraints
+            .iter()
+            .filter_map(|constraint| match &constraint.kind {
+                ConstraintKind::Unique(columns) => Some((
+                    constraint.name.as_str(),
+                    columns.iter().map(|col| resolve_column(col, &self.columns)).collect(),
+                )),
+                ConstraintKind::Check(_) => None,
+            })
+            .collect();
+        let mut holders: Vec<HashMap<Vec<Value>, usize>> = uniques
+            .iter()
+            .map(|(_, indices)| {
+                self.rows.iter().enumerate().filter_map(|(pos, row)| Some((unique_key(indices, row)?, pos))).collect()
+            })
+            .collect();
+
+        let mut changed = BTreeSet::new();
+        for row in rows {
+            // (constraint, position of the row holding the key) for every key the row repeats
+            let conflicts: Vec<(&str, usize)> = uniques
+                .iter()
+                .zip(&holders)
+                .filter_map(|((constraint, indices), holder)| {
+                    Some((*constraint, *holder.get(&unique_key(indices, &row)?)?))
+                })
+                .collect();
+            let holder_positions: BTreeSet<usize> = conflicts.iter().map(|(_, pos)| *pos).collect();
+            let describe = |row: &[Value]| {
+                let values: Vec<String> = row.iter().map(|v| Expr::Literal(v.clone()).to_string()).collect();
+                values.join(", ")
+            };
+            let pos = match (conflicts.first(), on_conflict) {
+                (None, _) => {
+                    self.rows.push(row);
+                    report.rows_added += 1;
+                    self.rows.len() - 1
+                }
+                (Some(_), OnConflict::Skip) => {
+                    report.rows_skipped += 1;
+                    continue;
+                }
+                (Some(&(_, pos)), OnConflict::Overwrite) if holder_positions.len() == 1 => {
+                    for ((_, indices), holder) in uniques.iter().zip(&mut holders) {
+                        if let Some(key) = unique_key(indices, &self.rows[pos]) {
+                            holder.remove(&key);
+                        }
+                    }
+                    self.rows[pos] = row;
+                    report.rows_replaced += 1;
+                    pos
+                }
+                (Some(_), OnConflict::Overwrite) => {
+                    return Err(RqlError::ConstraintViolation(format!(
+                        "Table '{}': row ({}) conflicts with {} different rows; ON CONFLICT OVERWRITE replaces one",
+                        name,
+                        describe(&row),
+                        holder_positions.len()
+                    )));
+                }
+                (Some(&(constraint, pos)), OnConflict::Error) => {
+                    return Err(RqlError::ConstraintViolation(format!(
+                        "Table '{}': row ({}) repeats the UNIQUE constraint '{}' key of row ({}); \
+                         use ON CONFLICT SKIP or ON CONFLICT OVERWRITE",
+                        name,
+                        describe(&row),
+                        constraint,
+                        describe(&self.rows[pos])
+                    )));
+                }
+            };
+            for ((_, indices), holder) in uniques.iter().zip(&mut holders) {
+                if let Some(key) = unique_key(indices, &self.rows[pos]) {
+                    holder.insert(key, pos);
+                }
+            }
+            changed.insert(pos);
+        }
+        Ok(changed)
+    }
+
+    // Recompute every index from scratch, e.g. after rows were removed or changed
+    pub fn rebuild_indexes(&mut self) {
+        for index in &mut self.indexes {
+            index.entries.clear();
+            index.sorted.clear();
+            for (pos, row) in self.rows.iter().enumerate() {
+                index.insert(row, &self.columns, pos);
+            }
+        }
+    }
+
+    // Copies the rows into a fresh, exactly-sized vector and rebuilds the indexes from empty maps.
+    // Returns the unused row slots and the approximate heap bytes released.
+    pub fn compact(&mut self) -> (usize, usize) {
+        let slots = self.rows.capacity() - self.rows.len();
+        let before = self.heap_bytes();
+        let mut rows = Vec::with_capacity(self.rows.len());
+        rows.extend(self.rows.drain(..).map(|mut row| {
+            for value in &mut row {
+                if let Value::Str(s) | Value::Date(s) = value {
+                    s.shrink_to_fit();
+                }
+            }
+            row.shrink_to_fit();
+            row
+        }));
+        self.rows = rows;
+        for index in &mut self.indexes {
+            index.entries = HashMap::new();
+            index.sorted = BTreeMap::new();
+        }
+        self.rebuild_indexes();
+        (slots, before.saturating_sub(self.heap_bytes()))
+    }
+
+    // Heap memory held by the rows, counting allocated capacity rather than length
+    fn heap_bytes(&self) -> usize {
+        let cells: usize = self
+            .rows
+            .iter()
+            .map(|row| {
+                let text: usize = row
+                    .iter()
+                    .map(|value| match value {
+                        Value::Str(s) | Value::Date(s) => s.capacity(),
+                        _ => 0,
+                    })
+                    .sum();
+                row.capacity() * std::mem::size_of::<Value>() + text
+            })
+            .sum();
+        self.rows.capacity() * std::mem::size_of::<Vec<Value>>() + cells
+    }
+
+    // Append rows, reserving room for all of them up front, then file the new rows in each index
+    fn append_rows(&mut self, rows: Vec<Vec<Value>>) {
+        let start = self.rows.len();
+        self.rows.reserve(rows.len());
+        self.rows.extend(rows);
+        for index in &mut self.indexes {
+            for (pos, row) in self.rows.iter().enumerate().skip(start) {
+                index.insert(row, &self.columns, pos);
+            }
+        }
+    }
+
+    // Number of columns: the declared ones, or the widest row of an implicit table
+    fn width(&self) -> usize {
+        if self.columns.is_empty() {
+            self.rows.iter().map(|r| r.len()).max().unwrap_or(0)
+        } else {
+            self.columns.len()
+        }
+    }
+}
+
+// Query behavior an embedder (or the REPL's dot commands) can change without touching the SQL
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct QueryOptions {
+    // Compare TEXT ignoring case in =, <, IN, LIKE etc. (the REPL's `.ci on`)
+    pub ignore_case: bool,
+    // Where ORDER BY puts NULLs when a key doesn't say NULLS FIRST or NULLS LAST (the REPL's `.nulls`)
+    pub null_order: NullOrder,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum NullOrder {
+    // NULL sorts as the smallest value: first with ASC, last with DESC
+    #[default]
+    Smallest,
+    First,
+    Last,
+}
+
+impl NullOrder {
+    // Whether NULLs go first for `key`, or None to sort them as the smallest value
+    fn nulls_first(self, key: &OrderKey) -> Option<bool> {
+        key.nulls_first.or(match self {
+            NullOrder::Smallest => None,
+            NullOrder::First => Some(true),
+            NullOrder::Last => Some(false),
+        })
+    }
+}
+
+impl std::str::FromStr for NullOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "smallest" => Ok(NullOrder::Smallest),
+            "first" => Ok(NullOrder::First),
+            "last" => Ok(NullOrder::Last),
+            other => Err(format!("Unknown NULL order '{}' (use first, last or smallest)", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for NullOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            NullOrder::Smallest => "smallest",
+            NullOrder::First => "first",
+            NullOrder::Last => "last",
+        })
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Database {
     pub tables: HashMap<String, Table>,
+    // Print execution diagnostics (condition parsing, row checks) to stdout
+    #[serde(skip)]
+    pub verbose: bool,
+    // Settings that change how queries compare and order values
+    #[serde(skip)]
+    pub options: QueryOptions,
+    // Let INSERT create a missing table (with no schema) instead of failing. Off by default, so a
+    // misspelled table name is an error rather than a new table.
+    #[serde(skip)]
+    pub implicit_tables: bool,
+    // Refuse every statement that would change the tables (`--readonly`); reading, and
+    // BEGIN/COMMIT/ROLLBACK, still work
+    #[serde(skip)]
+    pub read_only: bool,
+    // Keep the file `save` replaces as `<path>.bak` (`--backup`), one generation deep
+    #[serde(skip)]
+    pub keep_backup: bool,
+    // Tables as of BEGIN while a transaction is open; restored on ROLLBACK
+    #[serde(skip)]
+    snapshot: Option<HashMap<String, Table>>,
+    // Table names in the order the tables were created, kept in the saved file as the order of
+    // its tables. Tables added to `tables` directly come after these, in name order.
+    #[serde(skip)]
+    table_order: Vec<String>,
+}
+
+// Result set produced by a SELECT
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl QueryResult {
+    // Converts every row into a `T`, failing on the first row `T::from_row` rejects
+    pub fn rows_as<T: FromRow>(&self) -> Result<Vec<T>, RqlError> {
+        self.rows.iter().map(|row| T::from_row(row)).collect()
+    }
+}
+
+// The rows of a SELECT, one at a time (see `Database::select_iter`). An Err item ends the result.
+pub struct SelectRows<'a> {
+    pub columns: Vec<String>,
+    rows: Box<dyn Iterator<Item = Result<Vec<Value>, RqlError>> + 'a>,
+}
+
+impl Iterator for SelectRows<'_> {
+    type Item = Result<Vec<Value>, RqlError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rows.next()
+    }
+}
+
+// Outcome of a successfully executed statement
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExecResult {
+    Created(String),
+    // Rows removed by DELETE or TRUNCATE, or matched by UPDATE's WHERE (whether or not a value
+    // changed). Rows a DELETE removes from other tables through ON DELETE CASCADE are not counted.
+    RowsAffected(usize),
+    // Rows added by INSERT or IMPORT; ids are the 1-based positions first_id..first_id + count
+    Inserted { count: usize, first_id: usize },
+    Rows(QueryResult),
+    Message(String),
+}
+
+// What `Database::merge` did. Rows of tables only the other database had are not counted as rows.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MergeReport {
+    pub tables_added: usize,
+    pub rows_added: usize,    // appended to tables both databases have
+    pub rows_skipped: usize,  // conflicting rows dropped by ON CONFLICT SKIP
+    pub rows_replaced: usize, // existing rows replaced by ON CONFLICT OVERWRITE
+}
+
+impl std::fmt::Display for MergeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} table(s) added, {} row(s) appended, {} skipped, {} replaced",
+            self.tables_added, self.rows_added, self.rows_skipped, self.rows_replaced
+        )
+    }
+}
+
+impl ExecResult {
+    // Rows returned or changed by the statement; None for statements that don't deal in rows
+    pub fn row_count(&self) -> Option<usize> {
+        match self {
+            ExecResult::RowsAffected(n) => Some(*n),
+            ExecResult::Inserted { count, .. } => Some(*count),
+            ExecResult::Rows(result) => Some(result.rows.len()),
+            ExecResult::Created(_) | ExecResult::Message(_) => None,
+        }
+    }
+}
+
+// The handle `Database::transaction` passes to its closure. Dropping it before the closure has
+// returned (a panic unwinding through it) rolls the transaction back.
+pub struct Transaction<'a> {
+    db: &'a mut Database,
+    finished: bool,
+}
+
+impl Transaction<'_> {
+    // Tokenize, parse and execute one statement inside the transaction. BEGIN, COMMIT and
+    // ROLLBACK are refused: the closure's result decides how the transaction ends.
+    pub fn execute(&mut self, sql: &str) -> Result<ExecResult, RqlError> {
+        let statement = parser::parse(&parser::tokenize(sql)?)?;
+        if matches!(statement, Statement::Begin | Statement::Commit | Statement::Rollback) {
+            return Err(RqlError::execution("A transaction closure ends when it returns; return Err to roll back"));
+        }
+        self.db.execute(statement)
+    }
+
+    // Read the data as the transaction sees it
+    pub fn db(&self) -> &Database {
+        self.db
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.db.execute(Statement::Rollback);
+        }
+    }
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Database {
     pub fn new() -> Self {
         Database {
             tables: HashMap::new(),
+            verbose: false,
+            options: QueryOptions::default(),
+            implicit_tables: false,
+            read_only: false,
+            keep_backup: false,
+            snapshot: None,
+            table_order: vec![],
+        }
+    }
+
+    // Every table name, in the order the tables were created (see `table_order`)
+    pub fn table_names(&self) -> Vec<&str> {
+        in_creation_order(&self.table_order, &self.tables).into_iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    // A hash of every table's name, columns, column types and rows, for telling whether two
+    // databases (or one database at two moments) hold the same data. Tables are taken in name order,
+    // so creation order, indexes, constraints and the file format make no difference.
+    pub fn checksum(&self) -> u64 {
+        let mut names: Vec<&String> = self.tables.keys().collect();
+        names.sort();
+        let mut hash = wal::Fnv1a::default();
+        for name in names {
+            let table = &self.tables[name];
+            bincode::serialize_into(&mut hash, &(name, &table.columns, &table.column_types, &table.rows))
+                .expect("hashing writes cannot fail");
         }
+        hash.0
+    }
+
+    // Adds a new table, after the existing ones in creation order
+    fn add_table(&mut self, name: &str, table: Table) {
+        self.tables.insert(name.to_string(), table);
+        self.table_order.retain(|n| n != name);
+        self.table_order.push(name.to_string());
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.snapshot.is_some()
     }
 
-    pub fn execute(&mut self, stmt: Statement) {
+    pub fn execute(&mut self, stmt: Statement) -> Result<ExecResult, RqlError> {
+        if self.read_only
+            && !stmt.is_read_only()
+            && !matches!(stmt, Statement::Begin | Statement::Commit | Statement::Rollback)
+        {
+            return Err(RqlError::ReadOnly(stmt.name().to_string()));
+        }
         match stmt {
             Statement::Insert(insert_stmt) => self.execute_insert(insert_stmt),
             Statement::Select(select_stmt) => self.execute_select(select_stmt),
+            Statement::Union(union_stmt) => self.execute_union(union_stmt),
             Statement::Create(create_stmt) => self.execute_create(create_stmt),
             Statement::Delete(delete_stmt) => self.execute_delete(delete_stmt),
             Statement::Update(update_stmt) => self.execute_update(update_stmt),
+            Statement::Describe(describe_stmt) => self.execute_describe(describe_stmt),
+            Statement::ShowTables => self.execute_show_tables(),
+            Statement::Checksum => Ok(ExecResult::Message(format!("Checksum: {:016x}", self.checksum()))),
+            Statement::Explain(select_stmt) => self.execute_explain(select_stmt),
+            Statement::ExplainAnalyze(select_stmt) => self.execute_explain_analyze(select_stmt),
+            Statement::DryRun(statement) => self.execute_dry_run(*statement),
+            Statement::Import(import_stmt) => self.execute_import(import_stmt),
+            Statement::CreateIndex(index_stmt) => self.execute_create_index(index_stmt),
+            Statement::Truncate(truncate_stmt) => self.execute_truncate(truncate_stmt),
+            Statement::Vacuum => self.execute_vacuum(),
+            Statement::AlterTable(alter_stmt) => self.execute_alter_table(alter_stmt),
+            Statement::Copy(copy_stmt) => Err(RqlError::execution(format!(
+                "COPY {} FROM STDIN reads its rows in the REPL; use IMPORT to load a file",
+                copy_stmt.table_name
+            ))),
+            Statement::Dump(dump_stmt) => self.execute_dump(dump_stmt),
+            Statement::Export(export_stmt) => self.execute_export(export_stmt),
+            Statement::Diff(diff_stmt) => execute_diff(diff_stmt),
+            Statement::Merge(merge_stmt) => {
+                let other = read_database_file(&merge_stmt.path)?;
+                Ok(ExecResult::Message(self.merge(other, merge_stmt.on_conflict)?.to_string()))
+            }
+            Statement::Begin => {
+                if self.snapshot.is_some() {
+                    return Err(RqlError::execution("A transaction is already open"));
+                }
+                self.snapshot = Some(self.tables.clone());
+                Ok(ExecResult::Message("Transaction started".into()))
+            }
+            Statement::Commit => match self.snapshot.take() {
+                Some(_) => Ok(ExecResult::Message("Transaction committed".into())),
+                None => Err(RqlError::execution("No transaction is open")),
+            },
+            Statement::Rollback => match self.snapshot.take() {
+                Some(tables) => {
+                    self.tables = tables;
+                    self.table_order.retain(|name| self.tables.contains_key(name));
+                    Ok(ExecResult::Message("Transaction rolled back".into()))
+                }
+                None => Err(RqlError::execution("No transaction is open")),
+            },
         }
     }
 
-    fn execute_insert(&mut self, insert_stmt: InsertStatement) {
-        let table = self
-            .tables
-            .entry(insert_stmt.table_name.clone())
-            .or_insert(Table { rows: vec![], columns: vec![] });
+    // Run a statement that doesn't modify the database (see `Statement::is_read_only`)
+    pub fn execute_read(&self, stmt: Statement) -> Result<ExecResult, RqlError> {
+        match stmt {
+            Statement::Select(select_stmt) => self.execute_select(select_stmt),
+            Statement::Union(union_stmt) => self.execute_union(union_stmt),
+            Statement::Describe(describe_stmt) => self.execute_describe(describe_stmt),
+            Statement::ShowTables => self.execute_show_tables(),
+            Statement::Checksum => Ok(ExecResult::Message(format!("Checksum: {:016x}", self.checksum()))),
+            Statement::Explain(select_stmt) => self.execute_explain(select_stmt),
+            Statement::ExplainAnalyze(select_stmt) => self.execute_explain_analyze(select_stmt),
+            Statement::DryRun(statement) => self.execute_dry_run(*statement),
+            Statement::Dump(dump_stmt) => self.execute_dump(dump_stmt),
+            Statement::Export(export_stmt) => self.execute_export(export_stmt),
+            Statement::Diff(diff_stmt) => execute_diff(diff_stmt),
+            other => Err(RqlError::execution(format!("Not a read-only statement: {:?}", other))),
+        }
+    }
 
-        table.rows.push(insert_stmt.values);
+    // Prepare a statement with `?` placeholders for repeated execution
+    pub fn prepare(&self, sql: &str) -> Result<PreparedStatement, RqlError> {
+        PreparedStatement::new(sql)
     }
-    fn execute_select(&self, select_stmt: parser::SelectStatement) {
-        let table = match self.tables.get(&select_stmt.table_name) {
-            Some(t) => t,
-            None => {
-                println!("Table '{}' not found", select_stmt.table_name);
-                return;
-            }
+
+    // Run a prepared statement with one value per placeholder, in order
+    pub fn execute_prepared(&mut self, stmt: &PreparedStatement, params: &[Value]) -> Result<ExecResult, RqlError> {
+        self.execute(stmt.bind(params)?)
+    }
+
+    // Runs `body` inside a transaction: its changes are committed if it returns Ok, and rolled
+    // back if it returns Err or panics. Fails without running `body` if a transaction is open.
+    pub fn transaction<T>(
+        &mut self,
+        body: impl FnOnce(&mut Transaction) -> Result<T, RqlError>,
+    ) -> Result<T, RqlError> {
+        self.execute(Statement::Begin)?;
+        let mut tx = Transaction { db: self, finished: false };
+        let outcome = body(&mut tx);
+        tx.finished = true;
+        let end = if outcome.is_ok() { Statement::Commit } else { Statement::Rollback };
+        tx.db.execute(end)?;
+        outcome
+    }
+
+    // The `n` rows with the highest (`descending`) or lowest values in `column`, in that order,
+    // as ORDER BY column [DESC] would return them first. Only the winning rows are sorted, after
+    // a partial selection, so this stays cheap for small `n` on a large table.
+    pub fn top_n(
+        &self,
+        table_name: &str,
+        column: &str,
+        n: usize,
+        descending: bool,
+    ) -> Result<Vec<Vec<Value>>, RqlError> {
+        let table = match self.tables.get(table_name) {
+            Some(table) => table,
+            None => return Err(RqlError::TableNotFound(table_name.to_string())),
+        };
+        let idx = lookup_column(column, table)?;
+        // Ties are broken by position, keeping the order a stable sort would give
+        let order = |a: &(usize, &Vec<Value>), b: &(usize, &Vec<Value>)| {
+            let ord = match (a.1.get(idx), b.1.get(idx)) {
+                (Some(x), Some(y)) => sort_order(x, y),
+                (x, y) => x.is_some().cmp(&y.is_some()),
+            };
+            let ord = if descending { ord.reverse() } else { ord };
+            ord.then(a.0.cmp(&b.0))
         };
 
-        if table.rows.is_empty() {
-            println!("No rows found in table '{}'", select_stmt.table_name);
-            return;
+        let mut candidates: Vec<(usize, &Vec<Value>)> = table.rows.iter().enumerate().collect();
+        if n < candidates.len() {
+            if n == 0 {
+                return Ok(vec![]);
+            }
+            candidates.select_nth_unstable_by(n - 1, order);
+            candidates.truncate(n);
         }
+        candidates.sort_unstable_by(order);
+        Ok(candidates.into_iter().map(|(_, row)| row.clone()).collect())
+    }
 
-        // Build headers and rows as strings
-        let mut headers: Vec<String> = Vec::new();
-        let mut rows_out: Vec<Vec<String>> = Vec::new();
+    // INSERT ... SELECT runs the whole query before inserting anything, so a table can be copied
+    // into itself
+    fn execute_insert(&mut self, insert_stmt: InsertStatement) -> Result<ExecResult, RqlError> {
+        let rows = match insert_stmt.source {
+            InsertSource::Values(rows) => rows,
+            InsertSource::Select(select_stmt) => self.select_rows(*select_stmt)?.rows,
+        };
+        self.insert_many(&insert_stmt.table_name, rows)
+    }
 
-        // If SELECT * -> headers are table.columns (fallback to colN if empty)
-        if select_stmt.values.len() == 1 {
-            if let Value::Star = select_stmt.values[0] {
-                if !table.columns.is_empty() {
-                    headers = table.columns.clone();
-                } else {
-                    headers = (0..table.rows[0].len()).map(|i| format!("col{}", i)).collect();
+    // Insert a batch of rows into `table_name`, which is created if missing only when
+    // `implicit_tables` is set. Every row is checked before any is stored, so a bad row leaves the
+    // table unchanged; the rows are then appended in one go and the indexes brought up to date in
+    // a single pass.
+    pub fn insert_many(&mut self, table_name: &str, mut rows: Vec<Vec<Value>>) -> Result<ExecResult, RqlError> {
+        if self.read_only {
+            return Err(RqlError::ReadOnly("INSERT".to_string()));
+        }
+        if let Some(table) = self.tables.get(table_name) {
+            for values in &mut rows {
+                // Tables created implicitly by INSERT have no schema to check against
+                if !table.columns.is_empty() && values.len() != table.columns.len() {
+                    return Err(RqlError::execution(format!(
+                        "expected {} values, got {}",
+                        table.columns.len(),
+                        values.len()
+                    )));
                 }
+                check_types(table, values)?;
+                self.check_references(table, values)?;
+            }
+            let start = table.rows.len();
+            let candidates: Vec<RowRef> =
+                rows.iter().enumerate().map(|(i, row)| RowRef { rowid: start + i + 1, values: row }).collect();
+            table.check_constraints(&candidates)?;
+        } else if !self.implicit_tables {
+            return Err(RqlError::TableNotFound(table_name.to_string()));
+        }
 
-                for row in &table.rows {
-                    let row_str: Vec<String> = row.iter().map(|v| match v {
-                        Value::Int(i) => i.to_string(),
-                        Value::Str(s) => s.clone(),
-                        _ => String::from("NULL"),
-                    }).collect();
-                    rows_out.push(row_str);
-                }
+        if !self.tables.contains_key(table_name) {
+            self.add_table(table_name, Table::new(vec![]));
+        }
+        let table = self.tables.get_mut(table_name).expect("table exists or was just added");
+        let count = rows.len();
+        let first_id = table.rows.len() + 1;
+        table.append_rows(rows);
+        Ok(ExecResult::Inserted { count, first_id })
+    }
 
-                self.print_table(&headers, &rows_out);
-                return;
-            }
+    // Copies `other`'s tables into this database: a table only `other` has is added whole (after
+    // the existing ones), and the rows of a table both have are appended. Such a table must have
+    // the same columns and types, in the same order, in both, since rows are appended by position.
+    // An incoming row whose UNIQUE constraint key is already held is a conflict, settled by
+    // `on_conflict`; CHECK constraints and foreign keys are checked as for INSERT. On any error the
+    // database is left as it was.
+    pub fn merge(&mut self, mut other: Database, on_conflict: OnConflict) -> Result<MergeReport, RqlError> {
+        if self.read_only {
+            return Err(RqlError::ReadOnly("MERGE".to_string()));
+        }
+        let names: Vec<String> = other.table_names().into_iter().map(str::to_string).collect();
+        let mismatched = SchemaDiff {
+            changed_tables: names
+                .iter()
+                .filter_map(|name| diff_table(name.clone(), self.tables.get(name)?, &other.tables[name]))
+                .collect(),
+            ..SchemaDiff::default()
+        };
+        if !mismatched.is_empty() {
+            return Err(RqlError::execution(format!(
+                "Cannot merge: tables in both databases must have the same columns in the same order\n{}",
+                mismatched
+            )));
         }
 
-        // Otherwise explicit column selection
-        // Build headers from requested identifiers
-        for val in &select_stmt.values {
-            match val {
-                Value::Identifier(name) => {
-                    // Resolve to actual column name if possible
-                    if name.starts_with("col") {
-                        // positional
-                        if let Ok(idx) = name[3..].parse::<usize>() {
-                            if idx < table.columns.len() {
-                                headers.push(table.columns[idx].clone());
-                            } else {
-                                headers.push(name.clone());
-                            }
-                        } else {
-                            headers.push(name.clone());
-                        }
-                    } else if let Some(idx) = table.columns.iter().position(|c| c == name) {
-                        headers.push(table.columns[idx].clone());
-                    } else {
-                        headers.push(name.clone());
+        let saved = (self.tables.clone(), self.table_order.clone());
+        let mut report = MergeReport::default();
+        let mut changed = vec![];
+        let mut outcome = Ok(());
+        for name in names {
+            let theirs = other.tables.remove(&name).expect("table_names lists existing tables");
+            match self.tables.get_mut(&name) {
+                Some(ours) => match ours.merge_rows(&name, theirs.rows, on_conflict, &mut report) {
+                    Ok(positions) => {
+                        ours.rebuild_indexes();
+                        changed.push((name, positions));
                     }
+                    Err(e) => {
+                        outcome = Err(e);
+                        break;
+                    }
+                },
+                None => {
+                    let positions = (0..theirs.rows.len()).collect();
+                    self.add_table(&name, theirs);
+                    report.tables_added += 1;
+                    changed.push((name, positions));
                 }
-                _ => {}
             }
         }
-
-        // For each row, extract the requested columns
-        for row in &table.rows {
-            let mut row_strs: Vec<String> = Vec::new();
-            for val in &select_stmt.values {
-                match val {
-                    Value::Identifier(name) => {
-                        let col_index = if name.starts_with("col") {
-                            name[3..].parse::<usize>().unwrap_or(0)
-                        } else {
-                            table.columns.iter().position(|c| c == name).unwrap_or(0)
-                        };
-                        if let Some(cell) = row.get(col_index) {
-                            match cell {
-                                Value::Int(i) => row_strs.push(i.to_string()),
-                                Value::Str(s) => row_strs.push(s.clone()),
-                                _ => row_strs.push(String::from("NULL")),
-                            }
-                        } else {
-                            row_strs.push(String::new());
-                        }
-                    }
-                    _ => {}
-                }
+        // With every table's rows in place, a row may reference one merged after it
+        if outcome.is_ok() {
+            outcome = changed.iter().try_for_each(|(name, positions)| {
+                let table = &self.tables[name];
+                let candidates: Vec<RowRef> = positions.iter().map(|&pos| RowRef::at(table, pos)).collect();
+                table.check_constraints(&candidates)?;
+                positions.iter().try_for_each(|&pos| self.check_references(table, &table.rows[pos]))
+            });
+        }
+        match outcome {
+            Ok(()) => Ok(report),
+            Err(e) => {
+                (self.tables, self.table_order) = saved;
+                Err(e)
             }
-            rows_out.push(row_strs);
         }
+    }
 
-        self.print_table(&headers, &rows_out);
+    fn execute_select(&self, select_stmt: parser::SelectStatement) -> Result<ExecResult, RqlError> {
+        Ok(ExecResult::Rows(self.select_rows(select_stmt)?))
     }
 
-    // Helper: pretty-print table
-    fn print_table(&self, headers: &[String], rows: &[Vec<String>]) {
-        // compute column widths
-        let cols = headers.len();
-        let mut widths = headers.iter().map(|h| h.len()).collect::<Vec<usize>>();
-        for row in rows {
-            for (i, cell) in row.iter().enumerate().take(cols) {
-                if cell.len() > widths[i] {
-                    widths[i] = cell.len();
-                }
-            }
+    // The rows of a SELECT, produced as the iterator is advanced. A plain SELECT from one table
+    // filters and projects each row only when it is asked for, so taking the first few rows (or a
+    // LIMIT) never looks at the rest of the table. ORDER BY, GROUP BY, aggregates and joins need
+    // every matching row before the first can be returned, so their whole result is built up front.
+    pub fn select_iter(&self, mut select_stmt: parser::SelectStatement) -> Result<SelectRows<'_>, RqlError> {
+        // Aggregates without GROUP BY summarise all matching rows as a single group
+        let aggregated = select_stmt.is_aggregated();
+        let buffered = aggregated || !select_stmt.group_by.is_empty() || !select_stmt.order_by.is_empty();
+        if buffered || !select_stmt.joins.is_empty() {
+            let result = self.select_buffered(select_stmt, aggregated, None)?;
+            return Ok(SelectRows { columns: result.columns, rows: Box::new(result.rows.into_iter().map(Ok)) });
         }
 
-        // horizontal border builders
-        let mut sep = String::new();
-        sep.push('+');
-        for w in &widths {
-            sep.push_str(&"-".repeat(*w + 2));
-            sep.push('+');
+        let table = match self.tables.get(&select_stmt.table_name) {
+            Some(table) => table,
+            None => return Err(RqlError::TableNotFound(select_stmt.table_name.clone())),
+        };
+        normalize_qualifiers(&mut select_stmt, &table.columns)?;
+        check_select_columns(&select_stmt, table)?;
+        if select_stmt.having.is_some() {
+            return Err(RqlError::execution("HAVING requires GROUP BY"));
         }
+        let condition = select_stmt.condition.map(|cond| self.run_subqueries(cond)).transpose()?;
+        let projection = Projection::new(select_stmt.items, table)?;
+        let columns = projection.headers.clone();
+
+        let ignore_case = self.options.ignore_case;
+        let candidates: Box<dyn Iterator<Item = usize>> =
+            match condition.as_ref().and_then(|cond| plan_index(table, cond, ignore_case)) {
+                Some(plan) => Box::new(index_positions(table, plan, false).into_iter()),
+                None => Box::new(0..table.rows.len()),
+            };
+        // OFFSET skips matching rows, never an error
+        let mut to_skip = select_stmt.offset;
+        let rows = candidates
+            .filter_map(move |pos| {
+                let row = RowRef::at(table, pos);
+                match condition.as_ref().map(|cond| row_matches(cond, row, &table.columns, ignore_case)) {
+                    Some(Ok(false)) => None,
+                    Some(Err(e)) => Some(Err(e)),
+                    None | Some(Ok(true)) => Some(Ok(row)),
+                }
+            })
+            .filter(move |row| match row {
+                Ok(_) if to_skip > 0 => {
+                    to_skip -= 1;
+                    false
+                }
+                _ => true,
+            })
+            .take(select_stmt.limit.unwrap_or(usize::MAX))
+            .map(move |row| projection.project(row?, &table.columns, ignore_case));
+        Ok(SelectRows { columns, rows: Box::new(rows) })
+    }
 
-        // print header
-        println!("{}", sep);
-        // header row
-        let mut header_row = String::from("|");
-        for (i, h) in headers.iter().enumerate() {
-            let pad = widths[i].saturating_sub(h.len());
-            header_row.push(' ');
-            header_row.push_str(h);
-            header_row.push_str(&" ".repeat(pad + 1));
-            header_row.push('|');
+    // A SELECT whose rows all have to be seen first: one with ORDER BY, grouping or joins. With a
+    // profile (EXPLAIN ANALYZE) each phase is timed and its row count recorded.
+    fn select_buffered(
+        &self,
+        mut select_stmt: parser::SelectStatement,
+        aggregated: bool,
+        mut profile: Option<&mut Profile>,
+    ) -> Result<QueryResult, RqlError> {
+        let started = Instant::now();
+        let base_columns = self.tables.get(&select_stmt.table_name).map(|t| t.columns.clone()).unwrap_or_default();
+        normalize_qualifiers(&mut select_stmt, &base_columns)?;
+        let source = self.source_table(&select_stmt)?;
+        let table = source.as_ref();
+        check_select_columns(&select_stmt, table)?;
+        if let Some(profile) = profile.as_deref_mut() {
+            let joined: Vec<&str> = select_stmt.joins.iter().map(|join| join.table_name.as_str()).collect();
+            let scan = if joined.is_empty() {
+                format!("Scan '{}'", select_stmt.table_name)
+            } else {
+                format!("Scan '{}' joined with '{}'", select_stmt.table_name, joined.join("', '"))
+            };
+            profile.examined = table.rows.len();
+            profile.record(scan, table.rows.len(), started);
         }
-        println!("{}", header_row);
-        println!("{}", sep);
 
-        // print rows
-        for row in rows {
-            let mut row_line = String::from("|");
-            for (i, cell) in row.iter().enumerate().take(cols) {
-                let pad = widths[i].saturating_sub(cell.len());
-                row_line.push(' ');
-                row_line.push_str(cell);
-                row_line.push_str(&" ".repeat(pad + 1));
-                row_line.push('|');
-            }
-            println!("{}", row_line);
-        }
-        println!("{}", sep);
-    }
-fn execute_create(&mut self, create_stmt: parser::CreateTableStatement) {
-        self.tables.insert(
-            create_stmt.table_name, 
-            Table { 
-                rows: vec![], 
-                columns: create_stmt.columns 
-            }
-        );
-    }
-
-    fn execute_delete(&mut self, delete_stmt: parser::DeleteStatement) {
-        if let Some(table) = self.tables.get_mut(&delete_stmt.table_name) {
-            let cond = delete_stmt.condition.trim();
-            println!("Delete condition: '{}'", cond);
-            if cond.is_empty() {
-                println!("No condition provided. Nothing to delete.");
-                return;
-            }
-            if let Some(eq_pos) = cond.find('=') {
-                let (col_part, val_part) = cond.split_at(eq_pos);
-                let col_part = col_part.trim();
-                let val_part = val_part[1..].trim(); // skip '='
-
-                // Resolve column name or index
-                let col_index = if col_part.starts_with("col") {
-                    col_part[3..].parse::<usize>().unwrap_or(0)
-                } else {
-                    // Try to find column by name
-                    table.columns.iter().position(|c| c == col_part).unwrap_or(0)
-                };
-                println!("Column index: {}", col_index);
+        let started = Instant::now();
+        select_stmt.condition = select_stmt.condition.map(|cond| self.run_subqueries(cond)).transpose()?;
+        select_stmt.having = select_stmt.having.map(|cond| self.run_subqueries(cond)).transpose()?;
+        // Apply the WHERE filter before projecting
+        let mut matching: Vec<RowRef> =
+            matching_positions(table, select_stmt.condition.as_ref(), false, self.options.ignore_case)?
+                .into_iter()
+                .map(|pos| RowRef::at(table, pos))
+                .collect();
+        if let Some(profile) = profile.as_deref_mut()
+            && let Some(cond) = &select_stmt.condition
+        {
+            let ignore_case = self.options.ignore_case;
+            profile.examined = match plan_index(table, cond, ignore_case) {
+                Some(plan) => index_positions(table, plan, false).len(),
+                None => table.rows.len(),
+            };
+            profile.record(filter_step(table, cond, ignore_case), matching.len(), started);
+        }
 
-                let cond_value = if val_part.starts_with("'") && val_part.ends_with("'") {
-                    Value::Str(val_part.trim_matches('\'').to_string())
-                } else if let Ok(i) = val_part.parse::<i32>() {
-                    Value::Int(i)
+        if !select_stmt.group_by.is_empty() || aggregated {
+            let started = Instant::now();
+            let mut result = match execute_grouped_select(&select_stmt, table, matching, self.options)? {
+                ExecResult::Rows(result) => result,
+                _ => return Err(RqlError::execution("SELECT did not produce rows")),
+            };
+            if let Some(profile) = profile.as_deref_mut() {
+                let step = if select_stmt.group_by.is_empty() {
+                    "Aggregate all rows into one".to_string()
                 } else {
-                    Value::Str(val_part.to_string())
+                    format!("Group by: {}", select_stmt.group_by.join(", "))
                 };
-                println!("Condition value: {:?}", cond_value);
-
-                let before = table.rows.len();
-                table.rows.retain(|row| {
-                    if let Some(row_val) = row.get(col_index) {
-                        println!("Checking row value: {:?} against {:?}", row_val, cond_value);
-                        row_val != &cond_value
-                    } else {
-                        true
-                    }
-                });
-                let after = table.rows.len();
-                println!("Rows before: {}, after: {}", before, after);
-            } else {
-                println!("No '=' found in condition. Nothing deleted.");
+                profile.record(step, result.rows.len(), started);
             }
-        } else {
-            println!("Table '{}' not found.", delete_stmt.table_name);
+            let limit = select_stmt.limit.unwrap_or(usize::MAX);
+            result.rows = result.rows.into_iter().skip(select_stmt.offset).take(limit).collect();
+            return Ok(result);
+        }
+        if select_stmt.having.is_some() {
+            return Err(RqlError::execution("HAVING requires GROUP BY"));
         }
-    }
 
-    fn execute_update(&mut self, update_stmt: parser::UpdateStatement) {
-        if let Some(table) = self.tables.get_mut(&update_stmt.table_name) {
-            // Parse SET clause (e.g., "col0 = 123" or "name = 'Bob'")
-            let set_parts: Vec<&str> = update_stmt.set_clause.split('=').collect();
-            if set_parts.len() != 2 {
-                println!("Invalid SET clause format");
-                return;
+        // ORDER BY: compare key by key; the sort is stable so ties keep insertion order.
+        // A key naming a column alias sorts by that item's expression.
+        if !select_stmt.order_by.is_empty() {
+            let started = Instant::now();
+            let key_exprs: Vec<Expr> = select_stmt
+                .order_by
+                .iter()
+                .map(|key| {
+                    aliased_expr(&select_stmt.items, &key.column).unwrap_or_else(|| Expr::Column(key.column.clone()))
+                })
+                .collect();
+            let mut keyed = matching
+                .into_iter()
+                .map(|row| {
+                    let key = key_exprs
+                        .iter()
+                        .map(|expr| eval_expr(expr, row, &table.columns, self.options.ignore_case))
+                        .collect::<Result<_, RqlError>>()?;
+                    Ok(SortEntry { key, row })
+                })
+                .collect::<Result<Vec<SortEntry>, RqlError>>()?;
+            let keys: Vec<(usize, &OrderKey)> = select_stmt.order_by.iter().enumerate().collect();
+            sort_by_keys(&mut keyed, &keys, self.options.null_order);
+            matching = keyed.into_iter().map(|entry| entry.row).collect();
+            if let Some(profile) = profile.as_deref_mut() {
+                profile.record(sort_step(&select_stmt.order_by), matching.len(), started);
             }
-            
-            let set_col = set_parts[0].trim();
-            let set_val = set_parts[1].trim();
-            
-            // Resolve SET column
-            let set_col_index = if set_col.starts_with("col") {
-                set_col[3..].parse::<usize>().unwrap_or(0)
-            } else {
-                table.columns.iter().position(|c| c == set_col).unwrap_or(0)
-            };
-            
-            // Parse SET value
-            let new_value = if set_val.starts_with("'") && set_val.ends_with("'") {
-                Value::Str(set_val.trim_matches('\'').to_string())
-            } else if let Ok(i) = set_val.parse::<i32>() {
-                Value::Int(i)
-            } else {
-                Value::Str(set_val.to_string())
-            };
-
-            // Parse WHERE condition if present
-            let cond = update_stmt.condition.trim();
-            if cond.is_empty() {
-                // Update all rows
-                for row in &mut table.rows {
-                    if let Some(cell) = row.get_mut(set_col_index) {
-                        *cell = new_value.clone();
-                    }
-                }
-                println!("Updated all rows");
-            } else if let Some(eq_pos) = cond.find('=') {
-                let (col_part, val_part) = cond.split_at(eq_pos);
-                let col_part = col_part.trim();
-                let val_part = val_part[1..].trim();
+        }
+        let limit = select_stmt.limit.unwrap_or(usize::MAX);
+        let matching = matching.into_iter().skip(select_stmt.offset).take(limit);
 
-                // Resolve WHERE column
-                let where_col_index = if col_part.starts_with("col") {
-                    col_part[3..].parse::<usize>().unwrap_or(0)
-                } else {
-                    table.columns.iter().position(|c| c == col_part).unwrap_or(0)
-                };
+        let started = Instant::now();
+        let projection = Projection::new(select_stmt.items, table)?;
+        let rows: Vec<Vec<Value>> = matching
+            .map(|row| projection.project(row, &table.columns, self.options.ignore_case))
+            .collect::<Result<_, RqlError>>()?;
+        if let Some(profile) = profile {
+            profile.record("Project".to_string(), rows.len(), started);
+        }
+        Ok(QueryResult { columns: projection.headers, rows })
+    }
 
-                // Parse WHERE value
-                let cond_value = if val_part.starts_with("'") && val_part.ends_with("'") {
-                    Value::Str(val_part.trim_matches('\'').to_string())
-                } else if let Ok(i) = val_part.parse::<i32>() {
-                    Value::Int(i)
-                } else {
-                    Value::Str(val_part.to_string())
-                };
+    // The table a SELECT reads from. With joins this is a temporary table holding every
+    // combination of rows that satisfies the ON conditions, whose columns are named
+    // `table.column` (or `alias.column` for an aliased table).
+    fn source_table(&self, select_stmt: &parser::SelectStatement) -> Result<Cow<'_, Table>, RqlError> {
+        let table = match self.tables.get(&select_stmt.table_name) {
+            Some(t) => t,
+            None => return Err(RqlError::TableNotFound(select_stmt.table_name.clone())),
+        };
+        if select_stmt.joins.is_empty() {
+            return Ok(Cow::Borrowed(table));
+        }
 
-                // Update matching rows
-                let mut count = 0;
-                for row in &mut table.rows {
-                    if let Some(row_val) = row.get(where_col_index) {
-                        if row_val == &cond_value {
-                            if let Some(cell) = row.get_mut(set_col_index) {
-                                *cell = new_value.clone();
-                                count += 1;
-                            }
-                        }
-                    }
-                }
-                println!("Updated {} rows", count);
+        let first = select_stmt.table_alias.as_ref().unwrap_or(&select_stmt.table_name);
+        let mut seen = HashSet::from([first.as_str()]);
+        let mut combined = qualified_table(first, table);
+        for join in &select_stmt.joins {
+            let name = join.alias.as_ref().unwrap_or(&join.table_name);
+            if !seen.insert(name.as_str()) {
+                return Err(RqlError::execution(format!(
+                    "Table name '{}' appears more than once in the join; give each one an alias",
+                    name
+                )));
             }
-        } else {
-            println!("Table '{}' not found", update_stmt.table_name);
+            let right = match self.tables.get(&join.table_name) {
+                Some(t) => t,
+                None => return Err(RqlError::TableNotFound(join.table_name.clone())),
+            };
+            let on = match &join.kind {
+                JoinKind::Cross => None,
+                JoinKind::Inner(cond) => Some(self.run_subqueries(cond.clone())?),
+            };
+            combined = join_tables(combined, qualified_table(name, right), on.as_ref(), self.options.ignore_case)?;
         }
+        Ok(Cow::Owned(combined))
     }
 
-    // Save database to file
-    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let encoded = bincode::serialize(&self)?;
-        std::fs::write(path, encoded)?;
-        Ok(())
+    // Concatenates the results of each SELECT in turn; UNION (without ALL) also drops every
+    // row already produced. Column names come from the first SELECT.
+    fn execute_union(&self, union_stmt: parser::UnionStatement) -> Result<ExecResult, RqlError> {
+        let mut result = self.select_rows(union_stmt.first)?;
+        for (all, select_stmt) in union_stmt.rest {
+            let next = self.select_rows(select_stmt)?;
+            if next.columns.len() != result.columns.len() {
+                return Err(RqlError::execution(format!(
+                    "UNION requires the same number of columns on each side ({} vs {})",
+                    result.columns.len(),
+                    next.columns.len()
+                )));
+            }
+            result.rows.extend(next.rows);
+            if !all {
+                let mut seen = HashSet::new();
+                result.rows.retain(|row| seen.insert(row.clone()));
+            }
+        }
+        Ok(ExecResult::Rows(result))
+    }
+
+    // Runs a SELECT and returns its result set
+    fn select_rows(&self, select_stmt: parser::SelectStatement) -> Result<QueryResult, RqlError> {
+        let rows = self.select_iter(select_stmt)?;
+        let columns = rows.columns.clone();
+        Ok(QueryResult { columns, rows: rows.collect::<Result<_, RqlError>>()? })
+    }
+
+    // Runs each `IN (SELECT ...)` subquery of a condition once, turning it into a list of values
+    fn run_subqueries(&self, cond: Condition) -> Result<Condition, RqlError> {
+        let (left, subquery, negated) = match cond {
+            Condition::InSelect { left, subquery, negated } => (left, subquery, negated),
+            Condition::And(left, right) => {
+                return Ok(Condition::And(
+                    Box::new(self.run_subqueries(*left)?),
+                    Box::new(self.run_subqueries(*right)?),
+                ))
+            }
+            Condition::Or(left, right) => {
+                return Ok(Condition::Or(
+                    Box::new(self.run_subqueries(*left)?),
+                    Box::new(self.run_subqueries(*right)?),
+                ))
+            }
+            other => return Ok(other),
+        };
+        let result = self.select_rows(*subquery)?;
+        if result.columns.len() != 1 {
+            return Err(RqlError::execution(format!(
+                "Subquery must return exactly one column, got {}",
+                result.columns.len()
+            )));
+        }
+        let values = result.rows.into_iter().filter_map(|row| row.into_iter().next()).collect();
+        Ok(Condition::InList { left, values, negated })
+    }
+
+    fn execute_describe(&self, describe_stmt: parser::DescribeStatement) -> Result<ExecResult, RqlError> {
+        let table = match self.tables.get(&describe_stmt.table_name) {
+            Some(t) => t,
+            None => return Err(RqlError::TableNotFound(describe_stmt.table_name.clone())),
+        };
+
+        // Implicitly created tables have no names; describe them positionally, as SELECT * shows them
+        let names: Vec<String> = if table.columns.is_empty() {
+            (0..table.width()).map(|i| format!("col{}", i)).collect()
+        } else {
+            table.columns.clone()
+        };
+
+        Ok(ExecResult::Rows(QueryResult {
+            columns: vec!["Column".to_string()],
+            rows: names.into_iter().map(|n| vec![Value::Str(n)]).collect(),
+        }))
+    }
+
+    fn execute_show_tables(&self) -> Result<ExecResult, RqlError> {
+        let mut names: Vec<&String> = self.tables.keys().collect();
+        names.sort();
+
+        Ok(ExecResult::Rows(QueryResult {
+            columns: vec!["Tables".to_string()],
+            rows: names.into_iter().map(|n| vec![Value::Str(n.clone())]).collect(),
+        }))
+    }
+
+    // One row per column of `table_name`: its declared type, how many distinct non-NULL values and
+    // how many NULLs it holds, the smallest and largest value when every value is a number, and
+    // the indexes covering it. The REPL's `.stats` shows it.
+    pub fn column_stats(&self, table_name: &str) -> Result<QueryResult, RqlError> {
+        let table = self.tables.get(table_name).ok_or_else(|| RqlError::TableNotFound(table_name.to_string()))?;
+        let names: Vec<String> = if table.columns.is_empty() {
+            (0..table.width()).map(|i| format!("col{}", i)).collect()
+        } else {
+            table.columns.clone()
+        };
+        let headers = ["Column", "Type", "Distinct", "NULLs", "Min", "Max", "Indexes"];
+
+        let mut rows = vec![];
+        for (idx, name) in names.iter().enumerate() {
+            let present: Vec<&Value> =
+                table.rows.iter().filter_map(|row| row.get(idx)).filter(|v| **v != Value::Null).collect();
+            let distinct: HashSet<&Value> = present.iter().copied().collect();
+            let nulls = table.rows.len() - present.len();
+            let numeric = !present.is_empty() && present.iter().all(|v| matches!(v, Value::Int(_) | Value::Float(_)));
+            let extreme = |wanted: Ordering| -> Value {
+                let found = present.iter().copied().reduce(|a, b| if compare_values(b, a) == wanted { b } else { a });
+                found.filter(|_| numeric).cloned().unwrap_or(Value::Null)
+            };
+            let indexes: Vec<&str> = table
+                .indexes
+                .iter()
+                .filter(|index| index.columns.iter().any(|col| resolve_column(col, &table.columns) == Some(idx)))
+                .map(|index| index.name.as_str())
+                .collect();
+            rows.push(vec![
+                Value::Str(name.clone()),
+                table.column_types.get(idx).copied().flatten().map_or(Value::Null, |t| Value::Str(t.to_string())),
+                Value::Int(i32::try_from(distinct.len()).unwrap_or(i32::MAX)),
+                Value::Int(i32::try_from(nulls).unwrap_or(i32::MAX)),
+                extreme(Ordering::Less),
+                extreme(Ordering::Greater),
+                if indexes.is_empty() { Value::Null } else { Value::Str(indexes.join(", ")) },
+            ]);
+        }
+        Ok(QueryResult { columns: headers.iter().map(|h| h.to_string()).collect(), rows })
+    }
+
+    // The SQL that recreates a table (or every table) when run on an empty database: CREATE TABLE,
+    // an INSERT per row, then the indexes and constraints. Tables come in the order they were
+    // created, except that a table referenced by a foreign key comes before the tables referencing it.
+    fn execute_dump(&self, dump_stmt: DumpStatement) -> Result<ExecResult, RqlError> {
+        let mut order = vec![];
+        match &dump_stmt.table_name {
+            Some(name) if !self.tables.contains_key(name) => return Err(RqlError::TableNotFound(name.clone())),
+            Some(name) => order.push(name.as_str()),
+            None => {
+                for name in self.table_names() {
+                    self.push_with_parents(name, &mut order);
+                }
+            }
+        }
+
+        let mut sql = vec![];
+        for name in order {
+            dump_table(name, &self.tables[name], &mut sql);
+        }
+        Ok(ExecResult::Message(sql.join("\n")))
+    }
+
+    // Appends `name` to `order` after the tables it references, skipping those already there.
+    // A foreign key can only name a table that existed already (or the table itself), so
+    // references never form a cycle.
+    fn push_with_parents<'a>(&'a self, name: &'a str, order: &mut Vec<&'a str>) {
+        if order.contains(&name) {
+            return;
+        }
+        for fk in &self.tables[name].foreign_keys {
+            if fk.parent_table != name && self.tables.contains_key(&fk.parent_table) {
+                self.push_with_parents(&fk.parent_table, order);
+            }
+        }
+        order.push(name);
+    }
+
+    // Describes, one step per row, how execute_select would run the query
+    fn execute_explain(&self, mut select_stmt: parser::SelectStatement) -> Result<ExecResult, RqlError> {
+        let table = match self.tables.get(&select_stmt.table_name) {
+            Some(t) => t,
+            None => return Err(RqlError::TableNotFound(select_stmt.table_name.clone())),
+        };
+        normalize_qualifiers(&mut select_stmt, &table.columns)?;
+
+        let alias = select_stmt.table_alias.as_ref().map(|a| format!(" as '{}'", a)).unwrap_or_default();
+        let mut plan = vec![format!("Table '{}'{} ({} rows)", select_stmt.table_name, alias, table.rows.len())];
+        let source = self.source_table(&select_stmt)?;
+        let table = source.as_ref();
+        check_select_columns(&select_stmt, table)?;
+        for join in &select_stmt.joins {
+            let rows = self.tables.get(&join.table_name).map_or(0, |t| t.rows.len());
+            let alias = join.alias.as_ref().map(|a| format!(" as '{}'", a)).unwrap_or_default();
+            match &join.kind {
+                JoinKind::Cross => plan.push(format!("Cross join '{}'{} ({} rows)", join.table_name, alias, rows)),
+                JoinKind::Inner(cond) => {
+                    plan.push(format!("Join '{}'{} ({} rows) on {}", join.table_name, alias, rows, cond))
+                }
+            }
+        }
+        if !select_stmt.joins.is_empty() {
+            plan.push(format!("Combined rows: {}", table.rows.len()));
+        }
+        if let Some(cond) = &select_stmt.condition {
+            plan.push(filter_step(table, cond, self.options.ignore_case));
+        }
+        let aggregated = select_stmt.is_aggregated();
+        if !select_stmt.group_by.is_empty() {
+            plan.push(format!("Group by: {}", select_stmt.group_by.join(", ")));
+        } else if aggregated {
+            plan.push("Aggregate all rows into one".to_string());
+        }
+        if let Some(cond) = &select_stmt.having {
+            plan.push(format!("Filter groups: {}", cond));
+        }
+        if select_stmt.order_by.is_empty() {
+            plan.push("No sort needed".to_string());
+        } else {
+            plan.push(sort_step(&select_stmt.order_by));
+        }
+        let items: Vec<String> = select_stmt.items.iter().map(|item| item.to_string()).collect();
+        plan.push(format!("Project: {}", items.join(", ")));
+
+        Ok(ExecResult::Rows(QueryResult {
+            columns: vec!["Plan".to_string()],
+            rows: plan.into_iter().map(|step| vec![Value::Str(step)]).collect(),
+        }))
+    }
+
+    // EXPLAIN ANALYZE: runs the SELECT and reports, instead of its rows, each phase with the rows it
+    // produced and the time it took, then how many rows were examined and returned
+    fn execute_explain_analyze(&self, select_stmt: parser::SelectStatement) -> Result<ExecResult, RqlError> {
+        let aggregated = select_stmt.is_aggregated();
+        let mut profile = Profile::default();
+        let started = Instant::now();
+        // Timing every phase means seeing every row first, as ORDER BY does
+        let result = self.select_buffered(select_stmt, aggregated, Some(&mut profile))?;
+        let total = started.elapsed();
+
+        let count = |n: usize| Value::Int(i32::try_from(n).unwrap_or(i32::MAX));
+        let millis = |time: Duration| Value::Float((time.as_secs_f64() * 1e6).round() / 1e3);
+        let mut rows: Vec<Vec<Value>> = profile
+            .phases
+            .into_iter()
+            .map(|(phase, n, time)| vec![Value::Str(phase), count(n), millis(time)])
+            .collect();
+        rows.push(vec![Value::Str("Rows examined".to_string()), count(profile.examined), Value::Null]);
+        rows.push(vec![Value::Str("Rows returned".to_string()), count(result.rows.len()), millis(total)]);
+        Ok(ExecResult::Rows(QueryResult {
+            columns: vec!["Phase".to_string(), "Rows".to_string(), "Time (ms)".to_string()],
+            rows,
+        }))
+    }
+
+    // EXPLAIN DELETE / EXPLAIN UPDATE: finds the rows the statement would change, running every
+    // check the statement itself would (so a DELETE blocked by a foreign key fails here too), and
+    // reports them as plan steps without touching the table
+    fn execute_dry_run(&self, statement: Statement) -> Result<ExecResult, RqlError> {
+        let (table_name, condition) = match &statement {
+            Statement::Delete(delete_stmt) => (&delete_stmt.table_name, &delete_stmt.condition),
+            Statement::Update(update_stmt) => (&update_stmt.table_name, &update_stmt.condition),
+            other => return Err(RqlError::execution(format!("EXPLAIN cannot dry-run {}", other.name()))),
+        };
+        let table = self.tables.get(table_name).ok_or_else(|| RqlError::TableNotFound(table_name.clone()))?;
+        let mut plan = vec![format!("Table '{}' ({} rows)", table_name, table.rows.len())];
+        if let Some(cond) = condition {
+            plan.push(filter_step(table, cond, self.options.ignore_case));
+        }
+
+        match &statement {
+            Statement::Delete(delete_stmt) => {
+                let doomed = self.planned_delete(delete_stmt)?;
+                plan.push(format!("Rows to delete: {}", doomed.get(table_name).map_or(0, |rows| rows.len())));
+                let mut cascaded: Vec<_> = doomed.iter().filter(|(name, _)| *name != table_name).collect();
+                cascaded.sort_by_key(|(name, _)| name.as_str());
+                for (name, rows) in cascaded {
+                    plan.push(format!("Rows to delete from '{}' (ON DELETE CASCADE): {}", name, rows.len()));
+                }
+            }
+            Statement::Update(update_stmt) => {
+                let (positions, _) = self.planned_update(update_stmt)?;
+                plan.push(format!("Rows to update: {}", positions.len()));
+                let assignments: Vec<String> =
+                    update_stmt.set_clause.iter().map(|(col, expr)| format!("{} = {}", col, expr)).collect();
+                plan.push(format!("Set: {}", assignments.join(", ")));
+            }
+            _ => unreachable!("only DELETE and UPDATE reach this point"),
+        }
+
+        Ok(ExecResult::Rows(QueryResult {
+            columns: vec!["Plan".to_string()],
+            rows: plan.into_iter().map(|step| vec![Value::Str(step)]).collect(),
+        }))
+    }
+
+    // Loads a CSV or JSON file (see `DataFormat`) into a table, which is created if missing;
+    // either every row is imported or, on the first bad row, none are.
+    fn execute_import(&mut self, import_stmt: parser::ImportStatement) -> Result<ExecResult, RqlError> {
+        let text = std::fs::read_to_string(&import_stmt.path)
+            .map_err(|e| RqlError::execution(format!("Could not read '{}': {}", import_stmt.path, e)))?;
+        let (header, rows) = match DataFormat::for_path(&import_stmt.path).unwrap_or(DataFormat::Csv) {
+            DataFormat::Csv => csv_rows(&text)?,
+            DataFormat::Json => json_rows(&text, self.tables.get(&import_stmt.table_name))?,
+        };
+        let Some(header) = header else {
+            return Err(RqlError::execution(format!("'{}' is empty", import_stmt.path)));
+        };
+
+        let name = import_stmt.table_name;
+        let created = !self.tables.contains_key(&name);
+        if created {
+            self.add_table(&name, Table::new(header.clone()));
+        } else if self.tables[&name].columns.len() != header.len() {
+            return Err(RqlError::execution(format!(
+                "'{}' has {} columns but table '{}' has {}",
+                import_stmt.path,
+                header.len(),
+                name,
+                self.tables[&name].columns.len()
+            )));
+        }
+        let before = self.tables[&name].rows.len();
+
+        let mut outcome = Ok(());
+        for (location, values) in rows {
+            if values.len() != header.len() {
+                outcome = Err(RqlError::execution(format!(
+                    "{}: expected {} fields, got {}",
+                    location,
+                    header.len(),
+                    values.len()
+                )));
+                break;
+            }
+            if let Err(e) = self.insert_many(&name, vec![values]) {
+                outcome = Err(RqlError::execution(format!("{}: {}", location, e)));
+                break;
+            }
+        }
+
+        match outcome {
+            Ok(()) => Ok(ExecResult::Inserted { count: self.tables[&name].rows.len() - before, first_id: before + 1 }),
+            Err(e) => {
+                if created {
+                    self.tables.remove(&name);
+                    self.table_order.retain(|n| *n != name);
+                } else if let Some(table) = self.tables.get_mut(&name) {
+                    table.rows.truncate(before);
+                    table.rebuild_indexes();
+                }
+                Err(e)
+            }
+        }
+    }
+
+    // Writes a table to a CSV or JSON file (see `DataFormat`) that IMPORT reads back as the same
+    // rows, returning how many rows were written
+    fn execute_export(&self, export_stmt: parser::ExportStatement) -> Result<ExecResult, RqlError> {
+        let table = match self.tables.get(&export_stmt.table_name) {
+            Some(t) => t,
+            None => return Err(RqlError::TableNotFound(export_stmt.table_name.clone())),
+        };
+        let columns: Vec<String> =
+            (0..table.width()).map(|i| table.columns.get(i).cloned().unwrap_or_else(|| format!("col{}", i))).collect();
+        let text = match DataFormat::for_path(&export_stmt.path).unwrap_or(DataFormat::Csv) {
+            DataFormat::Csv => csv_text(&columns, &table.rows),
+            DataFormat::Json => {
+                // Short rows of an implicit table are padded with NULL so every object has every key
+                let rows = table
+                    .rows
+                    .iter()
+                    .map(|row| (0..columns.len()).map(|i| row.get(i).cloned().unwrap_or(Value::Null)).collect())
+                    .collect();
+                to_json(&QueryResult { columns, rows }) + "\n"
+            }
+        };
+        std::fs::write(&export_stmt.path, text)
+            .map_err(|e| RqlError::execution(format!("Could not write '{}': {}", export_stmt.path, e)))?;
+        Ok(ExecResult::Message(format!("{} row(s) exported to '{}'", table.rows.len(), export_stmt.path)))
+    }
+
+    // Inserts the rows COPY ... FROM STDIN read: one per line of `text`, fields separated by the
+    // statement's delimiter and quoted as in CSV. A field is read as the type of its column (an
+    // untyped column infers one, as IMPORT does) and an empty field is NULL. Nothing is inserted
+    // unless every row is valid.
+    pub fn copy_rows(&mut self, copy_stmt: &CopyStatement, text: &str) -> Result<ExecResult, RqlError> {
+        if self.read_only {
+            return Err(RqlError::ReadOnly("COPY".to_string()));
+        }
+        let table = match self.tables.get(&copy_stmt.table_name) {
+            Some(table) => table,
+            None => return Err(RqlError::TableNotFound(copy_stmt.table_name.clone())),
+        };
+        let mut rows = vec![];
+        for record in csv::parse_delimited(text, copy_stmt.delimiter)? {
+            if record.is_blank() && table.columns.len() != 1 {
+                continue;
+            }
+            if record.fields.len() != table.columns.len() {
+                return Err(RqlError::execution(format!(
+                    "line {}: expected {} fields, got {}",
+                    record.line,
+                    table.columns.len(),
+                    record.fields.len()
+                )));
+            }
+            let row: Vec<Value> = record
+                .fields
+                .into_iter()
+                .zip(&table.column_types)
+                .map(|(field, data_type)| match data_type {
+                    // Digits stay text in a TEXT column
+                    Some(DataType::Text) if field.quoted || !field.text.trim().is_empty() => Value::Str(field.text),
+                    _ => csv_value(field),
+                })
+                .collect();
+            rows.push((record.line, row));
+        }
+        // Type errors are the likeliest, so check for them here where the line is known
+        for (line, row) in &mut rows {
+            check_types(table, row).map_err(|e| RqlError::execution(format!("line {}: {}", line, e)))?;
+        }
+        self.insert_many(&copy_stmt.table_name, rows.into_iter().map(|(_, row)| row).collect())
+    }
+
+    // Removes every row but keeps the schema, constraints and (emptied) indexes
+    fn execute_truncate(&mut self, truncate_stmt: parser::TruncateStatement) -> Result<ExecResult, RqlError> {
+        let table = match self.tables.get(&truncate_stmt.table_name) {
+            Some(t) => t,
+            None => return Err(RqlError::TableNotFound(truncate_stmt.table_name.clone())),
+        };
+        // Foreign keys apply as they do to a DELETE of every row
+        let doomed = self.cascade_deletes(&truncate_stmt.table_name, (0..table.rows.len()).collect())?;
+        Ok(ExecResult::RowsAffected(self.remove_rows(doomed, &truncate_stmt.table_name)))
+    }
+
+    // Compacts every table; the caller rewrites the database file afterwards (the REPL's auto-save)
+    fn execute_vacuum(&mut self) -> Result<ExecResult, RqlError> {
+        if self.in_transaction() {
+            return Err(RqlError::execution("VACUUM cannot run inside a transaction"));
+        }
+        let (mut slots, mut bytes) = (0, 0);
+        for table in self.tables.values_mut() {
+            let (table_slots, table_bytes) = table.compact();
+            slots += table_slots;
+            bytes += table_bytes;
+        }
+        Ok(ExecResult::Message(format!(
+            "Vacuumed {} table(s): {} unused row slot(s), {} bytes reclaimed",
+            self.tables.len(),
+            slots,
+            bytes
+        )))
+    }
+
+    fn execute_create_index(&mut self, index_stmt: parser::CreateIndexStatement) -> Result<ExecResult, RqlError> {
+        let taken = self
+            .tables
+            .values()
+            .any(|t| t.indexes.iter().any(|i| i.name == index_stmt.index_name));
+        if taken {
+            return Err(RqlError::execution(format!("Index '{}' already exists", index_stmt.index_name)));
+        }
+        let table = match self.tables.get_mut(&index_stmt.table_name) {
+            Some(t) => t,
+            None => return Err(RqlError::TableNotFound(index_stmt.table_name.clone())),
+        };
+        if let Some(missing) = index_stmt.columns.iter().find(|col| !table.columns.contains(col)) {
+            return Err(RqlError::ColumnNotFound(missing.clone()));
+        }
+        let kind = if index_stmt.sorted { IndexKind::Sorted } else { IndexKind::Hash };
+        if kind == IndexKind::Sorted && index_stmt.columns.len() > 1 {
+            return Err(RqlError::execution("A BTREE index can only cover one column"));
+        }
+
+        table.indexes.push(Index::new(index_stmt.index_name.clone(), index_stmt.columns, kind));
+        table.rebuild_indexes();
+        Ok(ExecResult::Message(format!("Index '{}' created", index_stmt.index_name)))
+    }
+
+    // Adds a UNIQUE or CHECK constraint, provided the rows already in the table satisfy it
+    fn execute_alter_table(&mut self, alter_stmt: parser::AlterTableStatement) -> Result<ExecResult, RqlError> {
+        let table = match self.tables.get_mut(&alter_stmt.table_name) {
+            Some(t) => t,
+            None => return Err(RqlError::TableNotFound(alter_stmt.table_name.clone())),
+        };
+        let kind = match alter_stmt.constraint {
+            TableConstraint::Unique(columns) => {
+                for col in &columns {
+                    lookup_column(col, table)?;
+                }
+                ConstraintKind::Unique(columns)
+            }
+            TableConstraint::Check(cond) => {
+                check_condition_columns(&cond, table)?;
+                ConstraintKind::Check(cond.to_string())
+            }
+        };
+        let name = alter_stmt.constraint_name.unwrap_or_else(|| match &kind {
+            ConstraintKind::Unique(columns) => format!("{}_{}_unique", alter_stmt.table_name, columns.join("_")),
+            ConstraintKind::Check(_) => {
+                let checks = table.constraints.iter().filter(|c| matches!(c.kind, ConstraintKind::Check(_))).count();
+                format!("{}_check{}", alter_stmt.table_name, checks + 1)
+            }
+        });
+        if table.constraints.iter().any(|c| c.name == name) {
+            return Err(RqlError::execution(format!(
+                "Constraint '{}' already exists on table '{}'",
+                name, alter_stmt.table_name
+            )));
+        }
+
+        let constraint = Constraint { name, kind };
+        let rows: Vec<RowRef> = (0..table.rows.len()).map(|pos| RowRef::at(table, pos)).collect();
+        constraint
+            .check(&table.columns, std::iter::empty(), &rows)
+            .map_err(|e| RqlError::ConstraintViolation(format!("Cannot add constraint: {}", e)))?;
+        let message = format!("Constraint '{}' added to '{}'", constraint.name, alter_stmt.table_name);
+        table.constraints.push(constraint);
+        Ok(ExecResult::Message(message))
     }
 
-    // Load database from file
+    fn execute_create(&mut self, create_stmt: parser::CreateTableStatement) -> Result<ExecResult, RqlError> {
+        let name = create_stmt.table_name.clone();
+        let mut table = Table::new(create_stmt.columns.iter().map(|c| c.name.clone()).collect());
+        table.column_types = create_stmt.columns.iter().map(|c| c.data_type).collect();
+
+        for column in &create_stmt.columns {
+            if let Some((parent_table, parent_column)) = &column.references {
+                // A table may reference itself, so look in the new schema too
+                let parent_columns = if *parent_table == name {
+                    &table.columns
+                } else {
+                    match self.tables.get(parent_table) {
+                        Some(parent) => &parent.columns,
+                        None => return Err(RqlError::TableNotFound(parent_table.clone())),
+                    }
+                };
+                if !parent_columns.contains(parent_column) {
+                    return Err(RqlError::ColumnNotFound(format!("{}.{}", parent_table, parent_column)));
+                }
+                table.foreign_keys.push(ForeignKey {
+                    column: column.name.clone(),
+                    parent_table: parent_table.clone(),
+                    parent_column: parent_column.clone(),
+                    on_delete: column.on_delete,
+                });
+            }
+        }
+
+        self.add_table(&create_stmt.table_name, table);
+        Ok(ExecResult::Created(name))
+    }
+
+    // Every non-NULL foreign key value must exist in the parent table's key column
+    fn check_references(&self, table: &Table, values: &[Value]) -> Result<(), RqlError> {
+        for fk in &table.foreign_keys {
+            let value = match resolve_column(&fk.column, &table.columns).and_then(|idx| values.get(idx)) {
+                Some(Value::Null) | None => continue,
+                Some(value) => value,
+            };
+            // A self-referencing row may point at its own key
+            let parent = self.tables.get(&fk.parent_table).unwrap_or(table);
+            let idx = resolve_column(&fk.parent_column, &parent.columns);
+            let self_match = std::ptr::eq(parent, table) && idx.and_then(|i| values.get(i)) == Some(value);
+            if !self_match && !parent.rows.iter().any(|row| idx.and_then(|i| row.get(i)) == Some(value)) {
+                return Err(RqlError::ConstraintViolation(format!(
+                    "Foreign key violation: {} = {} has no matching {}.{}",
+                    fk.column,
+                    Expr::Literal(value.clone()),
+                    fk.parent_table,
+                    fk.parent_column
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // Rejects an UPDATE that changes a parent key child rows still reference. `rows` pairs each
+    // updated row as it is with the row it becomes.
+    fn check_keys_kept(&self, table_name: &str, rows: &[(&Vec<Value>, &Vec<Value>)]) -> Result<(), RqlError> {
+        for (child_name, child) in &self.tables {
+            for fk in child.foreign_keys.iter().filter(|fk| fk.parent_table == table_name) {
+                let parent_idx = resolve_column(&fk.parent_column, &self.tables[table_name].columns);
+                let child_idx = resolve_column(&fk.column, &child.columns);
+                for (old, new) in rows {
+                    let key = match parent_idx.and_then(|idx| old.get(idx)) {
+                        Some(Value::Null) | None => continue,
+                        Some(key) => key,
+                    };
+                    if parent_idx.and_then(|idx| new.get(idx)) == Some(key) {
+                        continue;
+                    }
+                    if child.rows.iter().any(|child_row| child_idx.and_then(|idx| child_row.get(idx)) == Some(key)) {
+                        return Err(RqlError::ConstraintViolation(format!(
+                            "Cannot update: {}.{} = {} is still referenced by {}.{}",
+                            table_name,
+                            fk.parent_column,
+                            Expr::Literal(key.clone()),
+                            child_name,
+                            fk.column
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn execute_delete(&mut self, delete_stmt: parser::DeleteStatement) -> Result<ExecResult, RqlError> {
+        let verbose = self.verbose;
+        // Find every match (and every row it cascades to) first, so an error deletes nothing
+        let doomed = self.planned_delete(&delete_stmt)?;
+        let deleted = self.remove_rows(doomed, &delete_stmt.table_name);
+        if verbose {
+            println!("Deleted {} rows", deleted);
+        }
+        Ok(ExecResult::RowsAffected(deleted))
+    }
+
+    // Removes the rows a DELETE or TRUNCATE planned, per table, returning how many `table_name` lost
+    fn remove_rows(&mut self, doomed: HashMap<String, BTreeSet<usize>>, table_name: &str) -> usize {
+        let verbose = self.verbose;
+        let mut deleted = 0;
+        for (name, positions) in doomed {
+            let table = self.tables.get_mut(&name).expect("doomed rows come from existing tables");
+            let before = table.rows.len();
+            let mut pos = 0;
+            table.rows.retain(|_| {
+                pos += 1;
+                !positions.contains(&(pos - 1))
+            });
+            table.rebuild_indexes();
+            if verbose {
+                println!("{}: rows before: {}, after: {}", name, before, table.rows.len());
+            }
+            if name == table_name {
+                deleted = before - table.rows.len();
+            }
+        }
+        deleted
+    }
+
+    // The positions of the rows a DELETE removes, per table, including the ones it cascades to.
+    // Matches come in row order, so LIMIT keeps the first ones.
+    fn planned_delete(
+        &self,
+        delete_stmt: &parser::DeleteStatement,
+    ) -> Result<HashMap<String, BTreeSet<usize>>, RqlError> {
+        let table = match self.tables.get(&delete_stmt.table_name) {
+            Some(t) => t,
+            None => return Err(RqlError::TableNotFound(delete_stmt.table_name.clone())),
+        };
+        let condition = match &delete_stmt.condition {
+            Some(cond) => cond.clone(),
+            None => return Err(unconditional_error("DELETE", "delete", table)),
+        };
+        if self.verbose {
+            println!("Delete condition: {}", condition);
+        }
+        check_condition_columns(&condition, table)?;
+        let condition = self.run_subqueries(condition)?;
+
+        let mut positions = matching_positions(table, Some(&condition), self.verbose, self.options.ignore_case)?;
+        if let Some(limit) = delete_stmt.limit {
+            positions.truncate(limit);
+        }
+        self.cascade_deletes(&delete_stmt.table_name, positions)
+    }
+
+    // The rows deleting `positions` from `table_name` removes, per table: the rows themselves plus,
+    // through ON DELETE CASCADE foreign keys, every row referencing them (recursively). Fails if a
+    // row that is not being deleted still references a deleted one through a RESTRICT key.
+    fn cascade_deletes(
+        &self,
+        table_name: &str,
+        positions: Vec<usize>,
+    ) -> Result<HashMap<String, BTreeSet<usize>>, RqlError> {
+        let mut doomed: HashMap<String, BTreeSet<usize>> = HashMap::new();
+        doomed.insert(table_name.to_string(), positions.iter().copied().collect());
+        // Parent rows whose children still have to be visited; rows already doomed are never
+        // queued twice, so reference cycles terminate
+        let mut pending = vec![(table_name.to_string(), positions)];
+        // Children that must be deleted too, or else the delete fails
+        let mut restricted = vec![];
+
+        while let Some((parent_name, parent_positions)) = pending.pop() {
+            let parent = &self.tables[&parent_name];
+            for (child_name, child) in &self.tables {
+                for fk in child.foreign_keys.iter().filter(|fk| fk.parent_table == parent_name) {
+                    let parent_idx = resolve_column(&fk.parent_column, &parent.columns);
+                    let child_idx = resolve_column(&fk.column, &child.columns);
+                    let keys: HashSet<&Value> = parent_positions
+                        .iter()
+                        .filter_map(|&pos| parent_idx.and_then(|idx| parent.rows[pos].get(idx)))
+                        .filter(|key| **key != Value::Null)
+                        .collect();
+                    let referencing = child.rows.iter().enumerate().filter_map(|(pos, row)| {
+                        let value = child_idx.and_then(|idx| row.get(idx))?;
+                        keys.contains(value).then_some((pos, value))
+                    });
+                    let mut added = vec![];
+                    for (pos, value) in referencing {
+                        match fk.on_delete {
+                            OnDelete::Cascade => {
+                                if doomed.entry(child_name.clone()).or_default().insert(pos) {
+                                    added.push(pos);
+                                }
+                            }
+                            OnDelete::Restrict => restricted.push((child_name, fk, pos, value)),
+                        }
+                    }
+                    if !added.is_empty() {
+                        pending.push((child_name.clone(), added));
+                    }
+                }
+            }
+        }
+
+        for (child_name, fk, pos, value) in restricted {
+            if !doomed.get(child_name).is_some_and(|rows| rows.contains(&pos)) {
+                return Err(RqlError::ConstraintViolation(format!(
+                    "Cannot delete: {}.{} = {} is still referenced by {}.{}",
+                    fk.parent_table,
+                    fk.parent_column,
+                    Expr::Literal(value.clone()),
+                    child_name,
+                    fk.column
+                )));
+            }
+        }
+        Ok(doomed)
+    }
+
+    fn execute_update(&mut self, update_stmt: parser::UpdateStatement) -> Result<ExecResult, RqlError> {
+        let (positions, changes) = self.planned_update(&update_stmt)?;
+        let table = self.tables.get_mut(&update_stmt.table_name).expect("planned_update found the table");
+        for (pos, idx, value) in changes {
+            if let Some(cell) = table.rows[pos].get_mut(idx) {
+                *cell = value;
+            }
+        }
+        if !positions.is_empty() {
+            table.rebuild_indexes();
+        }
+        if self.verbose {
+            println!("Updated {} rows", positions.len());
+        }
+        Ok(ExecResult::RowsAffected(positions.len()))
+    }
+
+    // The positions of the rows an UPDATE changes, and every (position, column, new value) it
+    // writes. The new values are evaluated and type-checked against the rows as they were before
+    // the UPDATE, so an evaluation error or a value of the wrong type leaves the table untouched.
+    fn planned_update(&self, update_stmt: &parser::UpdateStatement) -> Result<PlannedUpdate, RqlError> {
+        let ignore_case = self.options.ignore_case;
+        let table = match self.tables.get(&update_stmt.table_name) {
+            Some(t) => t,
+            None => return Err(RqlError::TableNotFound(update_stmt.table_name.clone())),
+        };
+        let condition = update_stmt.condition.clone().map(|cond| self.run_subqueries(cond)).transpose()?;
+        // Resolve each SET target column once, before touching any rows
+        let mut assignments = Vec::new();
+        for (col, expr) in &update_stmt.set_clause {
+            check_columns(expr, table)?;
+            assignments.push((lookup_column(col, table)?, expr));
+        }
+
+        let condition = match condition {
+            Some(cond) => cond,
+            None => return Err(unconditional_error("UPDATE", "update", table)),
+        };
+        check_condition_columns(&condition, table)?;
+
+        // Matching rows come in row order, so LIMIT keeps the first ones
+        let mut positions = matching_positions(table, Some(&condition), false, ignore_case)?;
+        if let Some(limit) = update_stmt.limit {
+            positions.truncate(limit);
+        }
+
+        let mut changes = Vec::new();
+        for &pos in &positions {
+            for (idx, expr) in &assignments {
+                let mut value = eval_expr(expr, RowRef::at(table, pos), &table.columns, ignore_case)?;
+                check_type(&table.columns[*idx], table.column_types.get(*idx).copied().flatten(), &mut value)?;
+                changes.push((pos, *idx, value));
+            }
+        }
+        // The changed rows as they will be, checked against the rows left alone
+        let mut updated: BTreeMap<usize, Vec<Value>> =
+            positions.iter().map(|&pos| (pos, table.rows[pos].clone())).collect();
+        for (pos, idx, value) in &changes {
+            if let Some(cell) = updated.get_mut(pos).and_then(|row| row.get_mut(*idx)) {
+                *cell = value.clone();
+            }
+        }
+        if !table.constraints.is_empty() {
+            let candidates: Vec<RowRef> =
+                updated.iter().map(|(pos, row)| RowRef { rowid: pos + 1, values: row }).collect();
+            table.check_constraints(&candidates)?;
+        }
+        for row in updated.values() {
+            self.check_references(table, row)?;
+        }
+        let rekeyed: Vec<(&Vec<Value>, &Vec<Value>)> =
+            updated.iter().map(|(pos, row)| (&table.rows[*pos], row)).collect();
+        self.check_keys_kept(&update_stmt.table_name, &rekeyed)?;
+        Ok((positions, changes))
+    }
+
+    // Save database to file. Inside a transaction only the state as of BEGIN is written.
+    // A `.json` path is written as pretty-printed JSON, anything else as bincode.
+    // The file's write-ahead log is removed, since the snapshot now holds everything.
+    // With `keep_backup` the file being replaced becomes `<path>.bak`, once the new one is written.
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let tables = self.snapshot.as_ref().unwrap_or(&self.tables);
+        let file = SavedFile {
+            header: FileHeader::current(),
+            tables: OrderedTables(
+                in_creation_order(&self.table_order, tables).into_iter().map(|(name, t)| (name.clone(), t)).collect(),
+            ),
+        };
+        let encoded = match StorageFormat::for_path(path) {
+            StorageFormat::Bincode => bincode::serialize(&file)?,
+            StorageFormat::Json => serde_json::to_vec_pretty(&file)?,
+        };
+        let backup = self.keep_backup.then(|| backup_path(path));
+        write_atomic(path, &encoded, backup.as_deref())?;
+        match std::fs::remove_file(wal::log_path(path)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    // Load database from file, in the format its extension implies. Files written before
+    // the version header existed are still read and upgraded.
     pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
         let data = std::fs::read(path)?;
-        let db = bincode::deserialize(&data)?;
+        let format = StorageFormat::for_path(path);
+        let header = match format {
+            StorageFormat::Bincode => bincode::deserialize::<FileHeader>(&data).ok(),
+            StorageFormat::Json => serde_json::from_slice::<HeaderProbe>(&data).ok().and_then(|probe| probe.header),
+        }
+        .filter(|header| header.magic == FILE_MAGIC);
+
+        if let Some(header) = &header
+            && !(1..=FILE_VERSION).contains(&header.version)
+        {
+            return Err(format!(
+                "unsupported database version {} in {} (this build reads version {})",
+                header.version, path, FILE_VERSION
+            )
+            .into());
+        }
+
+        let mut db = migrate(header.map(|header| header.version), format, &data)?;
+        db.check_stored_constraints()?;
+        for table in db.tables.values_mut() {
+            table.rebuild_indexes();
+        }
         Ok(db)
     }
+
+    // Constraints are kept as text, so one that no longer reads (e.g. a file edited by hand)
+    // would otherwise only fail when a row is next checked against it. Each CHECK condition is
+    // rewritten the way ADD CONSTRAINT stores it, with canonical spacing and keyword case.
+    fn check_stored_constraints(&mut self) -> Result<(), RqlError> {
+        for (name, table) in &mut self.tables {
+            for constraint in &mut table.constraints {
+                match &mut constraint.kind {
+                    ConstraintKind::Unique(columns) => {
+                        if let Some(col) = columns.iter().find(|col| resolve_column(col, &table.columns).is_none()) {
+                            return Err(RqlError::execution(format!(
+                                "UNIQUE constraint '{}' on table '{}' names a missing column '{}'",
+                                constraint.name, name, col
+                            )));
+                        }
+                    }
+                    ConstraintKind::Check(sql) => match parser::parse_condition_text(sql) {
+                        Ok(cond) => *sql = cond.to_string(),
+                        Err(e) => {
+                            return Err(RqlError::execution(format!(
+                                "CHECK constraint '{}' on table '{}' ({}) can't be read: {}",
+                                constraint.name, name, sql, e
+                            )));
+                        }
+                    },
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Decodes a file in the layout of `version` (None for files from before the header existed) and
+// upgrades it to the current `Table` shape: old index and foreign key records are converted and
+// the fields added since are filled with their defaults. The version must be one this build reads.
+fn migrate(version: Option<u32>, format: StorageFormat, data: &[u8]) -> Result<Database, Box<dyn std::error::Error>> {
+    Ok(match version {
+        // Version 1 predates composite indexes, version 2 sorted ones
+        Some(1) => decode::<OldFile<IndexV1>>(format, data)?.tables.into(),
+        Some(2) => decode::<OldFile<IndexV2>>(format, data)?.tables.into(),
+        // Version 3 predates ON DELETE actions
+        Some(3) => decode::<OldFile<Index>>(format, data)?.tables.into(),
+        // Version 4 predates table constraints
+        Some(4) => decode::<OldFile<Index, ForeignKey>>(format, data)?.tables.into(),
+        Some(_) => decode::<LoadedFile>(format, data)?.tables.into(),
+        // Unversioned files share the version 1 table layout
+        None => match format {
+            StorageFormat::Json => serde_json::from_slice::<UnversionedDatabase>(data).map_err(damaged)?.tables.into(),
+            StorageFormat::Bincode => match bincode::deserialize::<UnversionedDatabase>(data) {
+                Ok(db) => db.tables.into(),
+                // Files saved before column types, foreign keys and indexes existed
+                Err(e) => match bincode::deserialize::<LegacyDatabase>(data) {
+                    Ok(legacy) => legacy.tables.into(),
+                    Err(_) => return Err(damaged(e)),
+                },
+            },
+        },
+    })
+}
+
+// Decodes a whole file in the given format
+fn decode<T: serde::de::DeserializeOwned>(format: StorageFormat, data: &[u8]) -> Result<T, Box<dyn std::error::Error>> {
+    Ok(match format {
+        StorageFormat::Bincode => bincode::deserialize(data).map_err(damaged)?,
+        StorageFormat::Json => serde_json::from_slice(data).map_err(damaged)?,
+    })
+}
+
+// The error for a file that exists but doesn't decode, as opposed to one that can't be read at all
+fn damaged(e: impl std::fmt::Display) -> Box<dyn std::error::Error> {
+    format!("the file is damaged or is not a RustQL database ({})", e).into()
+}
+
+// Write to a sibling temporary file, flush it to disk, then rename it over `path`, so a crash
+// mid-save leaves either the old file or the new one, never a truncated mix. The old file is
+// moved to `backup` (replacing the previous backup) only once the new one is safely on disk.
+fn write_atomic(path: &str, data: &[u8], backup: Option<&str>) -> std::io::Result<()> {
+    use std::io::Write;
+    let tmp_path = format!("{}.tmp", path);
+    let mut file = std::fs::File::create(&tmp_path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    drop(file);
+    let Some(backup) = backup.filter(|_| std::path::Path::new(path).exists()) else {
+        return std::fs::rename(&tmp_path, path);
+    };
+    std::fs::rename(path, backup)?;
+    std::fs::rename(&tmp_path, path).inspect_err(|_| {
+        // Put the old file back rather than leave nothing at `path`
+        let _ = std::fs::rename(backup, path);
+    })
+}
+
+// Path of the backup `save` keeps of the database file at `path` when `keep_backup` is set
+pub fn backup_path(path: &str) -> String {
+    format!("{}.bak", path)
+}
+
+// How IMPORT reads and EXPORT writes a data file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataFormat {
+    Csv,
+    Json, // an array with one object per row, keyed by column name
+}
+
+impl DataFormat {
+    // Chosen by the file's extension, `.csv` or `.json`; None for any other. IMPORT and EXPORT
+    // treat a file with another extension as CSV.
+    pub fn for_path(path: &str) -> Option<Self> {
+        let ext = std::path::Path::new(path).extension()?;
+        if ext.eq_ignore_ascii_case("csv") {
+            Some(DataFormat::Csv)
+        } else if ext.eq_ignore_ascii_case("json") {
+            Some(DataFormat::Json)
+        } else {
+            None
+        }
+    }
+}
+
+// How a database file is encoded on disk
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StorageFormat {
+    Bincode,
+    Json,
+}
+
+impl StorageFormat {
+    // `.json` files hold JSON; everything else (e.g. `database.bin`) is bincode
+    fn for_path(path: &str) -> Self {
+        let is_json = std::path::Path::new(path)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        if is_json { StorageFormat::Json } else { StorageFormat::Bincode }
+    }
+}
+
+// Identifies a RustQL database file and the layout of the data that follows
+const FILE_MAGIC: [u8; 4] = *b"RQDB";
+// Bump whenever Table or Database change shape, and teach `load` about the old layout
+const FILE_VERSION: u32 = 5;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct FileHeader {
+    magic: [u8; 4],
+    version: u32,
+}
+
+impl FileHeader {
+    fn current() -> Self {
+        FileHeader { magic: FILE_MAGIC, version: FILE_VERSION }
+    }
+}
+
+// What `save` writes: the header, then the committed tables
+#[derive(Serialize)]
+struct SavedFile<'a> {
+    header: FileHeader,
+    tables: OrderedTables<&'a Table>,
+}
+
+#[derive(Deserialize)]
+struct LoadedFile {
+    // Already checked by `load` before the tables are decoded
+    #[serde(rename = "header")]
+    _header: FileHeader,
+    tables: OrderedTables<Table>,
+}
+
+// The tables of a file, stored as a map from name to table (the layout every version has used)
+// but written and read back in creation order
+struct OrderedTables<T>(Vec<(String, T)>);
+
+impl<T: Serialize> Serialize for OrderedTables<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(name, table)| (name, table)))
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for OrderedTables<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor<T>(std::marker::PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> serde::de::Visitor<'de> for Visitor<T> {
+            type Value = OrderedTables<T>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a map of table names to tables")
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut tables = Vec::with_capacity(map.size_hint().unwrap_or(0));
+                while let Some(entry) = map.next_entry()? {
+                    tables.push(entry);
+                }
+                Ok(OrderedTables(tables))
+            }
+        }
+
+        deserializer.deserialize_map(Visitor(std::marker::PhantomData))
+    }
+}
+
+impl<T: Into<Table>> From<OrderedTables<T>> for Database {
+    fn from(tables: OrderedTables<T>) -> Self {
+        let mut db = Database::new();
+        for (name, table) in tables.0 {
+            db.add_table(&name, table.into());
+        }
+        db
+    }
+}
+
+// The tables named in `order` that are in `tables`, then any others in name order
+fn in_creation_order<'a>(order: &[String], tables: &'a HashMap<String, Table>) -> Vec<(&'a String, &'a Table)> {
+    let mut listed: Vec<(&String, &Table)> = order.iter().filter_map(|name| tables.get_key_value(name)).collect();
+    let mut others: Vec<(&String, &Table)> = tables.iter().filter(|(name, _)| !order.contains(name)).collect();
+    others.sort_by_key(|(name, _)| *name);
+    listed.append(&mut others);
+    listed
+}
+
+// Reads just the header of a JSON file; unversioned files have none
+#[derive(Deserialize)]
+struct HeaderProbe {
+    header: Option<FileHeader>,
+}
+
+// A table as stored by an older version: no constraints, and possibly older index and foreign
+// key records
+#[derive(Deserialize)]
+struct OldTable<I, F = ForeignKeyV1> {
+    rows: Vec<Vec<Value>>,
+    columns: Vec<String>,
+    column_types: Vec<Option<DataType>>,
+    foreign_keys: Vec<F>,
+    indexes: Vec<I>,
+}
+
+// Foreign key before ON DELETE actions: deletes were always restricted
+#[derive(Deserialize)]
+struct ForeignKeyV1 {
+    column: String,
+    parent_table: String,
+    parent_column: String,
+}
+
+impl From<ForeignKeyV1> for ForeignKey {
+    fn from(old: ForeignKeyV1) -> Self {
+        ForeignKey {
+            column: old.column,
+            parent_table: old.parent_table,
+            parent_column: old.parent_column,
+            on_delete: OnDelete::Restrict,
+        }
+    }
+}
+
+// Version 1 index: always a hash index on a single column
+#[derive(Deserialize)]
+struct IndexV1 {
+    name: String,
+    column: String,
+}
+
+// Version 2 index: a hash index on one or more columns
+#[derive(Deserialize)]
+struct IndexV2 {
+    name: String,
+    columns: Vec<String>,
+}
+
+impl From<IndexV1> for Index {
+    fn from(old: IndexV1) -> Self {
+        Index::new(old.name, vec![old.column], IndexKind::Hash)
+    }
+}
+
+impl From<IndexV2> for Index {
+    fn from(old: IndexV2) -> Self {
+        Index::new(old.name, old.columns, IndexKind::Hash)
+    }
+}
+
+// Unversioned files hold just the tables, in the version 1 layout
+#[derive(Deserialize)]
+struct UnversionedDatabase {
+    tables: OrderedTables<OldTable<IndexV1>>,
+}
+
+#[derive(Deserialize)]
+struct OldFile<I, F = ForeignKeyV1> {
+    #[serde(rename = "header")]
+    _header: FileHeader,
+    tables: OrderedTables<OldTable<I, F>>,
+}
+
+impl<I: Into<Index>, F: Into<ForeignKey>> From<OldTable<I, F>> for Table {
+    fn from(old: OldTable<I, F>) -> Self {
+        Table {
+            rows: old.rows,
+            columns: old.columns,
+            column_types: old.column_types,
+            foreign_keys: old.foreign_keys.into_iter().map(Into::into).collect(),
+            indexes: old.indexes.into_iter().map(Into::into).collect(),
+            constraints: vec![],
+        }
+    }
+}
+
+// On-disk layout of a table before column types and foreign keys were added
+#[derive(Deserialize)]
+struct LegacyTable {
+    rows: Vec<Vec<Value>>,
+    columns: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct LegacyDatabase {
+    tables: OrderedTables<LegacyTable>,
+}
+
+impl From<LegacyTable> for Table {
+    fn from(old: LegacyTable) -> Self {
+        let mut table = Table::new(old.columns);
+        table.rows = old.rows;
+        table
+    }
+}
+
+// DIFF compares two database files, neither of which has to be the one that is open
+fn execute_diff(diff_stmt: parser::DiffStatement) -> Result<ExecResult, RqlError> {
+    let (old, new) = (read_database_file(&diff_stmt.old_path)?, read_database_file(&diff_stmt.new_path)?);
+    Ok(ExecResult::Message(old.diff(&new).to_string()))
+}
+
+// A database file other than the open one, for DIFF and MERGE FROM. It is read as `Engine::open`
+// would, including statements left in its write-ahead log.
+fn read_database_file(path: &str) -> Result<Database, RqlError> {
+    let open = || {
+        let mut db = Database::load(path)?;
+        wal::replay(&mut db, path)?;
+        Ok::<_, Box<dyn std::error::Error>>(db)
+    };
+    open().map_err(|e| RqlError::execution(format!("Cannot read {}: {}", path, e)))
+}
+
+// Appends the statements that recreate one table to `sql`
+fn dump_table(name: &str, table: &Table, sql: &mut Vec<String>) {
+    let name_sql = quote_name(name);
+    let columns: Vec<String> = table
+        .columns
+        .iter()
+        .zip(&table.column_types)
+        .map(|(column, data_type)| {
+            let mut def = quote_name(column);
+            if let Some(data_type) = data_type {
+                def += &format!(" {}", data_type);
+            }
+            if let Some(fk) = table.foreign_keys.iter().find(|fk| fk.column == *column) {
+                def += &format!(" REFERENCES {}({})", quote_name(&fk.parent_table), quote_name(&fk.parent_column));
+                if fk.on_delete == OnDelete::Cascade {
+                    def += " ON DELETE CASCADE";
+                }
+            }
+            def
+        })
+        .collect();
+    sql.push(format!("CREATE TABLE {} ({});", name_sql, columns.join(", ")));
+
+    for row in &table.rows {
+        let values: Vec<String> = row.iter().map(|value| Expr::Literal(value.clone()).to_string()).collect();
+        sql.push(format!("INSERT INTO {} VALUES ({});", name_sql, values.join(", ")));
+    }
+
+    let names = |columns: &[String]| columns.iter().map(|c| quote_name(c)).collect::<Vec<_>>().join(", ");
+    for index in &table.indexes {
+        let using = if index.kind == IndexKind::Sorted { " USING BTREE" } else { "" };
+        sql.push(format!(
+            "CREATE INDEX {} ON {}({}){};",
+            quote_name(&index.name),
+            name_sql,
+            names(&index.columns),
+            using
+        ));
+    }
+    for constraint in &table.constraints {
+        let rule = match &constraint.kind {
+            ConstraintKind::Unique(columns) => format!("UNIQUE ({})", names(columns)),
+            ConstraintKind::Check(condition) => format!("CHECK ({})", condition),
+        };
+        sql.push(format!("ALTER TABLE {} ADD CONSTRAINT {} {};", name_sql, quote_name(&constraint.name), rule));
+    }
+}
+
+// What IMPORT reads from a file: the column names (None if the file is empty) and the rows, each
+// with where it came from, e.g. "line 3"
+type DataRows = (Option<Vec<String>>, Vec<(String, Vec<Value>)>);
+
+// What an UPDATE will do: the positions of the rows it changes and each (position, column index,
+// new value) it writes
+type PlannedUpdate = (Vec<usize>, Vec<(usize, usize, Value)>);
+
+// The column names and rows of a CSV file whose first record names the columns, each row with the
+// line it came from. Empty lines are skipped, except in a one-column file, where one stands for NULL.
+fn csv_rows(text: &str) -> Result<DataRows, RqlError> {
+    let mut records = csv::parse(text)?.into_iter().skip_while(csv::Record::is_blank);
+    let Some(header) = records.next() else { return Ok((None, vec![])) };
+    let header: Vec<String> = header.fields.into_iter().map(|f| f.text.trim().to_string()).collect();
+    let rows = records
+        .filter(|record| header.len() == 1 || !record.is_blank())
+        .map(|record| (format!("line {}", record.line), record.fields.into_iter().map(csv_value).collect()))
+        .collect();
+    Ok((Some(header), rows))
+}
+
+// A CSV file that `csv_rows` reads back as exactly these rows. Strings are always quoted, so they
+// stay text whatever they hold (digits, commas, quotes, newlines, surrounding spaces), and an empty
+// string ("") is kept apart from NULL (an empty field). Numbers and dates are written bare; floats
+// keep a decimal point or exponent so they don't come back as integers.
+fn csv_text(columns: &[String], rows: &[Vec<Value>]) -> String {
+    let header: Vec<String> = columns.iter().map(|name| csv::quote(name)).collect();
+    let mut text = header.join(",") + "\n";
+    for row in rows {
+        let fields: Vec<String> = (0..columns.len())
+            .map(|i| match row.get(i) {
+                Some(Value::Int(n)) => n.to_string(),
+                Some(Value::Float(f)) => format!("{:?}", f),
+                Some(Value::Str(s)) => csv::quote(s),
+                Some(Value::Date(d)) => d.clone(),
+                _ => String::new(),
+            })
+            .collect();
+        text += &fields.join(",");
+        text.push('\n');
+    }
+    text
+}
+
+// The column names and rows of a JSON array of objects, as EXPORT writes it. The columns are those
+// of the existing `table`, or else the keys of the first object; a key an object lacks is NULL.
+// Numbers with a decimal point or exponent become FLOAT, other numbers INT, strings TEXT.
+fn json_rows(text: &str, table: Option<&Table>) -> Result<DataRows, RqlError> {
+    let data: serde_json::Value =
+        serde_json::from_str(text).map_err(|e| RqlError::execution(format!("Invalid JSON: {}", e)))?;
+    let serde_json::Value::Array(items) = data else {
+        return Err(RqlError::execution("Expected a JSON array of objects, one per row"));
+    };
+    let mut objects = vec![];
+    for (i, item) in items.into_iter().enumerate() {
+        match item {
+            serde_json::Value::Object(object) => objects.push(object),
+            other => return Err(RqlError::execution(format!("row {}: expected an object, got {}", i + 1, other))),
+        }
+    }
+    let header = match table {
+        Some(table) if !table.columns.is_empty() => Some(table.columns.clone()),
+        _ => objects.first().map(|object| object.keys().cloned().collect::<Vec<_>>()),
+    };
+    let Some(columns) = &header else { return Ok((None, vec![])) };
+
+    let mut rows = vec![];
+    for (i, mut object) in objects.into_iter().enumerate() {
+        let location = format!("row {}", i + 1);
+        let mut values = vec![];
+        for column in columns {
+            values.push(match object.remove(column) {
+                None | Some(serde_json::Value::Null) => Value::Null,
+                Some(serde_json::Value::String(s)) => Value::Str(s),
+                // as_i64 is None for a number written with a decimal point or exponent
+                Some(serde_json::Value::Number(n)) => match n.as_i64().and_then(|n| i32::try_from(n).ok()) {
+                    Some(n) => Value::Int(n),
+                    None => Value::Float(n.as_f64().unwrap_or(f64::NAN)),
+                },
+                Some(other) => {
+                    let msg = format!("{}: unsupported value for {}: {}", location, column, other);
+                    return Err(RqlError::execution(msg));
+                }
+            });
+        }
+        if let Some(extra) = object.keys().next() {
+            return Err(RqlError::execution(format!("{}: unknown column '{}'", location, extra)));
+        }
+        rows.push((location, values));
+    }
+    Ok((header, rows))
+}
+
+// Unquoted integers become Int, empty unquoted fields NULL, everything else text
+fn csv_value(field: csv::Field) -> Value {
+    if field.quoted {
+        return Value::Str(field.text);
+    }
+    let trimmed = field.text.trim();
+    if trimmed.is_empty() {
+        Value::Null
+    } else if let Ok(i) = trimmed.parse::<i32>() {
+        Value::Int(i)
+    } else if let Some(f) = trimmed.parse::<f64>().ok().filter(|f| f.is_finite()) {
+        Value::Float(f)
+    } else {
+        Value::Str(field.text)
+    }
+}
+
+// Values must match the declared column types; NULL fits any column.
+// Strings stored into a DATE column are converted, so invalid dates are rejected here.
+fn check_types(table: &Table, values: &mut [Value]) -> Result<(), RqlError> {
+    for ((column, data_type), value) in table.columns.iter().zip(&table.column_types).zip(values) {
+        check_type(column, *data_type, value)?;
+    }
+    Ok(())
+}
+
+// Converts a value stored into a typed column where that's lossless (text to DATE, INT to
+// FLOAT), then fails if it still doesn't fit the column's type
+fn check_type(column: &str, data_type: Option<DataType>, value: &mut Value) -> Result<(), RqlError> {
+    if let (Some(DataType::Date), Value::Str(text)) = (data_type, &value) {
+        *value = parse_date(text)?;
+    }
+    // An integer stored into a FLOAT column is widened
+    if let (Some(DataType::Float), Value::Int(i)) = (data_type, &value) {
+        *value = Value::Float(f64::from(*i));
+    }
+    match data_type {
+        Some(expected) if !value_fits(expected, value) => Err(RqlError::TypeMismatch(format!(
+            "Column '{}' expects {}, got {}",
+            column,
+            expected,
+            Expr::Literal(value.clone())
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn value_fits(data_type: DataType, value: &Value) -> bool {
+    matches!(
+        (data_type, value),
+        (_, Value::Null)
+            | (DataType::Int, Value::Int(_))
+            | (DataType::Text, Value::Str(_))
+            | (DataType::Date, Value::Date(_))
+            | (DataType::Float, Value::Float(_))
+    )
+}
+
+// Resolve a column reference: symbolic name first, then positional colN
+fn resolve_column(name: &str, schema: &[String]) -> Option<usize> {
+    if let Some(idx) = schema.iter().position(|c| c == name) {
+        return Some(idx);
+    }
+    // In a join, an unqualified name stands for the one `table.name` column it matches
+    if let [idx] = qualified_matches(name, schema)[..] {
+        return Some(idx);
+    }
+    name.strip_prefix("col").and_then(|pos| pos.parse::<usize>().ok())
+}
+
+// `colN`, a column referenced by position
+fn is_positional(name: &str) -> bool {
+    name.strip_prefix("col").is_some_and(|pos| pos.parse::<usize>().is_ok())
+}
+
+// Positions of the `table.name` columns an unqualified name could refer to
+fn qualified_matches(name: &str, schema: &[String]) -> Vec<usize> {
+    if name.contains('.') {
+        return vec![];
+    }
+    (0..schema.len())
+        .filter(|&idx| schema[idx].split_once('.').is_some_and(|(_, column)| column == name))
+        .collect()
+}
+
+// Like resolve_column, but the column must exist: colN has to lie within the table's width
+fn lookup_column(name: &str, table: &Table) -> Result<usize, RqlError> {
+    match resolve_column(name, &table.columns) {
+        Some(idx) if idx < table.width() => Ok(idx),
+        _ => {
+            let candidates = qualified_matches(name, &table.columns);
+            if candidates.len() > 1 {
+                return Err(RqlError::AmbiguousColumn {
+                    name: name.to_string(),
+                    candidates: candidates.iter().map(|&idx| table.columns[idx].clone()).collect(),
+                });
+            }
+            Err(RqlError::ColumnNotFound(name.to_string()))
+        }
+    }
+}
+
+// A copy of a table for joining, with each column renamed to `name.column`
+fn qualified_table(name: &str, table: &Table) -> Table {
+    let width = table.width();
+    let columns = (0..width)
+        .map(|i| match table.columns.get(i) {
+            Some(column) => format!("{}.{}", name, column),
+            None => format!("{}.col{}", name, i),
+        })
+        .collect();
+    let mut column_types = table.column_types.clone();
+    column_types.resize(width, None);
+    let rows = table
+        .rows
+        .iter()
+        .map(|row| {
+            let mut row = row.clone();
+            row.resize(width, Value::Null);
+            row
+        })
+        .collect();
+    Table { rows, columns, column_types, foreign_keys: vec![], indexes: vec![], constraints: vec![] }
+}
+
+// Pairs every row of `left` with every row of `right`, keeping the pairs that satisfy `on`;
+// without a condition (CROSS JOIN) that is len(left) * len(right) rows
+fn join_tables(left: Table, right: Table, on: Option<&Condition>, ignore_case: bool) -> Result<Table, RqlError> {
+    let mut combined = Table {
+        rows: vec![],
+        columns: left.columns.into_iter().chain(right.columns).collect(),
+        column_types: left.column_types.into_iter().chain(right.column_types).collect(),
+        foreign_keys: vec![],
+        indexes: vec![],
+        constraints: vec![],
+    };
+    if let Some(cond) = on {
+        check_condition_columns(cond, &combined)?;
+    }
+    for l in &left.rows {
+        for r in &right.rows {
+            let row: Vec<Value> = l.iter().chain(r).cloned().collect();
+            let keep = match on {
+                Some(cond) => {
+                    let row = RowRef { rowid: combined.rows.len() + 1, values: &row };
+                    row_matches(cond, row, &combined.columns, ignore_case)?
+                }
+                None => true,
+            };
+            if keep {
+                combined.rows.push(row);
+            }
+        }
+    }
+    Ok(combined)
+}
+
+// Every column an expression mentions must exist in the table
+fn check_columns(expr: &Expr, table: &Table) -> Result<(), RqlError> {
+    match expr {
+        Expr::Column(name) if is_rowid(name, &table.columns) => Ok(()),
+        Expr::Column(name) => lookup_column(name, table).map(|_| ()),
+        Expr::Literal(_) => Ok(()),
+        Expr::Aggregate { arg, .. } => match arg {
+            Some(arg) => check_columns(arg, table),
+            None => Ok(()),
+        },
+        Expr::Binary { left, right, .. } => {
+            check_columns(left, table)?;
+            check_columns(right, table)
+        }
+        Expr::Case { branches, otherwise } => {
+            for (cond, result) in branches {
+                check_condition_columns(cond, table)?;
+                check_columns(result, table)?;
+            }
+            otherwise.as_ref().map_or(Ok(()), |expr| check_columns(expr, table))
+        }
+        Expr::Function { args, .. } => args.iter().try_for_each(|arg| check_columns(arg, table)),
+    }
+}
+
+// Rewrites `qualifier.column` references so they match the source table's column names. A
+// qualifier may be a table's alias or its real name (when that table appears only once).
+// In a single-table SELECT the qualifier is dropped, so `users.name` just means `name`, and one
+// naming any other table is an error; in a join it becomes the `alias.` prefix the joined columns use.
+fn normalize_qualifiers(select_stmt: &mut parser::SelectStatement, base_columns: &[String]) -> Result<(), RqlError> {
+    let mut sources = vec![(select_stmt.table_name.clone(), select_stmt.table_alias.clone())];
+    sources.extend(select_stmt.joins.iter().map(|join| (join.table_name.clone(), join.alias.clone())));
+    let single = select_stmt.joins.is_empty();
+    let canonical = |qualifier: &str| -> Option<String> {
+        if let Some((_, Some(alias))) = sources.iter().find(|(_, alias)| alias.as_deref() == Some(qualifier)) {
+            return Some(alias.clone());
+        }
+        match sources.iter().filter(|(name, _)| name == qualifier).collect::<Vec<_>>()[..] {
+            [(name, alias)] => Some(alias.clone().unwrap_or_else(|| name.clone())),
+            _ => None,
+        }
+    };
+    let mut unqualify = |name: &mut String| -> Result<(), RqlError> {
+        // A quoted column name may itself contain a dot
+        if single && base_columns.contains(name) {
+            return Ok(());
+        }
+        if let Some((qualifier, column)) = name.split_once('.') {
+            match canonical(qualifier) {
+                Some(_) if single => *name = column.to_string(),
+                Some(prefix) => *name = format!("{}.{}", prefix, column),
+                None if single => {
+                    return Err(RqlError::execution(format!(
+                        "Column '{}' refers to table '{}', but the query reads from '{}'",
+                        name, qualifier, select_stmt.table_name
+                    )))
+                }
+                // Left as is; resolving it reports the missing column
+                None => {}
+            }
+        }
+        Ok(())
+    };
+
+    for item in &mut select_stmt.items {
+        match item {
+            SelectItem::Expr(expr, _) => visit_columns(expr, &mut unqualify)?,
+            SelectItem::StarExcept(names) => names.iter_mut().try_for_each(&mut unqualify)?,
+            SelectItem::TableStar(qualifier) => match canonical(qualifier) {
+                Some(_) if single => *item = SelectItem::Star,
+                Some(prefix) => *qualifier = prefix,
+                None => {
+                    return Err(RqlError::execution(format!(
+                        "'{}.*' names a table the query doesn't read from",
+                        qualifier
+                    )))
+                }
+            },
+            SelectItem::Star => {}
+        }
+    }
+    for cond in select_stmt.condition.iter_mut().chain(&mut select_stmt.having) {
+        visit_condition_columns(cond, &mut unqualify)?;
+    }
+    for join in &mut select_stmt.joins {
+        if let JoinKind::Inner(cond) = &mut join.kind {
+            visit_condition_columns(cond, &mut unqualify)?;
+        }
+    }
+    select_stmt.group_by.iter_mut().try_for_each(&mut unqualify)?;
+    select_stmt.order_by.iter_mut().try_for_each(|key| unqualify(&mut key.column))
+}
+
+// Calls `f` on every column name an expression mentions
+fn visit_columns(expr: &mut Expr, f: &mut dyn FnMut(&mut String) -> Result<(), RqlError>) -> Result<(), RqlError> {
+    match expr {
+        Expr::Column(name) => f(name),
+        Expr::Literal(_) | Expr::Aggregate { arg: None, .. } => Ok(()),
+        Expr::Aggregate { arg: Some(arg), .. } => visit_columns(arg, f),
+        Expr::Binary { left, right, .. } => {
+            visit_columns(left, f)?;
+            visit_columns(right, f)
+        }
+        Expr::Case { branches, otherwise } => {
+            for (cond, result) in branches {
+                visit_condition_columns(cond, f)?;
+                visit_columns(result, f)?;
+            }
+            otherwise.as_mut().map_or(Ok(()), |expr| visit_columns(expr, f))
+        }
+        Expr::Function { args, .. } => args.iter_mut().try_for_each(|arg| visit_columns(arg, f)),
+    }
+}
+
+// Calls `f` on every column name a condition mentions, outside of subqueries
+fn visit_condition_columns(
+    cond: &mut Condition,
+    f: &mut dyn FnMut(&mut String) -> Result<(), RqlError>,
+) -> Result<(), RqlError> {
+    match cond {
+        Condition::Comparison { left, right, .. } | Condition::Distinct { left, right, .. } => {
+            visit_columns(left, f)?;
+            visit_columns(right, f)
+        }
+        Condition::InList { left, .. } | Condition::InSelect { left, .. } | Condition::Like { left, .. } => {
+            visit_columns(left, f)
+        }
+        Condition::And(left, right) | Condition::Or(left, right) => {
+            visit_condition_columns(left, f)?;
+            visit_condition_columns(right, f)
+        }
+    }
+}
+
+// Every column a condition tests must exist in the table (subqueries are checked when they run)
+fn check_condition_columns(cond: &Condition, table: &Table) -> Result<(), RqlError> {
+    match cond {
+        Condition::Comparison { left, right, .. } | Condition::Distinct { left, right, .. } => {
+            check_columns(left, table)?;
+            check_columns(right, table)
+        }
+        Condition::InList { left, .. } | Condition::InSelect { left, .. } | Condition::Like { left, .. } => {
+            check_columns(left, table)
+        }
+        Condition::And(left, right) | Condition::Or(left, right) => {
+            check_condition_columns(left, table)?;
+            check_condition_columns(right, table)
+        }
+    }
+}
+
+// A typed column compared with a literal it can never be compared with, e.g. `price = 'abc'` for
+// an INT price, is an error whatever the rows hold. Columns without a declared type, and anything
+// but a plain column against a literal, are left to the comparison itself.
+fn check_condition_types(cond: &Condition, table: &Table) -> Result<(), RqlError> {
+    let check = |column: &Expr, value: &Value| {
+        let Expr::Column(name) = column else { return Ok(()) };
+        let column_type =
+            resolve_column(name, &table.columns).and_then(|idx| table.column_types.get(idx).copied().flatten());
+        let comparable = match (column_type, value) {
+            (None, _) | (_, Value::Null) => true,
+            (Some(DataType::Int | DataType::Float), Value::Int(_) | Value::Float(_)) => true,
+            (Some(DataType::Text), Value::Str(_) | Value::Date(_)) => true,
+            (Some(DataType::Date), Value::Date(_)) => true,
+            (Some(DataType::Date), Value::Str(text)) => parse_date(text).is_ok(),
+            _ => false,
+        };
+        match column_type {
+            Some(column_type) if !comparable => Err(RqlError::TypeMismatch(format!(
+                "Cannot compare {} ({}) with {} ({})",
+                name,
+                column_type,
+                Expr::Literal(value.clone()),
+                value.type_name()
+            ))),
+            _ => Ok(()),
+        }
+    };
+    match cond {
+        Condition::Comparison { left, right, .. } | Condition::Distinct { left, right, .. } => match (left, right) {
+            (column, Expr::Literal(value)) | (Expr::Literal(value), column) => check(column, value),
+            _ => Ok(()),
+        },
+        Condition::InList { left, values, .. } => values.iter().try_for_each(|value| check(left, value)),
+        Condition::InSelect { .. } | Condition::Like { .. } => Ok(()),
+        Condition::And(left, right) | Condition::Or(left, right) => {
+            check_condition_types(left, table)?;
+            check_condition_types(right, table)
+        }
+    }
+}
+
+// Rejects a SELECT that mentions a missing column anywhere, or compares a typed column with a
+// literal of the wrong type, before any row is read
+fn check_select_columns(select_stmt: &parser::SelectStatement, table: &Table) -> Result<(), RqlError> {
+    for item in &select_stmt.items {
+        match item {
+            SelectItem::Expr(expr, _) => check_columns(expr, table)?,
+            SelectItem::StarExcept(excluded) => {
+                for name in excluded {
+                    lookup_column(name, table)?;
+                }
+            }
+            SelectItem::Star | SelectItem::TableStar(_) => {}
+        }
+    }
+    for cond in select_stmt.condition.iter().chain(&select_stmt.having) {
+        check_condition_columns(cond, table)?;
+        check_condition_types(cond, table)?;
+    }
+    for name in &select_stmt.group_by {
+        lookup_column(name, table)?;
+    }
+    for key in &select_stmt.order_by {
+        if aliased_expr(&select_stmt.items, &key.column).is_none() {
+            lookup_column(&key.column, table)?;
+        }
+    }
+    Ok(())
+}
+
+// The expression a SELECT item names with `AS alias`
+fn aliased_expr(items: &[SelectItem], alias: &str) -> Option<Expr> {
+    items.iter().find_map(|item| match item {
+        SelectItem::Expr(expr, Some(name)) if name == alias => Some(expr.clone()),
+        _ => None,
+    })
+}
+
+// DELETE and UPDATE must say which rows they touch; `WHERE 1 = 1` explicitly means all of them
+fn unconditional_error(statement: &str, verb: &str, table: &Table) -> RqlError {
+    RqlError::execution(format!(
+        "{} without WHERE would {} all {} row(s); add WHERE 1 = 1 to {} every row",
+        statement,
+        verb,
+        table.rows.len(),
+        verb
+    ))
+}
+
+// Total order used for sorting: numbers numerically, strings lexically, numbers before strings
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    fn rank(v: &Value) -> u8 {
+        match v {
+            Value::Int(_) | Value::Float(_) => 0,
+            Value::Str(_) => 1,
+            Value::Date(_) => 2,
+            _ => 3,
+        }
+    }
+    match (a, b) {
+        (Value::Int(x), Value::Int(y)) => x.cmp(y),
+        (Value::Int(_) | Value::Float(_), Value::Int(_) | Value::Float(_)) => a.as_f64().total_cmp(&b.as_f64()),
+        (Value::Str(x), Value::Str(y)) => x.cmp(y),
+        (Value::Date(x), Value::Date(y)) => x.cmp(y),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+// Apply a comparison operator; any comparison involving NULL is false
+fn compare(left: &Value, op: CompareOp, right: &Value, ignore_case: bool) -> Result<bool, RqlError> {
+    if *left == Value::Null || *right == Value::Null {
+        return Ok(false);
+    }
+    let ord = left.compare_with(right, ignore_case)?;
+    Ok(match op {
+        CompareOp::Eq => ord == Ordering::Equal,
+        CompareOp::NotEq => ord != Ordering::Equal,
+        CompareOp::Lt => ord == Ordering::Less,
+        CompareOp::LtEq => ord != Ordering::Greater,
+        CompareOp::Gt => ord == Ordering::Greater,
+        CompareOp::GtEq => ord != Ordering::Less,
+    })
+}
+
+// Stable multi-key sort; keys are (column index, ORDER BY key). Values of the same type are
+// ordered by Value::compare (numbers numerically, so 9 before 10); a key column mixing types,
+// e.g. INT and TEXT, sorts by Value::sort_rank between types instead of failing.
+// NULLS FIRST/LAST (or `null_order` for keys without either) places NULLs (and missing cells) at
+// that end whatever the direction.
+fn sort_by_keys<R: AsRef<[Value]>>(rows: &mut [R], keys: &[(usize, &OrderKey)], null_order: NullOrder) {
+    let is_null = |value: Option<&Value>| matches!(value, None | Some(Value::Null));
+    rows.sort_by(|a, b| {
+        for (idx, key) in keys {
+            let (x, y) = (a.as_ref().get(*idx), b.as_ref().get(*idx));
+            let ord = match (null_order.nulls_first(key), is_null(x), is_null(y)) {
+                (Some(first), x_null, y_null) if x_null != y_null => {
+                    let null_first = if x_null { Ordering::Less } else { Ordering::Greater };
+                    if first { null_first } else { null_first.reverse() }
+                }
+                _ => {
+                    let ord = match (x, y) {
+                        (Some(x), Some(y)) => sort_order(x, y),
+                        (x, y) => x.is_some().cmp(&y.is_some()),
+                    };
+                    if key.descending { ord.reverse() } else { ord }
+                }
+            };
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+        Ordering::Equal
+    });
+}
+
+// ORDER BY's order for two values: a total order, so sorting never fails
+fn sort_order(a: &Value, b: &Value) -> Ordering {
+    match a.sort_rank().cmp(&b.sort_rank()) {
+        Ordering::Equal => a.compare(b).unwrap_or(Ordering::Equal),
+        by_type => by_type,
+    }
+}
+
+// How a SELECT's items turn a row into output values: the result headers, and the columns
+// each `*` (or `* EXCEPT (...)`) item expands to
+struct Projection {
+    items: Vec<SelectItem>,
+    headers: Vec<String>,
+    star_columns: Vec<Vec<usize>>,
+}
+
+impl Projection {
+    fn new(items: Vec<SelectItem>, table: &Table) -> Result<Self, RqlError> {
+        // SELECT * -> headers are table.columns, or col0..colN spanning the widest row of an
+        // implicit table (shorter rows read NULL for the columns they lack)
+        let star_headers: Vec<String> = if !table.columns.is_empty() {
+            table.columns.clone()
+        } else {
+            (0..table.width()).map(|i| format!("col{}", i)).collect()
+        };
+
+        let mut star_columns: Vec<Vec<usize>> = Vec::new();
+        for item in &items {
+            match item {
+                SelectItem::Star => star_columns.push((0..star_headers.len()).collect()),
+                // The joined columns of that table, named `prefix.column`
+                SelectItem::TableStar(prefix) => {
+                    let prefix = format!("{}.", prefix);
+                    let columns: Vec<usize> =
+                        (0..star_headers.len()).filter(|&idx| star_headers[idx].starts_with(&prefix)).collect();
+                    if columns.is_empty() {
+                        return Err(RqlError::execution(format!("'{}*' matches no column", prefix)));
+                    }
+                    star_columns.push(columns);
+                }
+                SelectItem::StarExcept(excluded) => {
+                    let excluded = excluded
+                        .iter()
+                        .map(|name| lookup_column(name, table))
+                        .collect::<Result<HashSet<usize>, RqlError>>()?;
+                    let kept: Vec<usize> = (0..star_headers.len()).filter(|idx| !excluded.contains(idx)).collect();
+                    if kept.is_empty() {
+                        return Err(RqlError::execution("* EXCEPT excludes every column"));
+                    }
+                    star_columns.push(kept);
+                }
+                SelectItem::Expr(..) => star_columns.push(vec![]),
+            }
+        }
+
+        let mut headers: Vec<String> = Vec::new();
+        for (item, columns) in items.iter().zip(&star_columns) {
+            match item {
+                SelectItem::Star | SelectItem::StarExcept(_) | SelectItem::TableStar(_) => {
+                    headers.extend(columns.iter().map(|idx| star_headers[*idx].clone()))
+                }
+                SelectItem::Expr(_, Some(alias)) => headers.push(alias.clone()),
+                SelectItem::Expr(expr, None) => headers.push(expr_header(expr, &table.columns)),
+            }
+        }
+        Ok(Projection { items, headers, star_columns })
+    }
+
+    // Evaluates the requested items for one row
+    fn project(&self, row: RowRef, schema: &[String], ignore_case: bool) -> Result<Vec<Value>, RqlError> {
+        let mut out_row: Vec<Value> = Vec::new();
+        for (item, columns) in self.items.iter().zip(&self.star_columns) {
+            match item {
+                // Cells missing from a short row come out as NULL
+                SelectItem::Star | SelectItem::StarExcept(_) | SelectItem::TableStar(_) => {
+                    out_row.extend(columns.iter().map(|idx| row.values.get(*idx).cloned().unwrap_or(Value::Null)))
+                }
+                SelectItem::Expr(expr, _) => out_row.push(eval_expr(expr, row, schema, ignore_case)?),
+            }
+        }
+        Ok(out_row)
+    }
+}
+
+// A row together with its ORDER BY key values
+struct SortEntry<'a> {
+    key: Vec<Value>,
+    row: RowRef<'a>,
+}
+
+impl AsRef<[Value]> for SortEntry<'_> {
+    fn as_ref(&self) -> &[Value] {
+        &self.key
+    }
+}
+
+// Header for a projected expression; column references show the real column name
+fn expr_header(expr: &Expr, schema: &[String]) -> String {
+    match expr {
+        Expr::Column(name) => match resolve_column(name, schema).and_then(|idx| schema.get(idx)) {
+            Some(col) if col == name || is_positional(name) => col.clone(),
+            _ => name.clone(),
+        },
+        _ => expr.to_string(),
+    }
+}
+
+// A stored row as seen by expression evaluation, together with its rowid
+#[derive(Debug, Clone, Copy)]
+struct RowRef<'a> {
+    rowid: usize, // 1-based position in the table
+    values: &'a [Value],
+}
+
+impl<'a> RowRef<'a> {
+    fn at(table: &'a Table, pos: usize) -> Self {
+        RowRef { rowid: pos + 1, values: &table.rows[pos] }
+    }
+}
+
+impl AsRef<[Value]> for RowRef<'_> {
+    fn as_ref(&self) -> &[Value] {
+        self.values
+    }
+}
+
+// `rowid` names the row's position unless the table has a real column called that
+fn is_rowid(name: &str, schema: &[String]) -> bool {
+    name.eq_ignore_ascii_case("rowid") && resolve_column(name, schema).is_none()
+}
+
+// Evaluate an expression against a single row
+fn eval_expr(expr: &Expr, row: RowRef, schema: &[String], ignore_case: bool) -> Result<Value, RqlError> {
+    match expr {
+        Expr::Column(name) if is_rowid(name, schema) => Ok(Value::Int(row.rowid as i32)),
+        Expr::Column(name) => Ok(resolve_column(name, schema)
+            .and_then(|idx| row.values.get(idx))
+            .cloned()
+            .unwrap_or(Value::Null)),
+        Expr::Literal(value) => Ok(value.clone()),
+        Expr::Aggregate { .. } => Err(RqlError::execution(format!("Aggregate {} is not allowed here", expr))),
+        Expr::Binary { op, left, right } => {
+            apply_binary(*op, eval_expr(left, row, schema, ignore_case)?, eval_expr(right, row, schema, ignore_case)?)
+        }
+        Expr::Case { branches, otherwise } => {
+            let eval = |expr: &Expr| eval_expr(expr, row, schema, ignore_case);
+            eval_case(branches, otherwise.as_deref(), &eval, ignore_case)
+        }
+        Expr::Function { func, args } => {
+            apply_function(*func, args, &|arg| eval_expr(arg, row, schema, ignore_case), ignore_case)
+        }
+    }
+}
+
+// Calls a scalar function, computing each argument with `eval` only when the function needs it
+fn apply_function(
+    func: ScalarFunc,
+    args: &[Expr],
+    eval: &dyn Fn(&Expr) -> Result<Value, RqlError>,
+    ignore_case: bool,
+) -> Result<Value, RqlError> {
+    match func {
+        // Unlike ||, NULL arguments are skipped rather than making the result NULL
+        ScalarFunc::Concat => {
+            let args = args.iter().map(eval).collect::<Result<Vec<_>, _>>()?;
+            Ok(Value::Str(args.iter().filter_map(Value::as_text).collect()))
+        }
+        ScalarFunc::Coalesce => {
+            for arg in args {
+                let value = eval(arg)?;
+                if value != Value::Null {
+                    return Ok(value);
+                }
+            }
+            Ok(Value::Null)
+        }
+        // Equal as `=` compares, so NULLIF(2, 2.0) is NULL and a NULL argument never matches
+        ScalarFunc::NullIf => {
+            let (value, other) = (eval(&args[0])?, eval(&args[1])?);
+            if compare(&value, CompareOp::Eq, &other, ignore_case)? {
+                Ok(Value::Null)
+            } else {
+                Ok(value)
+            }
+        }
+        // The string functions give NULL for a NULL argument
+        ScalarFunc::Length | ScalarFunc::Upper | ScalarFunc::Lower | ScalarFunc::Trim | ScalarFunc::Substr => {
+            let args = args.iter().map(eval).collect::<Result<Vec<_>, _>>()?;
+            if args.contains(&Value::Null) {
+                return Ok(Value::Null);
+            }
+            let text = match &args[0] {
+                Value::Str(text) => text,
+                other => {
+                    return Err(RqlError::TypeMismatch(format!("{} expects TEXT, found {:?}", func, other)));
+                }
+            };
+            Ok(match func {
+                ScalarFunc::Length => Value::Int(i32::try_from(text.chars().count()).unwrap_or(i32::MAX)),
+                ScalarFunc::Upper => Value::Str(text.to_uppercase()),
+                ScalarFunc::Lower => Value::Str(text.to_lowercase()),
+                ScalarFunc::Trim => Value::Str(text.trim().to_string()),
+                _ => Value::Str(substring(text, &args[1..])?),
+            })
+        }
+        // So do the numeric ones
+        ScalarFunc::Abs | ScalarFunc::Round | ScalarFunc::Mod => {
+            let args = args.iter().map(eval).collect::<Result<Vec<_>, _>>()?;
+            if args.contains(&Value::Null) {
+                return Ok(Value::Null);
+            }
+            if let Some(other) = args.iter().find(|arg| !matches!(arg, Value::Int(_) | Value::Float(_))) {
+                return Err(RqlError::TypeMismatch(format!("{} expects numbers, found {:?}", func, other)));
+            }
+            match (func, &args[..]) {
+                (ScalarFunc::Abs, [Value::Int(n)]) => n
+                    .checked_abs()
+                    .map(Value::Int)
+                    .ok_or_else(|| RqlError::execution(format!("Integer overflow evaluating ABS({})", n))),
+                (ScalarFunc::Abs, [value]) => Ok(Value::Float(value.as_f64().abs())),
+                (ScalarFunc::Round, [value, rest @ ..]) => {
+                    let digits = match rest.first() {
+                        Some(Value::Int(digits)) => *digits,
+                        Some(other) => {
+                            let msg = format!("ROUND's digits must be an INT, found {:?}", other);
+                            return Err(RqlError::TypeMismatch(msg));
+                        }
+                        None => 0,
+                    };
+                    Ok(Value::Float(round_to(value.as_f64(), digits)))
+                }
+                (ScalarFunc::Mod, [Value::Int(a), Value::Int(b)]) => {
+                    if *b == 0 {
+                        return Err(RqlError::execution(format!("Division by zero evaluating MOD({}, {})", a, b)));
+                    }
+                    // Only i32::MIN % -1 overflows, and its remainder is 0
+                    Ok(Value::Int(a.checked_rem(*b).unwrap_or(0)))
+                }
+                (_, args) => {
+                    let found: Vec<String> = args.iter().map(|arg| format!("{:?}", arg)).collect();
+                    Err(RqlError::TypeMismatch(format!("{} expects INT arguments, found {}", func, found.join(", "))))
+                }
+            }
+        }
+    }
+}
+
+// `value` rounded half away from zero to `digits` decimals, or to tens, hundreds, ... for a
+// negative `digits`
+fn round_to(value: f64, digits: i32) -> f64 {
+    let scale = 10f64.powi(digits.clamp(-308, 308));
+    let scaled = value * scale;
+    // Past 2^52 a float has no fraction left to round, and scaling back could only lose precision
+    if !scaled.is_finite() || scaled.abs() >= 4_503_599_627_370_496.0 {
+        return value;
+    }
+    scaled.round() / scale
+}
+
+// SUBSTR(text, start [, length]) over characters, not bytes. Positions before 1 still count
+// towards the length, as in standard SQL, so SUBSTR('abc', 0, 2) is 'a'; a range past the end
+// of the text is cut short, down to ''.
+fn substring(text: &str, args: &[Value]) -> Result<String, RqlError> {
+    let int_arg = |value: &Value, what: &str| match value {
+        Value::Int(n) => Ok(i64::from(*n)),
+        other => Err(RqlError::TypeMismatch(format!("SUBSTR's {} must be an INT, found {:?}", what, other))),
+    };
+    let start = int_arg(&args[0], "start")? - 1;
+    let end = match args.get(1) {
+        Some(length) => match int_arg(length, "length")? {
+            length if length < 0 => return Err(RqlError::execution("SUBSTR's length cannot be negative")),
+            length => start + length,
+        },
+        None => i64::MAX,
+    };
+    let skip = start.max(0);
+    let take = (end - skip).max(0);
+    Ok(text.chars().skip(skip as usize).take(usize::try_from(take).unwrap_or(usize::MAX)).collect())
+}
+
+// The result of the first CASE branch whose condition holds, else the ELSE result (or NULL)
+fn eval_case(
+    branches: &[(Condition, Expr)],
+    otherwise: Option<&Expr>,
+    eval: &dyn Fn(&Expr) -> Result<Value, RqlError>,
+    ignore_case: bool,
+) -> Result<Value, RqlError> {
+    for (cond, result) in branches {
+        if eval_condition(cond, eval, ignore_case)? {
+            return eval(result);
+        }
+    }
+    otherwise.map_or(Ok(Value::Null), eval)
+}
+
+// Integer arithmetic, or floating-point if either operand is a FLOAT;
+// NULL operands give NULL, overflow and division by zero are errors
+fn apply_binary(op: BinaryOp, left: Value, right: Value) -> Result<Value, RqlError> {
+    let (l, r) = match (&left, &right) {
+        (Value::Null, _) | (_, Value::Null) => return Ok(Value::Null),
+        _ if op == BinaryOp::Concat => {
+            let (l, r) = (left.as_text().unwrap_or_default(), right.as_text().unwrap_or_default());
+            return Ok(Value::Str(l + &r));
+        }
+        (Value::Int(l), Value::Int(r)) => (*l, *r),
+        (Value::Int(_) | Value::Float(_), Value::Int(_) | Value::Float(_)) => {
+            return apply_float(op, left.as_f64(), right.as_f64());
+        }
+        _ => return Err(RqlError::TypeMismatch(format!("Cannot apply '{}' to {:?} and {:?}", op, left, right))),
+    };
+    let result = match op {
+        BinaryOp::Add => l.checked_add(r),
+        BinaryOp::Sub => l.checked_sub(r),
+        BinaryOp::Mul => l.checked_mul(r),
+        BinaryOp::Div if r == 0 => return Err(RqlError::execution("Division by zero")),
+        BinaryOp::Div => l.checked_div(r),
+        BinaryOp::Concat => unreachable!("|| is applied before the numeric cases"),
+    };
+    match result {
+        Some(v) => Ok(Value::Int(v)),
+        None => Err(RqlError::execution(format!("Integer overflow evaluating {} {} {}", l, op, r))),
+    }
+}
+
+fn apply_float(op: BinaryOp, l: f64, r: f64) -> Result<Value, RqlError> {
+    let result = match op {
+        BinaryOp::Add => l + r,
+        BinaryOp::Sub => l - r,
+        BinaryOp::Mul => l * r,
+        BinaryOp::Div if r == 0.0 => return Err(RqlError::execution("Division by zero")),
+        BinaryOp::Div => l / r,
+        BinaryOp::Concat => unreachable!("|| is applied before the numeric cases"),
+    };
+    if result.is_finite() {
+        Ok(Value::Float(result))
+    } else {
+        Err(RqlError::execution(format!("Float overflow evaluating {:?} {} {:?}", l, op, r)))
+    }
+}
+
+// How an index can narrow down the rows a condition has to be checked against
+enum IndexPlan<'a, 'c> {
+    // Every indexed column equals a literal (given in index column order)
+    Equal(&'a Index, Vec<&'c Value>),
+    // The sorted index's column lies between two bounds
+    Range(&'a Index, Bound<&'c Value>, Bound<&'c Value>),
+}
+
+// The EXPLAIN step for a WHERE condition, saying whether an index narrows it down
+fn filter_step(table: &Table, cond: &Condition, ignore_case: bool) -> String {
+    match plan_index(table, cond, ignore_case) {
+        Some(IndexPlan::Equal(index, _)) => format!("Filter rows: {} (index lookup via '{}')", cond, index.name),
+        Some(IndexPlan::Range(index, ..)) => format!("Filter rows: {} (index range scan via '{}')", cond, index.name),
+        None => format!("Filter rows: {} (full scan, no index)", cond),
+    }
+}
+
+// What EXPLAIN ANALYZE measures while `select_buffered` runs: each phase in order, with the rows
+// it produced and how long it took, and the rows read from the table (fewer with an index)
+#[derive(Default)]
+struct Profile {
+    phases: Vec<(String, usize, Duration)>,
+    examined: usize,
+}
+
+impl Profile {
+    fn record(&mut self, phase: String, rows: usize, started: Instant) {
+        self.phases.push((phase, rows, started.elapsed()));
+    }
+}
+
+// The ORDER BY step of a plan, e.g. "Sort by: age DESC, name ASC"
+fn sort_step(order_by: &[OrderKey]) -> String {
+    let keys: Vec<String> = order_by
+        .iter()
+        .map(|key| format!("{} {}{}", key.column, if key.descending { "DESC" } else { "ASC" }, key.nulls_clause()))
+        .collect();
+    format!("Sort by: {}", keys.join(", "))
+}
+
+// Picks an index for a condition. An index is usable when the condition (or one branch of a chain
+// of ANDs) sets every indexed column equal to a literal, e.g. `a = 1 AND b = 2` for an index on
+// (a, b); the widest such index wins. Failing that, a sorted index answers `<`, `<=`, `>`, `>=`
+// comparisons of its column with literals. The full condition is still checked per row.
+fn plan_index<'a, 'c>(table: &'a Table, cond: &'c Condition, ignore_case: bool) -> Option<IndexPlan<'a, 'c>> {
+    let mut comparisons = Vec::new();
+    collect_comparisons(table, cond, &mut comparisons);
+    if ignore_case {
+        // Index keys hold TEXT as stored, so a case-insensitive lookup has to scan
+        comparisons.retain(|(_, _, value)| !matches!(value, Value::Str(_)));
+    }
+    let equal = |idx: usize| {
+        comparisons.iter().find(|(i, op, _)| *i == idx && *op == CompareOp::Eq).map(|(_, _, value)| *value)
+    };
+    let equality = table
+        .indexes
+        .iter()
+        .filter_map(|index| {
+            let values = index
+                .columns
+                .iter()
+                .map(|col| equal(resolve_column(col, &table.columns)?))
+                .collect::<Option<Vec<&Value>>>()?;
+            Some((index, values))
+        })
+        .max_by_key(|(index, _)| index.columns.len());
+    if let Some((index, values)) = equality {
+        return Some(IndexPlan::Equal(index, values));
+    }
+
+    table.indexes.iter().filter(|index| index.kind == IndexKind::Sorted).find_map(|index| {
+        let idx = resolve_column(&index.columns[0], &table.columns)?;
+        let (mut lower, mut upper) = (Bound::Unbounded, Bound::Unbounded);
+        for (_, op, value) in comparisons.iter().filter(|(i, _, _)| *i == idx) {
+            match op {
+                CompareOp::Gt => lower = Bound::Excluded(*value),
+                CompareOp::GtEq => lower = Bound::Included(*value),
+                CompareOp::Lt => upper = Bound::Excluded(*value),
+                CompareOp::LtEq => upper = Bound::Included(*value),
+                _ => {}
+            }
+        }
+        match (lower, upper) {
+            (Bound::Unbounded, Bound::Unbounded) => None,
+            (lower, upper) => Some(IndexPlan::Range(index, lower, upper)),
+        }
+    })
+}
+
+// The `column op literal` (or `literal op column`) comparisons ANDed together at the top of a
+// condition, as (column index, operator with the column on the left, literal)
+fn collect_comparisons<'c>(table: &Table, cond: &'c Condition, out: &mut Vec<(usize, CompareOp, &'c Value)>) {
+    match cond {
+        Condition::Comparison { left: Expr::Column(name), op, right: Expr::Literal(value) } => {
+            if let Some(idx) = resolve_column(name, &table.columns) {
+                out.push((idx, *op, value));
+            }
+        }
+        Condition::Comparison { left: Expr::Literal(value), op, right: Expr::Column(name) } => {
+            if let Some(idx) = resolve_column(name, &table.columns) {
+                out.push((idx, op.flipped(), value));
+            }
+        }
+        Condition::And(left, right) => {
+            collect_comparisons(table, left, out);
+            collect_comparisons(table, right, out);
+        }
+        _ => {}
+    }
+}
+
+// The index keys a literal may be stored under: a string may be a DATE, and numbers
+// are looked up under both representations, so 2 finds 2.0 and vice versa
+fn key_variants(value: &Value) -> Vec<Value> {
+    let mut keys = vec![value.clone()];
+    match value {
+        Value::Str(text) => {
+            if let Ok(date) = parse_date(text) {
+                keys.push(date);
+            }
+        }
+        Value::Int(i) => keys.push(Value::Float(f64::from(*i))),
+        Value::Float(f) if f.fract() == 0.0 && *f >= f64::from(i32::MIN) && *f <= f64::from(i32::MAX) => {
+            keys.push(Value::Int(*f as i32))
+        }
+        _ => {}
+    }
+    keys
+}
+
+// The positions an index plan points at, in no particular order
+fn index_candidates<'a>(table: &Table, plan: IndexPlan<'a, '_>) -> (&'a Index, Vec<usize>) {
+    match plan {
+        IndexPlan::Equal(index, values) => {
+            // Every combination of the literals' possible stored forms
+            let mut keys: Vec<Vec<Value>> = vec![vec![]];
+            for value in values {
+                let variants = key_variants(value);
+                keys = keys
+                    .into_iter()
+                    .flat_map(|prefix| {
+                        variants.iter().map(move |variant| {
+                            let mut key = prefix.clone();
+                            key.push(variant.clone());
+                            key
+                        })
+                    })
+                    .collect();
+            }
+            let positions = match index.kind {
+                IndexKind::Hash => keys.iter().filter_map(|key| index.entries.get(key)).flatten().copied().collect(),
+                IndexKind::Sorted => keys
+                    .into_iter()
+                    .filter_map(|mut key| index.sorted.get(&SortKey(key.pop()?)))
+                    .flatten()
+                    .copied()
+                    .collect(),
+            };
+            (index, positions)
+        }
+        IndexPlan::Range(index, lower, upper) => {
+            let is_date = resolve_column(&index.columns[0], &table.columns)
+                .and_then(|idx| table.column_types.get(idx).copied().flatten())
+                == Some(DataType::Date);
+            let bound = |bound: Bound<&Value>| bound.map(|value| SortKey(range_key(value, is_date)));
+            let (lower, upper) = (bound(lower), bound(upper));
+            // BTreeMap::range panics on an inverted or empty-and-open range, which simply matches nothing
+            let empty = match (&lower, &upper) {
+                (Bound::Included(lo), Bound::Included(hi)) => lo > hi,
+                (Bound::Included(lo) | Bound::Excluded(lo), Bound::Included(hi) | Bound::Excluded(hi)) => lo >= hi,
+                _ => false,
+            };
+            if empty {
+                return (index, vec![]);
+            }
+            (index, index.sorted.range((lower, upper)).flat_map(|(_, positions)| positions).copied().collect())
+        }
+    }
+}
+
+// A range bound on a DATE column written as text is read as a date, like a comparison would
+fn range_key(value: &Value, is_date: bool) -> Value {
+    match value {
+        Value::Str(text) if is_date => parse_date(text).unwrap_or_else(|_| value.clone()),
+        _ => value.clone(),
+    }
+}
+
+// Positions of the rows satisfying `cond` (every row when there is none), in table order.
+// An equality on an indexed column only checks the rows the index points at.
+fn matching_positions(
+    table: &Table,
+    cond: Option<&Condition>,
+    verbose: bool,
+    ignore_case: bool,
+) -> Result<Vec<usize>, RqlError> {
+    let cond = match cond {
+        Some(cond) => cond,
+        None => return Ok((0..table.rows.len()).collect()),
+    };
+    let candidates: Vec<usize> = match plan_index(table, cond, ignore_case) {
+        Some(plan) => index_positions(table, plan, verbose),
+        None => (0..table.rows.len()).collect(),
+    };
+
+    let mut positions = Vec::with_capacity(candidates.len());
+    for pos in candidates {
+        let row = RowRef::at(table, pos);
+        let matched = row_matches(cond, row, &table.columns, ignore_case)?;
+        if verbose {
+            println!("Checking row {:?}: {}", row.values, if matched { "match" } else { "no match" });
+        }
+        if matched {
+            positions.push(pos);
+        }
+    }
+    Ok(positions)
+}
+
+// The positions an index plan leads to, in row order; the condition still has to be checked
+fn index_positions(table: &Table, plan: IndexPlan<'_, '_>, verbose: bool) -> Vec<usize> {
+    let (index, mut positions) = index_candidates(table, plan);
+    // Several key variants can land on the same sorted-index entry
+    positions.sort_unstable();
+    positions.dedup();
+    if verbose {
+        println!("Using index '{}': {} candidate row(s)", index.name, positions.len());
+    }
+    positions
+}
+
+// Evaluate a WHERE condition against a single row
+fn row_matches(cond: &Condition, row: RowRef, schema: &[String], ignore_case: bool) -> Result<bool, RqlError> {
+    eval_condition(cond, &|expr| eval_expr(expr, row, schema, ignore_case), ignore_case)
+}
+
+// Evaluate a condition, computing each operand with `eval` (over a row, or a group of rows).
+// With ignore_case TEXT comparisons and LIKE disregard case, as ILIKE always does.
+fn eval_condition(
+    cond: &Condition,
+    eval: &dyn Fn(&Expr) -> Result<Value, RqlError>,
+    ignore_case: bool,
+) -> Result<bool, RqlError> {
+    match cond {
+        Condition::Comparison { left, op, right } => {
+            let (l, r) = (eval(left)?, eval(right)?);
+            compare(&l, *op, &r, ignore_case).map_err(|e| in_condition(e, cond))
+        }
+        Condition::InList { left, values, negated } => {
+            let left = eval(left)?;
+            for value in values {
+                if compare(&left, CompareOp::Eq, value, ignore_case).map_err(|e| in_condition(e, cond))? {
+                    return Ok(!negated);
+                }
+            }
+            // `x NOT IN (1, NULL)` can't rule out that x is the unknown value, so it is false,
+            // as is NOT IN with a NULL on the left
+            Ok(*negated && left != Value::Null && !values.contains(&Value::Null))
+        }
+        Condition::Like { left, pattern, escape, ignore_case: ilike, negated } => match eval(left)? {
+            Value::Null => Ok(false),
+            Value::Str(text) | Value::Date(text) => {
+                Ok(like_matches(&text, pattern, *escape, *ilike || ignore_case) != *negated)
+            }
+            other => {
+                let msg = format!("LIKE requires text, got {} ({})", Expr::Literal(other.clone()), other.type_name());
+                Err(in_condition(RqlError::TypeMismatch(msg), cond))
+            }
+        },
+        Condition::Distinct { left, right, negated } => {
+            let (l, r) = (eval(left)?, eval(right)?);
+            let same = match (&l, &r) {
+                (Value::Null, Value::Null) => true,
+                (Value::Null, _) | (_, Value::Null) => false,
+                _ => compare(&l, CompareOp::Eq, &r, ignore_case).map_err(|e| in_condition(e, cond))?,
+            };
+            Ok(same == *negated)
+        }
+        Condition::InSelect { .. } => Err(RqlError::execution("Subquery was not evaluated before filtering")),
+        Condition::And(left, right) => {
+            Ok(eval_condition(left, eval, ignore_case)? && eval_condition(right, eval, ignore_case)?)
+        }
+        Condition::Or(left, right) => {
+            Ok(eval_condition(left, eval, ignore_case)? || eval_condition(right, eval, ignore_case)?)
+        }
+    }
+}
+
+// One element of a LIKE pattern
+enum LikePart {
+    Any,        // %
+    One,        // _
+    Char(char), // a literal character, possibly escaped
+}
+
+// SQL LIKE matching; case-sensitive like the other comparisons unless ignore_case is set
+fn like_matches(text: &str, pattern: &str, escape: Option<char>, ignore_case: bool) -> bool {
+    let mut parts = vec![];
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        parts.push(match c {
+            c if Some(c) == escape => LikePart::Char(chars.next().unwrap_or(c)),
+            '%' => LikePart::Any,
+            '_' => LikePart::One,
+            c => LikePart::Char(c),
+        });
+    }
+    let text: Vec<char> = text.chars().collect();
+
+    // Greedy match that backtracks to the most recent % on a mismatch
+    let (mut t, mut p) = (0, 0);
+    let mut retry: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match parts.get(p) {
+            Some(LikePart::Any) => {
+                retry = Some((p, t));
+                p += 1;
+            }
+            Some(LikePart::One) => {
+                t += 1;
+                p += 1;
+            }
+            Some(LikePart::Char(c)) if *c == text[t] || ignore_case && c.to_lowercase().eq(text[t].to_lowercase()) => {
+                t += 1;
+                p += 1;
+            }
+            _ => match retry {
+                // Let the % absorb one more character and try again
+                Some((any_p, any_t)) => {
+                    retry = Some((any_p, any_t + 1));
+                    p = any_p + 1;
+                    t = any_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    parts[p..].iter().all(|part| matches!(part, LikePart::Any))
+}
+
+// The first value that compares `wanted` (Less for MIN, Greater for MAX) to every other one,
+// or NULL when there are none. Values that can't be compared, e.g. TEXT and INT, are an error.
+fn extreme(values: Vec<Value>, wanted: Ordering) -> Result<Value, RqlError> {
+    let mut best: Option<Value> = None;
+    for value in values {
+        best = match best {
+            Some(best) if value.compare(&best)? != wanted => Some(best),
+            _ => Some(value),
+        };
+    }
+    Ok(best.unwrap_or(Value::Null))
+}
+
+// Names the aggregate a type error came from, e.g. "... (in MAX(price))"
+fn in_aggregate(error: RqlError, expr: &Expr) -> RqlError {
+    match error {
+        RqlError::TypeMismatch(msg) => RqlError::TypeMismatch(format!("{} (in {})", msg, expr)),
+        other => other,
+    }
+}
+
+// Names the condition a type error came from, e.g. "... (in price > 'cheap')"
+fn in_condition(error: RqlError, cond: &Condition) -> RqlError {
+    match error {
+        RqlError::TypeMismatch(msg) => RqlError::TypeMismatch(format!("{} (in {})", msg, cond)),
+        other => other,
+    }
+}
+
+// SELECT ... GROUP BY ... [HAVING ...] [ORDER BY ...]
+fn execute_grouped_select(
+    select_stmt: &parser::SelectStatement,
+    table: &Table,
+    rows: Vec<RowRef>,
+    options: QueryOptions,
+) -> Result<ExecResult, RqlError> {
+    let ignore_case = options.ignore_case;
+    let schema = &table.columns;
+    let key_indices = select_stmt
+        .group_by
+        .iter()
+        .map(|col| lookup_column(col, table))
+        .collect::<Result<Vec<usize>, RqlError>>()?;
+
+    // Bucket rows by their key, keeping groups in order of first appearance
+    let mut groups: Vec<(Vec<Value>, Vec<RowRef>)> = Vec::new();
+    for row in rows {
+        let key: Vec<Value> = key_indices
+            .iter()
+            .map(|idx| row.values.get(*idx).cloned().unwrap_or(Value::Null))
+            .collect();
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, members)) => members.push(row),
+            None => groups.push((key, vec![row])),
+        }
+    }
+    // Without GROUP BY there is exactly one group, even when no rows matched
+    if key_indices.is_empty() && groups.is_empty() {
+        groups.push((vec![], vec![]));
+    }
+
+    // Every bare column in the SELECT list must be a grouping key
+    let mut headers = Vec::new();
+    for item in &select_stmt.items {
+        match item {
+            SelectItem::Star | SelectItem::StarExcept(_) | SelectItem::TableStar(_) => {
+                return Err(RqlError::execution("SELECT * is not allowed with GROUP BY"));
+            }
+            SelectItem::Expr(expr, alias) => {
+                check_grouped(expr, &key_indices, schema)?;
+                headers.push(alias.clone().unwrap_or_else(|| expr_header(expr, schema)));
+            }
+        }
+    }
+
+    let mut rows_out: Vec<Vec<Value>> = Vec::new();
+    for (_, members) in &groups {
+        if let Some(cond) = &select_stmt.having
+            && !eval_condition(cond, &|expr| eval_group_expr(expr, members, schema, ignore_case), ignore_case)?
+        {
+            continue;
+        }
+        let mut out_row = Vec::new();
+        for item in &select_stmt.items {
+            if let SelectItem::Expr(expr, _) = item {
+                out_row.push(eval_group_expr(expr, members, schema, ignore_case)?);
+            }
+        }
+        rows_out.push(out_row);
+    }
+
+    // ORDER BY keys of a grouped query refer to the output columns
+    if !select_stmt.order_by.is_empty() {
+        let mut keys = Vec::new();
+        for key in &select_stmt.order_by {
+            let name = &key.column;
+            let header = expr_header(&Expr::Column(name.clone()), schema);
+            match headers.iter().position(|h| *h == *name || *h == header) {
+                Some(idx) => keys.push((idx, key)),
+                None => {
+                    return Err(RqlError::execution(format!(
+                        "ORDER BY column '{}' must appear in the SELECT list of a grouped query",
+                        name
+                    )))
+                }
+            }
+        }
+        sort_by_keys(&mut rows_out, &keys, options.null_order);
+    }
+
+    Ok(ExecResult::Rows(QueryResult { columns: headers, rows: rows_out }))
+}
+
+// Columns outside aggregate calls must be grouping keys
+fn check_grouped(expr: &Expr, key_indices: &[usize], schema: &[String]) -> Result<(), RqlError> {
+    match expr {
+        Expr::Column(name) if !resolve_column(name, schema).is_some_and(|idx| key_indices.contains(&idx)) => {
+            Err(RqlError::execution(format!(
+                "Column '{}' must appear in GROUP BY or be used in an aggregate",
+                name
+            )))
+        }
+        Expr::Binary { left, right, .. } => {
+            check_grouped(left, key_indices, schema)?;
+            check_grouped(right, key_indices, schema)
+        }
+        Expr::Case { branches, otherwise } => {
+            for (cond, result) in branches {
+                for operand in cond.operands() {
+                    check_grouped(operand, key_indices, schema)?;
+                }
+                check_grouped(result, key_indices, schema)?;
+            }
+            otherwise.as_ref().map_or(Ok(()), |expr| check_grouped(expr, key_indices, schema))
+        }
+        Expr::Function { args, .. } => args.iter().try_for_each(|arg| check_grouped(arg, key_indices, schema)),
+        _ => Ok(()),
+    }
+}
+
+// Evaluate an expression over one group of rows
+fn eval_group_expr(expr: &Expr, members: &[RowRef], schema: &[String], ignore_case: bool) -> Result<Value, RqlError> {
+    match expr {
+        Expr::Aggregate { func, arg, distinct, separator } => {
+            // Non-NULL argument values of the group (COUNT(*) counts rows instead)
+            let arg = match arg {
+                Some(arg) => arg,
+                None => return Ok(Value::Int(members.len() as i32)),
+            };
+            let mut values = Vec::new();
+            for row in members {
+                let v = eval_expr(arg, *row, schema, ignore_case)?;
+                if v != Value::Null {
+                    values.push(v);
+                }
+            }
+            if *distinct {
+                // Keep the first of each value, so GROUP_CONCAT(DISTINCT ...) stays in row order
+                let mut seen = HashSet::new();
+                values.retain(|v| seen.insert(v.clone()));
+            }
+            match func {
+                AggregateFunc::Count => Ok(Value::Int(values.len() as i32)),
+                AggregateFunc::Sum => {
+                    if values.is_empty() {
+                        return Ok(Value::Null);
+                    }
+                    // Summed like `a + b + ...`, so a single FLOAT makes the total a FLOAT, and an
+                    // INT total past the INT range is an error rather than wrapping around
+                    let mut sum = Value::Int(0);
+                    for v in values {
+                        if !matches!(v, Value::Int(_) | Value::Float(_)) {
+                            return Err(RqlError::TypeMismatch(format!("SUM requires numeric values, found {:?}", v)));
+                        }
+                        let int_total = matches!((&sum, &v), (Value::Int(_), Value::Int(_)));
+                        sum = apply_binary(BinaryOp::Add, sum, v).map_err(|e| match e {
+                            RqlError::ExecutionError(_) if int_total => {
+                                let float_arg = match arg.as_ref() {
+                                    Expr::Column(name) => format!("{} * 1.0", name),
+                                    other => format!("({}) * 1.0", other),
+                                };
+                                RqlError::execution(format!(
+                                    "{} is outside the INT range; SUM({}) totals it as a FLOAT",
+                                    expr, float_arg
+                                ))
+                            }
+                            other => other,
+                        })?;
+                    }
+                    Ok(sum)
+                }
+                AggregateFunc::Min => extreme(values, Ordering::Less).map_err(|e| in_aggregate(e, expr)),
+                AggregateFunc::Max => extreme(values, Ordering::Greater).map_err(|e| in_aggregate(e, expr)),
+                // Rows are joined in table order
+                AggregateFunc::GroupConcat if values.is_empty() => Ok(Value::Null),
+                AggregateFunc::GroupConcat => {
+                    let texts: Vec<String> = values.iter().filter_map(Value::as_text).collect();
+                    Ok(Value::Str(texts.join(separator.as_deref().unwrap_or(","))))
+                }
+                // Always a FLOAT, so AVG of 1 and 2 is 1.5
+                AggregateFunc::Avg if values.is_empty() => Ok(Value::Null),
+                AggregateFunc::Avg => {
+                    let mut total = 0.0;
+                    for v in &values {
+                        if !matches!(v, Value::Int(_) | Value::Float(_)) {
+                            return Err(RqlError::TypeMismatch(format!("AVG requires numeric values, found {:?}", v)));
+                        }
+                        total += v.as_f64();
+                    }
+                    Ok(Value::Float(total / values.len() as f64))
+                }
+            }
+        }
+        Expr::Binary { op, left, right } => apply_binary(
+            *op,
+            eval_group_expr(left, members, schema, ignore_case)?,
+            eval_group_expr(right, members, schema, ignore_case)?,
+        ),
+        Expr::Case { branches, otherwise } => {
+            let eval = |expr: &Expr| eval_group_expr(expr, members, schema, ignore_case);
+            eval_case(branches, otherwise.as_deref(), &eval, ignore_case)
+        }
+        Expr::Function { func, args } => {
+            let eval = |arg: &Expr| eval_group_expr(arg, members, schema, ignore_case);
+            apply_function(*func, args, &eval, ignore_case)
+        }
+        // Grouping keys are constant within a group
+        _ => match members.first() {
+            Some(row) => eval_expr(expr, *row, schema, ignore_case),
+            None => Ok(Value::Null),
+        },
+    }
 }
@@ -0,0 +1,71 @@
+pub mod csv;
+pub mod diff;
+pub mod error;
+pub mod executor;
+pub mod parser;
+pub mod render;
+pub mod row;
+pub mod shared;
+pub mod wal;
+
+use crate::error::RqlError;
+use crate::executor::{Database, ExecResult, QueryResult, SelectRows};
+use crate::parser::{Limits, Statement, parse, parse_optional, tokenize_with_limits};
+
+// Facade for embedding the engine: open a database file, run SQL, save.
+// Changes stay in memory until `save` is called.
+pub struct Engine {
+    pub db: Database,
+    path: String,
+    limits: Limits,
+}
+
+impl Engine {
+    // Load the database at `path`, or start an empty one if the file doesn't exist yet.
+    // Statements a crashed REPL session left in the write-ahead log are applied too.
+    pub fn open(path: &str) -> Result<Engine, Box<dyn std::error::Error>> {
+        Engine::open_with_limits(path, Limits::default())
+    }
+
+    // Like `open`, but SQL passed to `run` and the other methods may only use names and string
+    // literals within `limits`; longer ones are a syntax error
+    pub fn open_with_limits(path: &str, limits: Limits) -> Result<Engine, Box<dyn std::error::Error>> {
+        let mut db = if std::path::Path::new(path).exists() {
+            Database::load(path)?
+        } else {
+            Database::new()
+        };
+        wal::replay(&mut db, path)?;
+        Ok(Engine { db, path: path.to_string(), limits })
+    }
+
+    // Tokenize, parse and execute a single statement. Blank input (or a lone `;`) does nothing.
+    pub fn run(&mut self, sql: &str) -> Result<ExecResult, RqlError> {
+        let tokens = tokenize_with_limits(sql, &self.limits)?;
+        match parse_optional(&tokens)? {
+            Some(statement) => self.db.execute(statement),
+            None => Ok(ExecResult::RowsAffected(0)),
+        }
+    }
+
+    // Like `run`, but expects a statement that produces rows
+    pub fn query(&mut self, sql: &str) -> Result<QueryResult, RqlError> {
+        match self.run(sql)? {
+            ExecResult::Rows(result) => Ok(result),
+            other => Err(RqlError::execution(format!("Statement did not return rows: {:?}", other))),
+        }
+    }
+
+    // Like `query`, but hands the rows of a SELECT over one at a time (see `Database::select_iter`)
+    pub fn query_iter(&self, sql: &str) -> Result<SelectRows<'_>, RqlError> {
+        match parse(&tokenize_with_limits(sql, &self.limits)?)? {
+            Statement::Select(select_stmt) => self.db.select_iter(select_stmt),
+            other => Err(RqlError::execution(format!("query_iter only runs a single SELECT, not {:?}", other))),
+        }
+    }
+
+    // Persist the database to the path it was opened from
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.db.save(&self.path)
+    }
+}
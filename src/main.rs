@@ -1,96 +1,1101 @@
-mod parser;
-mod executor;
-
-use crate::parser::{parse, tokenize};
-use crate::executor::Database;
+use rustql_lavanya::error::RqlError;
+use rustql_lavanya::executor::{ConstraintKind, DataFormat, Database, ExecResult, QueryOptions};
+use rustql_lavanya::parser::{
+    parse, parse_program, quote_name, statement_ranges, tokenize, tokenize_with_offsets, CopyStatement, Expr, Statement,
+    Token, Value,
+};
+use rustql_lavanya::render::{render_table, to_json, OutputFormat, RenderOptions};
+use rustql_lavanya::wal::{self, WriteAheadLog};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::fs::File;
+use std::io::Write;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+use unicode_width::UnicodeWidthChar;
 
 const DB_FILE: &str = "database.bin";
+const HISTORY_FILE: &str = ".rustql_history";
+// By default the REPL logs each change and rewrites the whole database file once this many are
+// logged (see `.autosave`)
+const CHECKPOINT_ENTRIES: usize = 1000;
+// With `.pager on`, result tables are shown this many lines at a time
+const PAGE_LINES: usize = 40;
+
+// Command-line options
+struct Options {
+    script: Option<String>,
+    command: Option<String>,
+    stop_on_error: bool,
+    // Commit each statement of a script or -c batch on its own instead of all or nothing
+    autocommit: bool,
+    db_path: String,
+    // False with --no-persist: nothing is loaded from or saved to db_path
+    persist: bool,
+    // INSERT into a missing table creates it (--implicit-tables)
+    implicit_tables: bool,
+    // Refuse statements that change data and never write the file (--readonly)
+    read_only: bool,
+    // Keep the file each save replaces as <db_path>.bak (--backup)
+    backup: bool,
+    // Script run before the REPL's first prompt (--init), and whether its failure stops the
+    // REPL from starting (--strict-init)
+    init: Option<String>,
+    strict_init: bool,
+}
+
+// When the REPL rewrites the database file on its own (`.autosave`). Changes are logged as they
+// run whatever the policy, and `save` and `quit` always write the file.
+struct SavePolicy {
+    cadence: Cadence,
+    last_save: Instant,
+    // Set by a statement the log can't replay as it ran, and kept until the next save, so one run
+    // inside a transaction is saved once the transaction ends
+    pending: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Cadence {
+    Off,
+    // Once this many changes are in the log
+    Statements(usize),
+    // After the first change once this long has passed since the last save
+    Interval(Duration),
+}
+
+impl SavePolicy {
+    fn new(cadence: Cadence) -> Self {
+        SavePolicy { cadence, last_save: Instant::now(), pending: false }
+    }
+
+    // Whether the file is due to be rewritten, given how many changes the log holds
+    fn due(&self, logged: usize) -> bool {
+        self.pending
+            || match self.cadence {
+                Cadence::Off => false,
+                Cadence::Statements(n) => logged >= n,
+                Cadence::Interval(interval) => logged > 0 && self.last_save.elapsed() >= interval,
+            }
+    }
+
+    fn saved(&mut self) {
+        self.last_save = Instant::now();
+        self.pending = false;
+    }
+}
+
+impl std::str::FromStr for Cadence {
+    type Err = String;
+
+    // `off`, `on` (the default cadence), `every N` (changes), `every Ns` or `every Nm`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let usage = || format!("unknown setting '{}' (expected off, on, every N, every Ns or every Nm)", s);
+        match s.as_str() {
+            "off" => return Ok(Cadence::Off),
+            "on" => return Ok(Cadence::Statements(CHECKPOINT_ENTRIES)),
+            _ => {}
+        }
+        let count = s.strip_prefix("every").ok_or_else(usage)?.trim();
+        let (digits, unit) = match count.strip_suffix('s') {
+            Some(digits) => (digits, 1),
+            None => match count.strip_suffix('m') {
+                Some(digits) => (digits, 60),
+                None => (count, 0),
+            },
+        };
+        match digits.trim().parse::<u64>() {
+            Ok(n) if n > 0 && unit == 0 => Ok(Cadence::Statements(n as usize)),
+            Ok(n) if n > 0 => Ok(Cadence::Interval(Duration::from_secs(n * unit))),
+            _ => Err(usage()),
+        }
+    }
+}
+
+impl std::fmt::Display for Cadence {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Cadence::Off => write!(f, "off (the file is written on save and quit)"),
+            Cadence::Statements(1) => write!(f, "after every change"),
+            Cadence::Statements(n) => write!(f, "every {} changes", n),
+            Cadence::Interval(interval) => write!(f, "every {}s", interval.as_secs()),
+        }
+    }
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        script: None,
+        command: None,
+        stop_on_error: false,
+        autocommit: false,
+        db_path: DB_FILE.to_string(),
+        persist: true,
+        implicit_tables: false,
+        read_only: false,
+        backup: false,
+        init: None,
+        strict_init: false,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--file" | "-f" => match args.next() {
+                Some(path) => options.script = Some(path),
+                None => return Err(format!("{} requires a file path", arg)),
+            },
+            "--command" | "-c" => match args.next() {
+                Some(sql) => options.command = Some(sql),
+                None => return Err(format!("{} requires a SQL statement", arg)),
+            },
+            "--stop-on-error" => options.stop_on_error = true,
+            "--autocommit" => options.autocommit = true,
+            "--no-persist" => options.persist = false,
+            "--implicit-tables" => options.implicit_tables = true,
+            "--readonly" | "--read-only" => options.read_only = true,
+            "--backup" => options.backup = true,
+            "--init" => match args.next() {
+                Some(path) => options.init = Some(path),
+                None => return Err(format!("{} requires a file path", arg)),
+            },
+            "--strict-init" => options.strict_init = true,
+            "--db" => match args.next() {
+                Some(path) => options.db_path = path,
+                None => return Err(format!("{} requires a database path", arg)),
+            },
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+    if options.strict_init && options.init.is_none() {
+        return Err("--strict-init requires --init".to_string());
+    }
+    // -c runs on its own, so a script alongside it would never run
+    if options.command.is_some() && options.script.is_some() {
+        return Err("-c cannot be combined with --file".to_string());
+    }
+    if options.command.is_some() && options.init.is_some() {
+        return Err("-c cannot be combined with --init".to_string());
+    }
+    Ok(options)
+}
 
 fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            eprintln!("Usage: rustql_lavanya [--db database.bin|data.json | --no-persist] [--file script.sql | -c \"SQL\"] [--autocommit [--stop-on-error]] [--implicit-tables] [--readonly] [--backup] [--init setup.sql [--strict-init]]");
+            std::process::exit(2);
+        }
+    };
+    // None in --no-persist mode, where the database lives only in memory
+    let db_file = options.persist.then_some(options.db_path.as_str());
+
+    // Single-command mode: run quietly so output can be consumed by scripts
+    if let Some(sql) = &options.command {
+        let mut db = match open_database(db_file) {
+            Ok(db) => db.map(|(db, _)| db).unwrap_or_default(),
+            Err(e) => {
+                eprintln!("Error loading {}: {}", options.db_path, e);
+                std::process::exit(1);
+            }
+        };
+        db.implicit_tables = options.implicit_tables;
+        db.read_only = options.read_only;
+        db.keep_backup = options.backup;
+        let ok = run_command(&mut db, sql);
+        // A failed batch saves nothing, unless each statement commits on its own
+        if (ok || options.autocommit)
+            && !options.read_only
+            && let Some(db_file) = db_file
+            && let Err(e) = db.save(db_file)
+        {
+            eprintln!("Error saving database: {}", e);
+            std::process::exit(1);
+        }
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
     // Try to load existing database, or create new one
-    let mut db = match Database::load(DB_FILE) {
-        Ok(loaded_db) => {
-            println!("Loaded existing database from {}", DB_FILE);
-            loaded_db
+    // An unreadable file is reported rather than silently replaced by an empty database
+    let (mut db, db_file) = match open_database(db_file) {
+        Ok(Some((loaded_db, replayed))) => {
+            println!("Loaded existing database from {}", options.db_path);
+            if replayed > 0 {
+                println!("Replayed {} statement(s) from {}", replayed, wal::log_path(&options.db_path));
+            }
+            (loaded_db, db_file)
+        }
+        Ok(None) if db_file.is_none() => {
+            println!("Starting with in-memory database (nothing will be saved)");
+            (Database::new(), None)
         }
-        Err(_) => {
+        Ok(None) if options.read_only => {
+            println!("Error: {} does not exist, and --readonly never creates it", options.db_path);
+            std::process::exit(1);
+        }
+        Ok(None) => {
             println!("Starting with new database");
-            Database::new()
+            (Database::new(), db_file)
+        }
+        Err(e) => {
+            println!("Error loading {}: {}", options.db_path, e);
+            // A script has no one to ask, and a read-only session must not move the file
+            let choice = if options.script.is_none() && !options.read_only {
+                choose_recovery(&options.db_path)
+            } else {
+                None
+            };
+            match choice {
+                Some(Recovery::InMemory) => {
+                    println!("Starting with in-memory database ({} is left as it is)", options.db_path);
+                    (Database::new(), None)
+                }
+                Some(Recovery::MovedAside(moved)) => {
+                    println!("Moved {} to {}; starting with new database", options.db_path, moved);
+                    (Database::new(), db_file)
+                }
+                None => {
+                    println!("{} was not changed", options.db_path);
+                    std::process::exit(1);
+                }
+            }
         }
     };
+    db.implicit_tables = options.implicit_tables;
+    db.read_only = options.read_only;
+    db.keep_backup = options.backup;
+    // Read-only sessions never write the file, nor start a log next to it
+    let db_file = if options.read_only { None } else { db_file };
+    if options.read_only {
+        println!("Read-only: statements that change data are refused and nothing is saved");
+    }
+
+    if let Some(path) = &options.script {
+        let ok = run_script(&mut db, path, options.stop_on_error, options.autocommit);
+        if let Some(db_file) = db_file {
+            match db.save(db_file) {
+                Ok(_) => println!("Database saved to {}", db_file),
+                Err(e) => println!("Error saving database: {}", e),
+            }
+        }
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
+    // The init script runs like --file, before the snapshot below takes in what it changed
+    if let Some(path) = &options.init
+        && !run_script(&mut db, path, options.stop_on_error, options.autocommit)
+    {
+        if options.strict_init {
+            println!("Init script {} failed; not starting (--strict-init)", path);
+            std::process::exit(1);
+        }
+        println!("Warning: init script {} failed; starting anyway", path);
+    }
+
+    // Changes are logged as they run; the replayed log is folded into the snapshot first.
+    // Without a usable log the REPL falls back to saving after every statement.
+    let mut log = None;
+    if let Some(db_file) = db_file
+        && let Err(e) = checkpoint(&db, db_file, &mut log)
+    {
+        println!("Warning: Could not start {} ({}); saving after each statement instead", wal::log_path(db_file), e);
+    }
 
     println!("Mini SQL Engine - Enter SQL commands (type 'quit' to exit)");
     println!("Supported commands:");
     println!("  CREATE TABLE table_name (col1, col2, ...);");
+    println!("  CREATE INDEX index_name ON table_name(col);");
     println!("  INSERT INTO table_name VALUES (val1, val2, ...);");
+    println!("  INSERT INTO table_name SELECT ...;");
     println!("  SELECT * FROM table_name;");
     println!("  SELECT col1, col2 FROM table_name;");
     println!("  UPDATE table_name SET col = value WHERE condition;");
     println!("  DELETE FROM table_name WHERE condition;");
+    println!("  TRUNCATE TABLE table_name;");
+    println!("  VACUUM;");
+    println!("  DESCRIBE table_name;");
+    println!("  SHOW TABLES;");
+    println!("  EXPLAIN SELECT ...;");
+    println!("  EXPLAIN ANALYZE SELECT ...;");
+    println!("  EXPLAIN DELETE ...; / EXPLAIN UPDATE ...;");
+    println!("  IMPORT table_name FROM 'file.csv';");
+    println!("  EXPORT table_name TO 'file.csv';");
+    println!("  COPY table_name FROM STDIN [DELIMITER ','];");
+    println!("  DUMP [table_name];");
+    println!("  DIFF 'old.bin' 'new.bin';");
+    println!("  MERGE FROM 'other.bin' [ON CONFLICT ERROR|SKIP|OVERWRITE];");
+    println!("  CHECKSUM;");
+    println!("  BEGIN; / COMMIT; / ROLLBACK;");
     println!("\nSpecial commands:");
     println!("  save  - Manually save database");
     println!("  debug - Show database structure and contents");
+    println!("  verbose - Toggle execution diagnostics");
+    println!("  .schema table_name - Show a table's columns");
+    println!("  .import table_name file.csv|file.json - Load rows from a file");
+    println!("  .export table_name file.csv|file.json - Write a table to a file");
+    println!("  .format table|json - Choose how query results are displayed");
+    println!("  .stats table_name - Show the row count and each column's distinct values, NULLs, range and indexes");
+    println!("  .output FILE - Write query results to FILE; .output alone goes back to the terminal");
+    println!("  .nullvalue TEXT - Text shown for NULL cells (default NULL)");
+    println!("  .precision N [scientific] - Decimal places shown for FLOATs (default 6)");
+    println!("  .timing on|off - Show each statement's row count and time");
+    println!("  .pager on|off - Show long results a page at a time (q at the prompt stops)");
+    println!("  .autosave off|on|every N|every Ns|every Nm - When the database file is rewritten");
+    println!("  .ci on|off - Compare text ignoring case in WHERE, LIKE, IN, ...");
+    println!("  .nulls first|last|smallest - Where ORDER BY puts NULLs unless NULLS FIRST/LAST says");
+    println!("  .style ascii|markdown|compact - Choose how table borders are drawn");
     println!("  quit  - Save and exit");
     println!();
 
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            println!("Error: could not initialise line editor: {}", e);
+            return;
+        }
+    };
+    // A missing history file is expected on first run
+    let _ = editor.load_history(HISTORY_FILE);
+
+    // Statement text accumulated across lines until a terminating semicolon
+    let mut buffer = String::new();
+    let mut format = OutputFormat::Table;
+    let mut render_options = RenderOptions::default();
+    // Print each statement's row count and execution time
+    let mut timing = true;
+    // Pause long result tables every PAGE_LINES lines
+    let mut pager = false;
+    // Results go to this file (and its path) after `.output FILE`, instead of the terminal
+    let mut output: Option<(String, File)> = None;
+    let mut save_policy = SavePolicy::new(Cadence::Statements(CHECKPOINT_ENTRIES));
+
     loop {
-        print!("> ");
-        use std::io::Write;
-        std::io::stdout().flush().unwrap();
-        
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).unwrap();
-        let input = input.trim();
-
-        if input.eq_ignore_ascii_case("quit") {
-            // Auto-save on quit
-            if let Err(e) = db.save(DB_FILE) {
-                println!("Error saving database: {}", e);
-            } else {
-                println!("Database saved to {}", DB_FILE);
+        let prompt = if buffer.is_empty() { "> " } else { "...> " };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            // Ctrl-C abandons the current line (and any pending statement)
+            Err(ReadlineError::Interrupted) => {
+                buffer.clear();
+                continue;
+            }
+            // Ctrl-D flushes a pending statement, then behaves like quit
+            Err(ReadlineError::Eof) if !buffer.is_empty() => String::new(),
+            Err(ReadlineError::Eof) => String::from("quit"),
+            Err(e) => {
+                println!("Error reading input: {}", e);
+                String::from("quit")
+            }
+        };
+        let trimmed = line.trim();
+        if !trimmed.is_empty() {
+            let _ = editor.add_history_entry(trimmed);
+        }
+
+        // Special commands are only recognised at the start of a statement
+        if buffer.is_empty() {
+            let input = trimmed;
+
+            if input.eq_ignore_ascii_case("quit") {
+                if db.in_transaction() {
+                    println!("Discarding uncommitted transaction");
+                }
+                // Auto-save on quit
+                if let Some(db_file) = db_file {
+                    match db.save(db_file) {
+                        Ok(_) => println!("Database saved to {}", db_file),
+                        Err(e) => println!("Error saving database: {}", e),
+                    }
+                }
+                if let Err(e) = editor.save_history(HISTORY_FILE) {
+                    println!("Warning: Could not save history: {}", e);
+                }
+                break;
+            }
+
+            if input.eq_ignore_ascii_case("save") {
+                match db_file {
+                    // Saving would drop the open transaction's statements from the log
+                    Some(_) if db.in_transaction() => println!("Error: COMMIT or ROLLBACK before saving"),
+                    Some(db_file) => match checkpoint(&db, db_file, &mut log) {
+                        Ok(_) => {
+                            save_policy.saved();
+                            println!("Database saved to {}", db_file)
+                        }
+                        Err(e) => println!("Error saving: {}", e),
+                    },
+                    None if db.read_only => println!("The database is open read-only; nothing is saved"),
+                    None => println!("The database is in memory only; nothing is saved"),
+                }
+                continue;
+            }
+
+            if input.eq_ignore_ascii_case("debug") {
+                println!("=== Database Debug Info ===");
+                println!("Tables: {}", db.tables.len());
+                for table_name in db.table_names() {
+                    let table = &db.tables[table_name];
+                    println!("\nTable: {}", table_name);
+                    println!("  Columns: {:?}", table.columns);
+                    println!("  Rows: {}", table.rows.len());
+                    for index in &table.indexes {
+                        println!(
+                            "  Index {} on ({}): {} distinct keys",
+                            index.name,
+                            index.columns.join(", "),
+                            index.distinct_keys()
+                        );
+                    }
+                    for constraint in &table.constraints {
+                        match &constraint.kind {
+                            ConstraintKind::Unique(columns) => {
+                                println!("  Constraint {}: UNIQUE ({})", constraint.name, columns.join(", "))
+                            }
+                            ConstraintKind::Check(condition) => {
+                                println!("  Constraint {}: CHECK ({})", constraint.name, condition)
+                            }
+                        }
+                    }
+                    for (i, row) in table.rows.iter().enumerate() {
+                        println!("    Row {}: {:?}", i, row);
+                    }
+                }
+                println!("===========================");
+                continue;
+            }
+
+            if input.eq_ignore_ascii_case("verbose") {
+                db.verbose = !db.verbose;
+                println!("Verbose mode {}", if db.verbose { "on" } else { "off" });
+                continue;
+            }
+
+            if let Some(arg) = input.strip_prefix(".format") {
+                match arg.trim().to_lowercase().as_str() {
+                    "table" => format = OutputFormat::Table,
+                    "json" => format = OutputFormat::Json,
+                    other => {
+                        println!("Error: unknown format '{}' (expected table or json)", other);
+                        continue;
+                    }
+                }
+                println!("Output format: {}", arg.trim().to_lowercase());
+                continue;
+            }
+
+            if let Some(arg) = input.strip_prefix(".style") {
+                match arg.trim().parse() {
+                    Ok(style) => {
+                        render_options.border_style = style;
+                        println!("Table style: {}", arg.trim().to_lowercase());
+                    }
+                    Err(e) => println!("Error: {}", e),
+                }
+                continue;
+            }
+
+            if let Some(arg) = input.strip_prefix(".timing") {
+                match arg.trim().to_lowercase().as_str() {
+                    "on" => timing = true,
+                    "off" => timing = false,
+                    other => {
+                        println!("Error: unknown setting '{}' (expected on or off)", other);
+                        continue;
+                    }
+                }
+                println!("Timing {}", if timing { "on" } else { "off" });
+                continue;
+            }
+
+            if let Some(arg) = input.strip_prefix(".autosave") {
+                match arg.parse() {
+                    Ok(cadence) => {
+                        save_policy.cadence = cadence;
+                        println!("Autosave {}", cadence);
+                    }
+                    Err(e) => println!("Error: {}", e),
+                }
+                continue;
+            }
+
+            if let Some(arg) = input.strip_prefix(".pager") {
+                match arg.trim().to_lowercase().as_str() {
+                    "on" => pager = true,
+                    "off" => pager = false,
+                    other => {
+                        println!("Error: unknown setting '{}' (expected on or off)", other);
+                        continue;
+                    }
+                }
+                println!("Pager {}", if pager { "on" } else { "off" });
+                continue;
+            }
+
+            if let Some(arg) = input.strip_prefix(".ci") {
+                match arg.trim().to_lowercase().as_str() {
+                    "on" => db.options.ignore_case = true,
+                    "off" => db.options.ignore_case = false,
+                    other => {
+                        println!("Error: unknown setting '{}' (expected on or off)", other);
+                        continue;
+                    }
+                }
+                println!("Case-insensitive comparison {}", if db.options.ignore_case { "on" } else { "off" });
+                log_options(&db, &mut log);
+                continue;
+            }
+
+            if let Some(arg) = input.strip_prefix(".stats") {
+                let name = arg.trim().trim_end_matches(';');
+                if name.is_empty() {
+                    println!("Usage: .stats table_name");
+                    continue;
+                }
+                match db.column_stats(name) {
+                    Ok(stats) => {
+                        println!("Table '{}': {} rows", name, db.tables[name].rows.len());
+                        print_result(&ExecResult::Rows(stats), OutputFormat::Table, &render_options);
+                    }
+                    Err(e) => println!("Error: {}", e),
+                }
+                continue;
+            }
+
+            if let Some(arg) = input.strip_prefix(".output") {
+                let path = arg.trim();
+                if path.is_empty() {
+                    if let Some((path, _)) = output.take() {
+                        println!("Results go to the terminal again ({} was closed)", path);
+                    }
+                } else {
+                    // A file that can't be opened leaves the current destination in place
+                    match File::create(path) {
+                        Ok(file) => {
+                            println!("Results go to {}", path);
+                            output = Some((path.to_string(), file));
+                        }
+                        Err(e) => println!("Error: Could not open {}: {}", path, e),
+                    }
+                }
+                continue;
+            }
+
+            if let Some(arg) = input.strip_prefix(".nulls") {
+                match arg.trim().parse() {
+                    Ok(order) => {
+                        db.options.null_order = order;
+                        println!("ORDER BY puts NULLs: {}", arg.trim().to_lowercase());
+                        log_options(&db, &mut log);
+                    }
+                    Err(e) => println!("Error: {}", e),
+                }
+                continue;
+            }
+
+            if let Some(arg) = input.strip_prefix(".precision") {
+                let mut words = arg.split_whitespace();
+                let digits = words.next().map(str::parse::<usize>);
+                let scientific = match words.next() {
+                    None => false,
+                    Some(word) if word.eq_ignore_ascii_case("scientific") => true,
+                    Some(_) => {
+                        println!("Usage: .precision DIGITS [scientific]");
+                        continue;
+                    }
+                };
+                match digits {
+                    Some(Ok(digits)) if digits <= 17 => {
+                        render_options.float_precision = digits;
+                        render_options.scientific = scientific;
+                        println!(
+                            "FLOATs are shown with up to {} decimal place(s){}",
+                            digits,
+                            if scientific { " in scientific notation" } else { "" }
+                        );
+                    }
+                    _ => println!("Usage: .precision DIGITS [scientific] (DIGITS from 0 to 17)"),
+                }
+                continue;
+            }
+
+            if let Some(arg) = input.strip_prefix(".nullvalue") {
+                render_options.null_text = arg.trim().to_string();
+                println!("NULL is shown as '{}'", render_options.null_text);
+                continue;
+            }
+
+            if input.is_empty() {
+                continue;
             }
-            break;
         }
 
-        if input.eq_ignore_ascii_case("save") {
-            match db.save(DB_FILE) {
-                Ok(_) => println!("Database saved to {}", DB_FILE),
-                Err(e) => println!("Error saving: {}", e),
+        // .schema, .import and .export stand for a statement, which runs immediately
+        let shorthand = if buffer.is_empty() { expand_dot_command(trimmed) } else { None };
+        let input = if let Some(sql) = shorthand {
+            match sql {
+                Ok(sql) => sql,
+                Err(e) => {
+                    println!("Error: {}", e);
+                    continue;
+                }
+            }
+        } else {
+            // An empty continuation line (or a bare ';') flushes the buffer
+            let flush = !buffer.is_empty() && trimmed.is_empty();
+            if !trimmed.is_empty() {
+                if !buffer.is_empty() {
+                    buffer.push('\n');
+                }
+                buffer.push_str(&line);
+            }
+            if !flush && !statement_complete(&buffer) {
+                continue;
+            }
+            std::mem::take(&mut buffer)
+        };
+        let input = input.as_str();
+
+        let (tokens, offsets): (Vec<_>, Vec<_>) = match tokenize_with_offsets(input) {
+            Ok(spanned) => spanned.into_iter().unzip(),
+            Err(e) => {
+                println!("Error: {}", e);
+                print_caret(input, error_position(&e, &[], 0..0, input));
+                continue;
+            }
+        };
+        // Each statement with its source text, which is what the log records
+        let statements = statement_ranges(&tokens)
+            .into_iter()
+            .map(|range| {
+                let end = offsets.get(range.end).copied().unwrap_or(input.len());
+                parse(&tokens[range.clone()])
+                    .map(|statement| (&input[offsets[range.start]..end], statement))
+                    .map_err(|e| (error_position(&e, &offsets, range, input), e))
+            })
+            .collect::<Result<Vec<_>, _>>();
+        let statements = match statements {
+            Ok(statements) => statements,
+            Err((pos, e)) => {
+                println!("Error: {}", e);
+                print_caret(input, pos);
+                continue;
+            }
+        };
+
+        // Execute in order, stopping at the first failure
+        let mut executed = 0;
+        // IMPORT and MERGE FROM read a file that may have changed by the time the log is replayed,
+        // the rows COPY reads aren't logged at all, and VACUUM is there to rewrite the file
+        // compacted, so each of them is saved straight away (or at COMMIT, inside a transaction)
+        for (sql, statement) in statements {
+            let logged = !statement.is_read_only() && !matches!(statement, Statement::Copy(_));
+            save_policy.pending |= matches!(
+                statement,
+                Statement::Import(_) | Statement::Merge(_) | Statement::Copy(_) | Statement::Vacuum
+            );
+            let (outcome, elapsed) = match statement {
+                Statement::Copy(copy_stmt) => match read_copy_rows(&db, &copy_stmt, &mut editor) {
+                    Ok(text) => {
+                        let started = Instant::now();
+                        (db.copy_rows(&copy_stmt, &text), started.elapsed())
+                    }
+                    Err(e) => (Err(e), Duration::ZERO),
+                },
+                statement => {
+                    let started = Instant::now();
+                    (db.execute(statement), started.elapsed())
+                }
+            };
+            match outcome {
+                Ok(result) => {
+                    match (&result, &mut output) {
+                        (_, Some((path, file))) => {
+                            if let Err(e) = write_result(file, &result, format, &render_options) {
+                                println!("Error: Could not write to {} ({}); showing results here again", path, e);
+                                print_result(&result, format, &render_options);
+                                output = None;
+                            }
+                        }
+                        (ExecResult::Rows(rows), None)
+                            if pager && format == OutputFormat::Table && !rows.rows.is_empty() =>
+                        {
+                            print_paged(&render_table(rows, &render_options), &mut editor)
+                        }
+                        _ => print_result(&result, format, &render_options),
+                    }
+                    if timing {
+                        println!("OK {}", timing_summary(&result, elapsed));
+                    } else {
+                        println!("OK");
+                    }
+                    executed += 1;
+                    if logged
+                        && let Some(wal) = &mut log
+                        && let Err(e) = wal.append(sql.trim())
+                    {
+                        println!("Warning: Could not write to the log ({}); saving after each statement instead", e);
+                        log = None;
+                    }
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    break;
+                }
+            }
+        }
+
+        // Rewrite the database file when the save policy says so, except inside a transaction.
+        // A pending IMPORT, MERGE FROM, COPY or VACUUM is folded in at once, and without a usable
+        // log every statement is saved unless autosave is off.
+        let due = match &log {
+            Some(wal) => save_policy.due(wal.entries()),
+            None => save_policy.cadence != Cadence::Off,
+        };
+        if executed > 0
+            && due
+            && !db.in_transaction()
+            && let Some(db_file) = db_file
+        {
+            match checkpoint(&db, db_file, &mut log) {
+                Ok(_) => save_policy.saved(),
+                Err(e) => println!("Warning: Could not auto-save: {}", e),
             }
-            continue;
         }
+    }
+}
 
-        if input.eq_ignore_ascii_case("debug") {
-            println!("=== Database Debug Info ===");
-            println!("Tables: {}", db.tables.len());
-            for (table_name, table) in &db.tables {
-                println!("\nTable: {}", table_name);
-                println!("  Columns: {:?}", table.columns);
-                println!("  Rows: {}", table.rows.len());
-                for (i, row) in table.rows.iter().enumerate() {
-                    println!("    Row {}: {:?}", i, row);
+// How the REPL goes on after the database file failed to load
+enum Recovery {
+    InMemory,           // with an empty database that is never saved, so the file can't be overwritten
+    MovedAside(String), // with a new database, the unreadable file having been renamed to this path
+}
+
+// Asks whether and how to continue when the database file (or its log) exists but can't be read.
+// None, the default, means quit; nothing is done to the file unless the user asks for it.
+fn choose_recovery(path: &str) -> Option<Recovery> {
+    let mut editor = DefaultEditor::new().ok()?;
+    let moved = (0..)
+        .map(|n| if n == 0 { format!("{}.corrupt", path) } else { format!("{}.corrupt{}", path, n) })
+        .find(|candidate| !std::path::Path::new(candidate).exists())?;
+    println!("How do you want to continue?");
+    println!("  q - quit, leaving the file untouched (default)");
+    println!("  m - start with an empty in-memory database; nothing is saved, so the file is left as it is");
+    println!("  n - rename the file to {} and start a new database in its place", moved);
+    loop {
+        let answer = match editor.readline("[q/m/n] ") {
+            Ok(answer) => answer.trim().to_lowercase(),
+            Err(_) => return None,
+        };
+        match answer.as_str() {
+            "" | "q" => return None,
+            "m" => return Some(Recovery::InMemory),
+            "n" => {
+                // The log belongs with the snapshot it follows, so it moves too
+                let rename =
+                    |from: &str, to: &str| !std::path::Path::new(from).exists() || std::fs::rename(from, to).is_ok();
+                if !(rename(path, &moved) && rename(&wal::log_path(path), &wal::log_path(&moved))) {
+                    println!("Could not rename {}", path);
+                    return None;
                 }
+                return Some(Recovery::MovedAside(moved));
             }
-            println!("===========================");
-            continue;
+            _ => println!("Please answer q, m or n"),
         }
+    }
+}
+
+// Loads the database file and applies its write-ahead log, returning the database and the
+// number of logged statements replayed; None if neither exists yet (or with --no-persist)
+fn open_database(path: Option<&str>) -> Result<Option<(Database, usize)>, Box<dyn std::error::Error>> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let mut db = if std::path::Path::new(path).exists() {
+        Database::load(path)?
+    } else if std::path::Path::new(&wal::log_path(path)).exists() {
+        // Stopped before the first snapshot was written
+        Database::new()
+    } else {
+        return Ok(None);
+    };
+    let replayed = wal::replay(&mut db, path)?;
+    Ok(Some((db, replayed)))
+}
+
+// Writes the whole database to its file and starts a fresh log after it
+fn checkpoint(db: &Database, path: &str, log: &mut Option<WriteAheadLog>) -> Result<(), Box<dyn std::error::Error>> {
+    *log = None;
+    db.save(path)?;
+    let mut wal = WriteAheadLog::create(path)?;
+    // A replayed log starts with the default options
+    if db.options != QueryOptions::default() {
+        wal.append_options(&db.options)?;
+    }
+    *log = Some(wal);
+    Ok(())
+}
+
+// Records changed options in the log, so the statements after them replay as they ran
+fn log_options(db: &Database, log: &mut Option<WriteAheadLog>) {
+    if let Some(wal) = log
+        && let Err(e) = wal.append_options(&db.options)
+    {
+        println!("Warning: Could not write to the log ({}); saving after each statement instead", e);
+        *log = None;
+    }
+}
+
+fn print_result(result: &ExecResult, format: OutputFormat, options: &RenderOptions) {
+    // Like println!, a closed stdout isn't worth reporting
+    let _ = write_result(&mut std::io::stdout(), result, format, options);
+}
 
-        if input.is_empty() {
-            continue;
+// Writes a statement's result to `out`: the terminal, or the file named with `.output`
+fn write_result(
+    out: &mut dyn Write,
+    result: &ExecResult,
+    format: OutputFormat,
+    options: &RenderOptions,
+) -> std::io::Result<()> {
+    match result {
+        ExecResult::Created(name) => writeln!(out, "Table '{}' created", name),
+        ExecResult::RowsAffected(n) => writeln!(out, "{} row(s) affected", n),
+        ExecResult::Inserted { count: 1, first_id } => writeln!(out, "1 row inserted (id={})", first_id),
+        ExecResult::Inserted { count: 0, .. } => writeln!(out, "0 rows inserted"),
+        ExecResult::Inserted { count, first_id } => {
+            writeln!(out, "{} rows inserted (ids {}-{})", count, first_id, first_id + count - 1)
         }
+        ExecResult::Message(message) => writeln!(out, "{}", message),
+        ExecResult::Rows(query_result) if format == OutputFormat::Json => {
+            writeln!(out, "{}", to_json(query_result))
+        }
+        ExecResult::Rows(query_result) => {
+            if query_result.rows.is_empty() {
+                writeln!(out, "No rows found")
+            } else {
+                write!(out, "{}", render_table(query_result, options))
+            }
+        }
+    }
+}
 
-        let tokens = tokenize(input);
-        match parse(&tokens) {
-            Ok(statement) => {
-                db.execute(statement);
-                println!("OK");
-                
-                // Auto-save after each successful operation
-                if let Err(e) = db.save(DB_FILE) {
-                    println!("Warning: Could not auto-save: {}", e);
+// Prints `text` PAGE_LINES lines at a time, waiting at a `-- more --` prompt between pages.
+// Enter shows the next page; `q` (or Ctrl-C / Ctrl-D) skips the rest.
+fn print_paged(text: &str, editor: &mut DefaultEditor) {
+    let lines: Vec<&str> = text.lines().collect();
+    for (i, page) in lines.chunks(PAGE_LINES).enumerate() {
+        if i > 0 {
+            match editor.readline("-- more -- ") {
+                Ok(answer) if !answer.trim().eq_ignore_ascii_case("q") => {}
+                _ => {
+                    println!("({} more lines not shown)", lines.len() - i * PAGE_LINES);
+                    return;
                 }
             }
-            Err(e) => println!("Error: {}", e),
         }
+        for line in page {
+            println!("{}", line);
+        }
+    }
+}
+
+// Reads the rows for COPY ... FROM STDIN, one per line, up to a line holding just `\.` (or the
+// end of input). Ctrl-C abandons the COPY.
+fn read_copy_rows(db: &Database, copy_stmt: &CopyStatement, editor: &mut DefaultEditor) -> Result<String, RqlError> {
+    // Refused before any rows are typed in
+    if db.read_only {
+        return Err(RqlError::ReadOnly("COPY".to_string()));
+    }
+    // Nothing would tie the rows to the log, so a crash after COMMIT would lose them
+    if db.in_transaction() {
+        return Err(RqlError::execution("COPY cannot run inside a transaction"));
+    }
+    if !db.tables.contains_key(&copy_stmt.table_name) {
+        return Err(RqlError::TableNotFound(copy_stmt.table_name.clone()));
+    }
+    let separator = match copy_stmt.delimiter {
+        '\t' => "tabs".to_string(),
+        c => format!("'{}'", c),
+    };
+    println!("Enter rows with fields separated by {}; end with \\. on a line by itself", separator);
+    let mut text = String::new();
+    loop {
+        match editor.readline(">> ") {
+            Ok(line) if line.trim() == "\\." => break,
+            Ok(line) => {
+                text.push_str(&line);
+                text.push('\n');
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(ReadlineError::Interrupted) => return Err(RqlError::execution("COPY cancelled")),
+            Err(e) => return Err(RqlError::execution(format!("Error reading input: {}", e))),
+        }
+    }
+    Ok(text)
+}
+
+// The statement a dot-command stands for: `.schema t` is DESCRIBE t, and `.import t file` and
+// `.export t file` are IMPORT and EXPORT, in the format the file's extension names. None if the
+// line is no such command.
+fn expand_dot_command(line: &str) -> Option<Result<String, String>> {
+    if let Some(rest) = line.strip_prefix(".schema") {
+        return Some(Ok(format!("DESCRIBE {};", rest.trim())));
+    }
+    let (command, direction, rest) = if let Some(rest) = line.strip_prefix(".import") {
+        ("IMPORT", "FROM", rest)
+    } else if let Some(rest) = line.strip_prefix(".export") {
+        ("EXPORT", "TO", rest)
+    } else {
+        return None;
+    };
+    // The file name is the rest of the line, so it may contain spaces
+    let Some((table, file)) = rest.trim().split_once(char::is_whitespace) else {
+        return Some(Err(format!("Usage: .{} table_name file.csv|file.json", command.to_lowercase())));
+    };
+    let file = file.trim();
+    if DataFormat::for_path(file).is_none() {
+        return Some(Err(format!("Can't tell the format of '{}': use a .csv or .json file", file)));
+    }
+    let path = Expr::Literal(Value::Str(file.to_string()));
+    Some(Ok(format!("{} {} {} {};", command, quote_name(table), direction, path)))
+}
+
+// "(3 rows, 0.42 ms)", or just the time for statements without a row count
+fn timing_summary(result: &ExecResult, elapsed: Duration) -> String {
+    let ms = elapsed.as_secs_f64() * 1000.0;
+    match result.row_count() {
+        Some(1) => format!("(1 row, {:.2} ms)", ms),
+        Some(n) => format!("({} rows, {:.2} ms)", n, ms),
+        None => format!("({:.2} ms)", ms),
+    }
+}
+
+// Runs every statement of a SQL file, reporting each by line. Unless autocommit is set the file
+// runs as one transaction: the first failure rolls back everything it changed. False if any statement failed.
+fn run_script(db: &mut Database, path: &str, stop_on_error: bool, autocommit: bool) -> bool {
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            println!("Error: could not read {}: {}", path, e);
+            return false;
+        }
+    };
+    let spanned = match tokenize_with_offsets(&source) {
+        Ok(spanned) => spanned,
+        Err(e) => {
+            println!("Error: {}", e);
+            print_caret(&source, error_position(&e, &[], 0..0, &source));
+            return false;
+        }
+    };
+    let (tokens, offsets): (Vec<_>, Vec<_>) = spanned.into_iter().unzip();
+
+    if !autocommit && let Err(e) = db.execute(Statement::Begin) {
+        println!("Error: {}", e);
+        return false;
+    }
+    let mut failures = 0;
+    for range in statement_ranges(&tokens) {
+        let line = source[..offsets[range.start]].matches('\n').count() + 1;
+        let outcome = parse(&tokens[range.clone()]).and_then(|statement| {
+            if !autocommit && matches!(statement, Statement::Begin | Statement::Commit | Statement::Rollback) {
+                return Err(RqlError::execution(
+                    "The script already runs as one transaction; use --autocommit to manage transactions in it",
+                ));
+            }
+            db.execute(statement)
+        });
+        match outcome {
+            Ok(result) => {
+                println!("line {}: OK", line);
+                print_result(&result, OutputFormat::Table, &RenderOptions::default());
+            }
+            Err(e) => {
+                println!("line {}: Error: {}", line, e);
+                print_caret(&source, error_position(&e, &offsets, range, &source));
+                failures += 1;
+                if stop_on_error || !autocommit {
+                    break;
+                }
+            }
+        }
+    }
+    if !autocommit {
+        let end = if failures == 0 { Statement::Commit } else { Statement::Rollback };
+        if let Err(e) = db.execute(end) {
+            println!("Error: {}", e);
+            return false;
+        }
+        if failures > 0 {
+            println!("Rolled back every change made by {}", path);
+        }
+    }
+    failures == 0
+}
+
+// Runs the statement(s) given with -c, printing results; errors go to stderr.
+// Returns false if tokenizing, parsing or executing failed.
+fn run_command(db: &mut Database, sql: &str) -> bool {
+    let statements = match tokenize(sql).and_then(|tokens| parse_program(&tokens)) {
+        Ok(statements) => statements,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return false;
+        }
+    };
+    for statement in statements {
+        match db.execute(statement) {
+            Ok(result) => print_result(&result, OutputFormat::Table, &RenderOptions::default()),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return false;
+            }
+        }
+    }
+    true
+}
+
+// Byte offset in `source` a syntax error points at: where the tokenizer gave up, or the start of
+// the offending token of the statement made of tokens `range` (`offsets` holds where each token
+// starts). A statement that ended too soon points at its semicolon, or just past the input.
+fn error_position(e: &RqlError, offsets: &[usize], range: Range<usize>, source: &str) -> Option<usize> {
+    match e {
+        RqlError::SyntaxError { pos: Some(pos), .. }
+        | RqlError::UnterminatedString { pos, .. }
+        | RqlError::UnterminatedIdentifier { pos, .. }
+        | RqlError::UnterminatedComment { pos } => Some(*pos),
+        RqlError::SyntaxError { token: Some(token), .. } if range.start + token < range.end => {
+            Some(offsets[range.start + token])
+        }
+        RqlError::SyntaxError { token: Some(_), .. } => {
+            Some(offsets.get(range.end).copied().unwrap_or(source.trim_end().len()))
+        }
+        _ => None,
+    }
+}
+
+// Prints the line of `source` holding byte offset `pos` with a `^` under that position
+fn print_caret(source: &str, pos: Option<usize>) {
+    let Some(pos) = pos.filter(|pos| *pos <= source.len()) else {
+        return;
+    };
+    let start = source[..pos].rfind('\n').map_or(0, |i| i + 1);
+    let end = source[pos..].find('\n').map_or(source.len(), |i| pos + i);
+    // Keep tabs so the caret lines up however wide the terminal draws them
+    let indent: String = source[start..pos]
+        .chars()
+        .map(|c| if c == '\t' { "\t".to_string() } else { " ".repeat(c.width().unwrap_or(0)) })
+        .collect();
+    println!("  {}", &source[start..end]);
+    println!("  {}^", indent);
+}
+
+// A buffered statement is complete once it ends with a semicolon outside of a string literal,
+// quoted identifier or comment
+fn statement_complete(buffer: &str) -> bool {
+    match tokenize(buffer) {
+        Ok(tokens) => matches!(tokens.last(), Some(Token::Semicolon)),
+        // Keep reading while one of them is still open
+        Err(e) => !e.is_unterminated(),
     }
 }
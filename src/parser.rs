@@ -1,4 +1,7 @@
 use serde::{Serialize, Deserialize};
+use crate::error::RqlError;
+use std::cell::Cell;
+use std::cmp::Ordering;
 
 #[derive(Debug, Clone)]
 pub enum Token {
@@ -12,6 +15,7 @@ pub enum Token {
     Semicolon,
     String(String),
     Int(i32),
+    Float(f64),
     Identifier(String),
     Select,
     From,
@@ -21,6 +25,26 @@ pub enum Token {
     Update,
     Set,
     Where,
+    Show,
+    Tables,
+    Order,
+    By,
+    Asc,
+    Desc,
+    Group,
+    Having,
+    NotEquals,     // != or <>
+    Less,          // <
+    LessEquals,    // <=
+    Greater,       // >
+    GreaterEquals, // >=
+    Plus,
+    Minus,
+    Slash,
+    Distinct,
+    Placeholder, // ? in a prepared statement
+    Dot,         // . in a qualified column name, e.g. users.id
+    Concat,      // || string concatenation
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -29,51 +53,784 @@ pub enum Value {
     Str(String),
     Star,
     Identifier(String),
+    Null,
+    Date(String), // normalised YYYY-MM-DD, so string order is chronological
+    Float(f64),   // always finite; the tokenizer and arithmetic reject NaN and infinities
+}
+
+// Floats are never NaN, so equality is reflexive and Value can be used as a map key
+impl Eq for Value {}
+
+impl std::hash::Hash for Value {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Value::Int(i) => i.hash(state),
+            Value::Str(s) | Value::Identifier(s) | Value::Date(s) => s.hash(state),
+            // 0.0 and -0.0 are equal, so they must hash the same
+            Value::Float(f) => (if *f == 0.0 { 0.0f64 } else { *f }).to_bits().hash(state),
+            Value::Star | Value::Null => {}
+        }
+    }
+}
+
+impl Value {
+    // Orders two values under the engine's coercion rules: INT and FLOAT compare numerically,
+    // TEXT lexically and DATE chronologically; TEXT compared with a DATE is read as a date.
+    // NULL orders before everything else. Any other pairing, e.g. TEXT with INT, is an error.
+    pub fn compare(&self, other: &Value) -> Result<Ordering, RqlError> {
+        self.compare_with(other, false)
+    }
+
+    // Like `compare`, but with ignore_case TEXT values compare by their lowercase forms, so
+    // 'bob' = 'Bob'; numbers and dates are unaffected
+    pub fn compare_with(&self, other: &Value, ignore_case: bool) -> Result<Ordering, RqlError> {
+        match (self, other) {
+            (Value::Str(a), Value::Str(b)) if ignore_case => Ok(a.to_lowercase().cmp(&b.to_lowercase())),
+            (Value::Null, Value::Null) => Ok(Ordering::Equal),
+            (Value::Null, _) => Ok(Ordering::Less),
+            (_, Value::Null) => Ok(Ordering::Greater),
+            (Value::Int(a), Value::Int(b)) => Ok(a.cmp(b)),
+            (Value::Int(_) | Value::Float(_), Value::Int(_) | Value::Float(_)) => {
+                Ok(self.as_f64().total_cmp(&other.as_f64()))
+            }
+            (Value::Str(a), Value::Str(b)) | (Value::Date(a), Value::Date(b)) => Ok(a.cmp(b)),
+            (Value::Str(s), Value::Date(_)) => parse_date(s)?.compare(other),
+            (Value::Date(_), Value::Str(s)) => self.compare(&parse_date(s)?),
+            _ => Err(RqlError::TypeMismatch(format!(
+                "Cannot compare {} ({}) with {} ({})",
+                Expr::Literal(self.clone()),
+                self.type_name(),
+                Expr::Literal(other.clone()),
+                other.type_name()
+            ))),
+        }
+    }
+
+    // Where ORDER BY puts a value relative to values of another type, since those can't be
+    // compared: NULL first, then numbers, then TEXT, then DATE
+    pub fn sort_rank(&self) -> u8 {
+        match self {
+            Value::Null => 0,
+            Value::Int(_) | Value::Float(_) => 1,
+            Value::Str(_) => 2,
+            Value::Date(_) => 3,
+            Value::Star | Value::Identifier(_) => 4,
+        }
+    }
+
+    // SQL-style type name, for error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "INT",
+            Value::Float(_) => "FLOAT",
+            Value::Str(_) => "TEXT",
+            Value::Date(_) => "DATE",
+            Value::Null => "NULL",
+            Value::Star | Value::Identifier(_) => "identifier",
+        }
+    }
+
+    // Numeric value of an INT or FLOAT (NaN for anything else)
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            Value::Int(i) => f64::from(*i),
+            Value::Float(f) => *f,
+            _ => f64::NAN,
+        }
+    }
+
+    // Text form used by || and CONCAT: numbers in decimal, dates as YYYY-MM-DD (None for NULL)
+    pub fn as_text(&self) -> Option<String> {
+        match self {
+            Value::Int(i) => Some(i.to_string()),
+            Value::Float(f) => Some(format!("{:?}", f)),
+            Value::Str(s) | Value::Date(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+}
+
+// Validates a YYYY-MM-DD date string and builds a Value::Date from it
+pub fn parse_date(text: &str) -> Result<Value, RqlError> {
+    let invalid = || RqlError::TypeMismatch(format!("Invalid date '{}': expected a valid YYYY-MM-DD date", text));
+    let parts: Vec<&str> = text.split('-').collect();
+    if parts.len() != 3
+        || [4, 2, 2].iter().zip(&parts).any(|(len, p)| p.len() != *len || !p.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return Err(invalid());
+    }
+    let year: u32 = parts[0].parse().map_err(|_| invalid())?;
+    let month: u32 = parts[1].parse().map_err(|_| invalid())?;
+    let day: u32 = parts[2].parse().map_err(|_| invalid())?;
+    let leap = year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400));
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return Err(invalid()),
+    };
+    if day == 0 || day > days_in_month {
+        return Err(invalid());
+    }
+    Ok(Value::Date(text.to_string()))
 }
 
 #[derive(Debug, Clone)]
 pub struct InsertStatement {
     pub table_name: String,
-    pub values: Vec<Value>,
+    pub source: InsertSource,
 }
+
+// Where an INSERT's rows come from
 #[derive(Debug, Clone)]
+pub enum InsertSource {
+    Values(Vec<Vec<Value>>),      // VALUES (...), (...)
+    Select(Box<SelectStatement>), // INSERT INTO archive SELECT * FROM logs WHERE ...
+}
+#[derive(Debug, Clone, PartialEq)]
 pub struct SelectStatement {
     pub table_name: String,
-    pub values: Vec<Value>,
+    pub table_alias: Option<String>, // FROM employees e
+    pub joins: Vec<Join>, // further tables combined with the first, in order
+    pub items: Vec<SelectItem>,
+    pub condition: Option<Condition>,
+    pub group_by: Vec<String>,
+    pub having: Option<Condition>,
+    pub order_by: Vec<OrderKey>,
+    pub limit: Option<usize>, // LIMIT n: at most n rows, after ORDER BY
+    pub offset: usize,        // OFFSET m: rows skipped first (after ORDER BY, before LIMIT counts)
+}
+
+impl SelectStatement {
+    // Whether an item uses an aggregate; without GROUP BY all matching rows then form one group
+    pub fn is_aggregated(&self) -> bool {
+        self.items.iter().any(|item| match item {
+            SelectItem::Expr(expr, _) => expr.contains_aggregate(),
+            SelectItem::Star | SelectItem::StarExcept(_) | SelectItem::TableStar(_) => false,
+        })
+    }
+}
+// One ORDER BY key: `column [ASC|DESC] [NULLS FIRST|NULLS LAST]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderKey {
+    pub column: String,
+    pub descending: bool,
+    pub nulls_first: Option<bool>, // None: NULL sorts as the smallest value, so first unless DESC
+}
+
+impl OrderKey {
+    // The `NULLS ...` modifier as written, or "" if there was none
+    pub fn nulls_clause(&self) -> &'static str {
+        match self.nulls_first {
+            Some(true) => " NULLS FIRST",
+            Some(false) => " NULLS LAST",
+            None => "",
+        }
+    }
+}
+// `CROSS JOIN table_name [alias]` or `[INNER] JOIN table_name [alias] ON condition`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Join {
+    pub table_name: String,
+    pub alias: Option<String>,
+    pub kind: JoinKind,
+}
+#[derive(Debug, Clone, PartialEq)]
+pub enum JoinKind {
+    Cross,            // every row of the tables so far paired with every row of this one
+    Inner(Condition), // only the pairs for which the ON condition holds
+}
+// One entry of the SELECT list
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectItem {
+    Star,
+    StarExcept(Vec<String>), // * EXCEPT (col, ...): every column but the listed ones
+    TableStar(String),       // users.*: every column of one table (or alias) in a join
+    Expr(Expr, Option<String>), // expression [AS alias]
+}
+
+impl std::fmt::Display for SelectItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SelectItem::Star => write!(f, "*"),
+            SelectItem::StarExcept(columns) => write!(f, "* EXCEPT ({})", columns.join(", ")),
+            SelectItem::TableStar(table) => write!(f, "{}.*", table),
+            SelectItem::Expr(expr, None) => write!(f, "{}", expr),
+            SelectItem::Expr(expr, Some(alias)) => write!(f, "{} AS {}", expr, alias),
+        }
+    }
+}
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Column(String),
+    Literal(Value),
+    // arg is None for COUNT(*); distinct is set for e.g. COUNT(DISTINCT city);
+    // separator is GROUP_CONCAT's optional second argument (a comma when None)
+    Aggregate { func: AggregateFunc, arg: Option<Box<Expr>>, distinct: bool, separator: Option<String> },
+    Binary { op: BinaryOp, left: Box<Expr>, right: Box<Expr> },
+    // CASE WHEN cond THEN result ... [ELSE result] END; no matching branch and no ELSE gives NULL
+    Case { branches: Vec<(Condition, Expr)>, otherwise: Option<Box<Expr>> },
+    // A scalar function call evaluated per row, e.g. CONCAT(first, ' ', last)
+    Function { func: ScalarFunc, args: Vec<Expr> },
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Concat, // `a || b`: both operands as text, NULL if either is NULL
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScalarFunc {
+    Concat,   // joins its arguments as text, skipping NULLs
+    Coalesce, // the first argument that isn't NULL; the ones after it are not evaluated
+    NullIf,   // NULLIF(a, b): NULL if a equals b, else a
+    Length,   // characters in a TEXT value
+    Upper,
+    Lower,
+    Trim,   // removes leading and trailing whitespace
+    Substr, // SUBSTR(text, start [, length]), counting characters from 1
+    Abs,
+    Round, // ROUND(number [, digits]): a FLOAT rounded to that many decimals (0 by default)
+    Mod,   // MOD(a, b): the remainder of INT division, with the sign of a
+}
+
+impl ScalarFunc {
+    // The fewest and most arguments the function takes (None: no limit)
+    pub fn arity(self) -> (usize, Option<usize>) {
+        match self {
+            ScalarFunc::Concat | ScalarFunc::Coalesce => (1, None),
+            ScalarFunc::NullIf => (2, Some(2)),
+            ScalarFunc::Length | ScalarFunc::Upper | ScalarFunc::Lower | ScalarFunc::Trim => (1, Some(1)),
+            ScalarFunc::Substr => (2, Some(3)),
+            ScalarFunc::Abs => (1, Some(1)),
+            ScalarFunc::Round => (1, Some(2)),
+            ScalarFunc::Mod => (2, Some(2)),
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggregateFunc {
+    Count,
+    Sum,
+    Min,
+    Max,
+    GroupConcat,
+    Avg,
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+}
+
+impl CompareOp {
+    // The operator with its operands swapped: `5 < a` is `a > 5`
+    pub fn flipped(self) -> CompareOp {
+        match self {
+            CompareOp::Lt => CompareOp::Gt,
+            CompareOp::LtEq => CompareOp::GtEq,
+            CompareOp::Gt => CompareOp::Lt,
+            CompareOp::GtEq => CompareOp::LtEq,
+            op => op,
+        }
+    }
+}
+// WHERE/HAVING predicate, e.g. `age > 30`, `COUNT(*) > 1` or `id IN (SELECT ...)`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    // Either side may be a literal, a column or arithmetic over columns, e.g. `price > cost`
+    Comparison { left: Expr, op: CompareOp, right: Expr },
+    // `left IN (v1, v2, ...)`; also what a subquery becomes once it has run.
+    // `negated` for NOT IN, which is false if left is NULL or the list holds a NULL
+    InList { left: Expr, values: Vec<Value>, negated: bool },
+    // `left [NOT] IN (SELECT ...)`; the subquery must produce exactly one column
+    InSelect { left: Expr, subquery: Box<SelectStatement>, negated: bool },
+    // `left LIKE 'pattern' [ESCAPE 'c']`: % matches any run of characters, _ exactly one;
+    // the escape character makes the %, _ or escape character after it literal.
+    // ILIKE is the same with ignore_case set: letters match regardless of case.
+    // `negated` for NOT LIKE / NOT ILIKE; a NULL left side matches neither form
+    Like { left: Expr, pattern: String, escape: Option<char>, ignore_case: bool, negated: bool },
+    // `left IS [NOT] DISTINCT FROM right`: like <> (or = when negated), except that two NULLs are
+    // not distinct and NULL is distinct from any other value, so it is never unknown
+    Distinct { left: Expr, right: Expr, negated: bool },
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+impl BinaryOp {
+    // Binding strength used by the expression parser: * and / bind tighter than + and -,
+    // which bind tighter than ||, so `'n' || 1 + 2` is `'n' || (1 + 2)`
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Concat => 1,
+            BinaryOp::Add | BinaryOp::Sub => 2,
+            BinaryOp::Mul | BinaryOp::Div => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let symbol = match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Concat => "||",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+impl Expr {
+    // True if an aggregate call appears anywhere in the expression
+    pub fn contains_aggregate(&self) -> bool {
+        match self {
+            Expr::Aggregate { .. } => true,
+            Expr::Binary { left, right, .. } => left.contains_aggregate() || right.contains_aggregate(),
+            Expr::Case { branches, otherwise } => {
+                branches.iter().any(|(cond, result)| {
+                    cond.operands().iter().any(|expr| expr.contains_aggregate()) || result.contains_aggregate()
+                }) || otherwise.as_ref().is_some_and(|expr| expr.contains_aggregate())
+            }
+            Expr::Function { args, .. } => args.iter().any(|arg| arg.contains_aggregate()),
+            _ => false,
+        }
+    }
+}
+
+impl Condition {
+    // The expressions a condition tests, through AND and OR (subqueries excluded)
+    pub fn operands(&self) -> Vec<&Expr> {
+        match self {
+            Condition::Comparison { left, right, .. } | Condition::Distinct { left, right, .. } => {
+                vec![left, right]
+            }
+            Condition::InList { left, .. } | Condition::InSelect { left, .. } | Condition::Like { left, .. } => {
+                vec![left]
+            }
+            Condition::And(left, right) | Condition::Or(left, right) => {
+                let mut operands = left.operands();
+                operands.extend(right.operands());
+                operands
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for AggregateFunc {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            AggregateFunc::Count => "COUNT",
+            AggregateFunc::Sum => "SUM",
+            AggregateFunc::Min => "MIN",
+            AggregateFunc::Max => "MAX",
+            AggregateFunc::GroupConcat => "GROUP_CONCAT",
+            AggregateFunc::Avg => "AVG",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::fmt::Display for ScalarFunc {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            ScalarFunc::Concat => "CONCAT",
+            ScalarFunc::Coalesce => "COALESCE",
+            ScalarFunc::NullIf => "NULLIF",
+            ScalarFunc::Length => "LENGTH",
+            ScalarFunc::Upper => "UPPER",
+            ScalarFunc::Lower => "LOWER",
+            ScalarFunc::Trim => "TRIM",
+            ScalarFunc::Substr => "SUBSTR",
+            ScalarFunc::Abs => "ABS",
+            ScalarFunc::Round => "ROUND",
+            ScalarFunc::Mod => "MOD",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let symbol = match self {
+            CompareOp::Eq => "=",
+            CompareOp::NotEq => "<>",
+            CompareOp::Lt => "<",
+            CompareOp::LtEq => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::GtEq => ">=",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+impl std::fmt::Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Condition::Comparison { left, op, right } => write!(f, "{} {} {}", left, op, right),
+            Condition::InList { left, values, negated } => {
+                let values: Vec<String> = values.iter().map(|v| Expr::Literal(v.clone()).to_string()).collect();
+                write!(f, "{} {}IN ({})", left, if *negated { "NOT " } else { "" }, values.join(", "))
+            }
+            Condition::InSelect { left, subquery, negated } => {
+                write!(f, "{} {}IN ({})", left, if *negated { "NOT " } else { "" }, subquery)
+            }
+            Condition::Like { left, pattern, escape, ignore_case, negated } => {
+                let keyword = if *ignore_case { "ILIKE" } else { "LIKE" };
+                let not = if *negated { "NOT " } else { "" };
+                write!(f, "{} {}{} '{}'", left, not, keyword, escape_string(pattern))?;
+                match escape {
+                    Some(c) => write!(f, " ESCAPE '{}'", escape_string(&c.to_string())),
+                    None => Ok(()),
+                }
+            }
+            Condition::Distinct { left, right, negated } => {
+                write!(f, "{} IS {}DISTINCT FROM {}", left, if *negated { "NOT " } else { "" }, right)
+            }
+            // AND binds tighter than OR, so only an OR inside an AND needs parentheses
+            Condition::And(left, right) => {
+                for (i, side) in [left, right].into_iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " AND ")?;
+                    }
+                    match side.as_ref() {
+                        Condition::Or(..) => write!(f, "({})", side)?,
+                        _ => write!(f, "{}", side)?,
+                    }
+                }
+                Ok(())
+            }
+            Condition::Or(left, right) => write!(f, "{} OR {}", left, right),
+        }
+    }
+}
+
+// Renders a SELECT back to SQL, e.g. for subqueries shown by EXPLAIN
+impl std::fmt::Display for SelectStatement {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let items: Vec<String> = self.items.iter().map(|item| item.to_string()).collect();
+        write!(f, "SELECT {} FROM {}", items.join(", "), self.table_name)?;
+        if let Some(alias) = &self.table_alias {
+            write!(f, " {}", alias)?;
+        }
+        for join in &self.joins {
+            let keyword = if join.kind == JoinKind::Cross { "CROSS JOIN" } else { "JOIN" };
+            write!(f, " {} {}", keyword, join.table_name)?;
+            if let Some(alias) = &join.alias {
+                write!(f, " {}", alias)?;
+            }
+            if let JoinKind::Inner(cond) = &join.kind {
+                write!(f, " ON {}", cond)?;
+            }
+        }
+        if let Some(cond) = &self.condition {
+            write!(f, " WHERE {}", cond)?;
+        }
+        if !self.group_by.is_empty() {
+            write!(f, " GROUP BY {}", self.group_by.join(", "))?;
+        }
+        if let Some(cond) = &self.having {
+            write!(f, " HAVING {}", cond)?;
+        }
+        if !self.order_by.is_empty() {
+            let keys: Vec<String> = self
+                .order_by
+                .iter()
+                .map(|key| format!("{}{}{}", key.column, if key.descending { " DESC" } else { "" }, key.nulls_clause()))
+                .collect();
+            write!(f, " ORDER BY {}", keys.join(", "))?;
+        }
+        if let Some(limit) = self.limit {
+            write!(f, " LIMIT {}", limit)?;
+        }
+        if self.offset > 0 {
+            write!(f, " OFFSET {}", self.offset)?;
+        }
+        Ok(())
+    }
+}
+
+// Writes a string's contents so that the tokenizer reads them back unchanged
+fn escape_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '\'' => out.push_str("''"),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+// Writes a table or column name so that it reads back as the same name: as it is if it is a plain
+// name, otherwise in double quotes
+pub fn quote_name(name: &str) -> String {
+    match tokenize(name).as_deref() {
+        Ok([Token::Identifier(word)]) if word == name => name.to_string(),
+        _ => format!("\"{}\"", name.replace('"', "\"\"")),
+    }
+}
+
+// Renders an expression back to SQL; used for result column headers
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Expr::Column(name) => {
+                let parts: Vec<String> = name.split('.').map(quote_name).collect();
+                write!(f, "{}", parts.join("."))
+            }
+            Expr::Literal(Value::Int(i)) => write!(f, "{}", i),
+            Expr::Literal(Value::Float(x)) => write!(f, "{:?}", x),
+            Expr::Literal(Value::Str(s)) => write!(f, "'{}'", escape_string(s)),
+            Expr::Literal(Value::Date(d)) => write!(f, "DATE '{}'", d),
+            Expr::Literal(_) => write!(f, "NULL"),
+            Expr::Aggregate { func, arg: None, .. } => write!(f, "{}(*)", func),
+            Expr::Aggregate { func, arg: Some(arg), distinct, separator } => {
+                write!(f, "{}({}{}", func, if *distinct { "DISTINCT " } else { "" }, arg)?;
+                if let Some(separator) = separator {
+                    write!(f, ", '{}'", escape_string(separator))?;
+                }
+                write!(f, ")")
+            }
+            Expr::Binary { op, left, right } => {
+                // Parenthesise operands only where precedence requires it
+                match left.as_ref() {
+                    Expr::Binary { op: inner, .. } if inner.precedence() < op.precedence() => {
+                        write!(f, "({})", left)?
+                    }
+                    _ => write!(f, "{}", left)?,
+                }
+                write!(f, " {} ", op)?;
+                match right.as_ref() {
+                    Expr::Binary { op: inner, .. } if inner.precedence() <= op.precedence() => {
+                        write!(f, "({})", right)
+                    }
+                    _ => write!(f, "{}", right),
+                }
+            }
+            Expr::Case { branches, otherwise } => {
+                write!(f, "CASE")?;
+                for (cond, result) in branches {
+                    write!(f, " WHEN {} THEN {}", cond, result)?;
+                }
+                if let Some(otherwise) = otherwise {
+                    write!(f, " ELSE {}", otherwise)?;
+                }
+                write!(f, " END")
+            }
+            Expr::Function { func, args } => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "{}({})", func, args.join(", "))
+            }
+        }
+    }
 }
 #[derive(Debug, Clone)]
 pub struct CreateTableStatement {
     pub table_name: String,
-    pub columns: Vec<String>,
+    pub columns: Vec<ColumnDef>,
+}
+// One column of CREATE TABLE: name [type] [REFERENCES parent(column) [ON DELETE action]]
+#[derive(Debug, Clone)]
+pub struct ColumnDef {
+    pub name: String,
+    pub data_type: Option<DataType>,
+    pub references: Option<(String, String)>, // (parent table, parent column)
+    pub on_delete: OnDelete,
+}
+// What deleting a referenced parent row does to the rows referencing it
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum OnDelete {
+    #[default]
+    Restrict, // refuse the delete
+    Cascade,  // delete the referencing rows too
+}
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DataType {
+    Int,
+    Text,
+    Date,
+    Float,
+}
+
+impl std::fmt::Display for DataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DataType::Int => write!(f, "INT"),
+            DataType::Text => write!(f, "TEXT"),
+            DataType::Date => write!(f, "DATE"),
+            DataType::Float => write!(f, "FLOAT"),
+        }
+    }
 }
 #[derive(Debug, Clone)]
 pub struct DeleteStatement {
     pub table_name: String,
-    pub condition: String,
+    pub condition: Option<Condition>, // None when there is no WHERE clause
+    pub limit: Option<usize>,         // LIMIT n: delete at most the first n matches, in row order
 }
 #[derive(Debug, Clone)]
 pub struct UpdateStatement {
     pub table_name: String,
-    pub set_clause: String,  // e.g., "col0 = 123"
-    pub condition: String,    // e.g., "col1 = 'Alice'"
+    pub set_clause: Vec<(String, Expr)>,  // e.g., [("col0", 123), ("stock", stock - 1)]
+    pub condition: Option<Condition>, // None when there is no WHERE clause
+    pub limit: Option<usize>,         // LIMIT n: update at most the first n matches, in row order
+}
+#[derive(Debug, Clone)]
+pub struct DescribeStatement {
+    pub table_name: String,
+}
+#[derive(Debug, Clone)]
+pub struct TruncateStatement {
+    pub table_name: String,
+}
+#[derive(Debug, Clone)]
+pub struct CreateIndexStatement {
+    pub index_name: String,
+    pub table_name: String,
+    pub columns: Vec<String>, // one for a plain index, several for a composite one
+    pub sorted: bool,         // USING BTREE: ordered, so it can also answer range conditions
+}
+// ALTER TABLE table_name ADD CONSTRAINT [name] UNIQUE (col, ...) | CHECK (condition)
+#[derive(Debug, Clone)]
+pub struct AlterTableStatement {
+    pub table_name: String,
+    pub constraint_name: Option<String>, // a name is made up when none is given
+    pub constraint: TableConstraint,
+}
+#[derive(Debug, Clone)]
+pub enum TableConstraint {
+    Unique(Vec<String>), // no two rows may share the same non-NULL values in these columns
+    Check(Condition),    // every row must satisfy the condition
+}
+// SELECT ... UNION [ALL] SELECT ... [UNION [ALL] SELECT ...]
+#[derive(Debug, Clone)]
+pub struct UnionStatement {
+    pub first: SelectStatement,
+    pub rest: Vec<(bool, SelectStatement)>, // (ALL, select): ALL keeps duplicate rows
+}
+#[derive(Debug, Clone)]
+pub struct ImportStatement {
+    pub table_name: String,
+    pub path: String,
+}
+// EXPORT table_name TO 'file.csv'
+#[derive(Debug, Clone)]
+pub struct ExportStatement {
+    pub table_name: String,
+    pub path: String,
+}
+// COPY table_name FROM STDIN [DELIMITER 'c']: the REPL reads the rows that follow
+#[derive(Debug, Clone)]
+pub struct CopyStatement {
+    pub table_name: String,
+    pub delimiter: char, // a tab unless DELIMITER gives another character
+}
+// DUMP [table_name]: the SQL that recreates one table, or every table when none is named
+#[derive(Debug, Clone)]
+pub struct DumpStatement {
+    pub table_name: Option<String>,
+}
+// DIFF 'old.bin' 'new.bin': how the second database file's schema differs from the first's
+#[derive(Debug, Clone)]
+pub struct DiffStatement {
+    pub old_path: String,
+    pub new_path: String,
+}
+// MERGE FROM 'other.bin' [ON CONFLICT ERROR | SKIP | OVERWRITE]: copies another database file's
+// tables and rows into this one (see `Database::merge`)
+#[derive(Debug, Clone)]
+pub struct MergeStatement {
+    pub path: String,
+    pub on_conflict: OnConflict,
+}
+// What MERGE does with an incoming row whose UNIQUE key is already held by a row of the table
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OnConflict {
+    #[default]
+    Error,     // refuse the whole merge
+    Skip,      // keep the row already there and drop the incoming one
+    Overwrite, // replace the row already there with the incoming one
 }
 #[derive(Debug, Clone)]
 pub enum Statement {
     Insert(InsertStatement),
     Select(SelectStatement),
+    Union(UnionStatement),
     Create(CreateTableStatement),
     Delete(DeleteStatement),
     Update(UpdateStatement),
+    Describe(DescribeStatement),
+    ShowTables,
+    Explain(SelectStatement),
+    // EXPLAIN ANALYZE SELECT: runs the query and reports how long each phase took
+    ExplainAnalyze(SelectStatement),
+    // EXPLAIN DELETE / EXPLAIN UPDATE: the rows the statement would change, without changing them
+    DryRun(Box<Statement>),
+    Import(ImportStatement),
+    CreateIndex(CreateIndexStatement),
+    Truncate(TruncateStatement),
+    Vacuum,
+    Begin,
+    Commit,
+    Rollback,
+    AlterTable(AlterTableStatement),
+    Copy(CopyStatement),
+    Dump(DumpStatement),
+    Export(ExportStatement),
+    Diff(DiffStatement),
+    Merge(MergeStatement),
+    Checksum,
 }
 
 // --- Tokenizer ---
-pub fn tokenize(input: &str) -> Vec<Token> {
-    let mut chars = input.chars().peekable();
+// Longest names and string literals the tokenizer accepts, in bytes, so that untrusted input
+// can't make it build arbitrarily large tokens
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub max_identifier_len: usize, // table, column and index names, quoted or not
+    pub max_string_len: usize,     // the text of a '...' literal, after escapes
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits { max_identifier_len: 1024, max_string_len: 64 * 1024 }
+    }
+}
+
+pub fn tokenize(input: &str) -> Result<Vec<Token>, RqlError> {
+    tokenize_with_limits(input, &Limits::default())
+}
+
+pub fn tokenize_with_limits(input: &str, limits: &Limits) -> Result<Vec<Token>, RqlError> {
+    Ok(spanned_tokens(input, limits)?.into_iter().map(|(tok, _)| tok).collect())
+}
+
+// Tokenizes input, pairing each token with the byte offset where it starts
+pub fn tokenize_with_offsets(input: &str) -> Result<Vec<(Token, usize)>, RqlError> {
+    spanned_tokens(input, &Limits::default())
+}
+
+fn spanned_tokens(input: &str, limits: &Limits) -> Result<Vec<(Token, usize)>, RqlError> {
+    let mut chars = input.char_indices().peekable();
     let mut tokens = vec![];
+    let mut offsets = vec![];
 
-    while let Some(&c) = chars.peek() {
+    while let Some(&(start, c)) = chars.peek() {
         match c {
-            ' ' | '\t' | '\n' => {
+            ' ' | '\t' | '\r' | '\n' => {
                 chars.next(); // skip whitespace
             }
             '(' => {
@@ -88,6 +845,10 @@ pub fn tokenize(input: &str) -> Vec<Token> {
                 tokens.push(Token::Comma);
                 chars.next();
             }
+            '.' => {
+                tokens.push(Token::Dot);
+                chars.next();
+            }
             ';' => {
                 tokens.push(Token::Semicolon);
                 chars.next();
@@ -96,39 +857,225 @@ pub fn tokenize(input: &str) -> Vec<Token> {
         tokens.push(Token::Equals);
         chars.next();
     }
+            '<' => {
+                chars.next();
+                match chars.peek() {
+                    Some((_, '=')) => {
+                        chars.next();
+                        tokens.push(Token::LessEquals);
+                    }
+                    Some((_, '>')) => {
+                        chars.next();
+                        tokens.push(Token::NotEquals);
+                    }
+                    _ => tokens.push(Token::Less),
+                }
+            }
+            '>' => {
+                chars.next();
+                if let Some((_, '=')) = chars.peek() {
+                    chars.next();
+                    tokens.push(Token::GreaterEquals);
+                } else {
+                    tokens.push(Token::Greater);
+                }
+            }
+            '!' => {
+                chars.next();
+                match chars.next() {
+                    Some((_, '=')) => tokens.push(Token::NotEquals),
+                    _ => {
+                        return Err(RqlError::SyntaxError {
+                            msg: "Expected '=' after '!'".into(),
+                            pos: Some(start),
+                            token: None,
+                        });
+                    }
+                }
+            }
              '*' => {
             tokens.push(Token::Star);
             chars.next();
         }
+            '+' => {
+                tokens.push(Token::Plus);
+                chars.next();
+            }
+            '-' => {
+                tokens.push(Token::Minus);
+                chars.next();
+            }
+            '/' => {
+                chars.next();
+                if let Some((_, '*')) = chars.peek() {
+                    // /* block comment */, skipped like whitespace
+                    chars.next();
+                    let mut previous = None;
+                    loop {
+                        match chars.next() {
+                            Some((_, '/')) if previous == Some('*') => break,
+                            Some((_, ch)) => previous = Some(ch),
+                            None => return Err(RqlError::UnterminatedComment { pos: start }),
+                        }
+                    }
+                } else {
+                    tokens.push(Token::Slash);
+                }
+            }
+            '|' => {
+                chars.next();
+                match chars.next() {
+                    Some((_, '|')) => tokens.push(Token::Concat),
+                    _ => {
+                        return Err(RqlError::SyntaxError {
+                            msg: "Expected '|' after '|' (write || to concatenate)".into(),
+                            pos: Some(start),
+                            token: None,
+                        })
+                    }
+                }
+            }
+            '?' => {
+                tokens.push(Token::Placeholder);
+                chars.next();
+            }
             '\'' => {
                 chars.next(); // skip opening '
                 let mut s = String::new();
-                while let Some(&ch) = chars.peek() {
-                    if ch == '\'' {
-                        chars.next(); // skip closing '
-                        break;
+                let mut terminated = false;
+                while let Some((i, ch)) = chars.next() {
+                    if s.len() > limits.max_string_len {
+                        return Err(too_long("String literal", limits.max_string_len, start));
                     }
-                    s.push(ch);
-                    chars.next();
+                    match ch {
+                        '\'' => {
+                            // '' inside a literal is an escaped quote
+                            if let Some((_, '\'')) = chars.peek() {
+                                s.push('\'');
+                                chars.next();
+                            } else {
+                                terminated = true;
+                                break;
+                            }
+                        }
+                        // Backslash escapes: \n, \t, \\ and \' (an alternative to '')
+                        '\\' => match chars.next() {
+                            Some((_, 'n')) => s.push('\n'),
+                            Some((_, 't')) => s.push('\t'),
+                            Some((_, '\\')) => s.push('\\'),
+                            Some((_, '\'')) => s.push('\''),
+                            Some((_, other)) => {
+                                return Err(RqlError::SyntaxError {
+                                    msg: format!("Unknown escape sequence '\\{}' in string literal (write \\\\ for a backslash)", other),
+                                    pos: Some(i),
+                                    token: None,
+                                });
+                            }
+                            None => break,
+                        },
+                        _ => s.push(ch),
+                    }
+                }
+                if !terminated {
+                    return Err(RqlError::UnterminatedString { text: format!("'{}", s), pos: start });
                 }
                 tokens.push(Token::String(s));
             }
+            '"' | '`' => {
+                // "quoted" or `quoted` identifier: any name, even with spaces or a keyword
+                let quote = c;
+                chars.next(); // skip opening quote
+                let mut name = String::new();
+                let mut terminated = false;
+                while let Some((_, ch)) = chars.next() {
+                    if name.len() > limits.max_identifier_len {
+                        return Err(too_long("Identifier", limits.max_identifier_len, start));
+                    }
+                    if ch == quote {
+                        // a doubled quote stands for one quote character
+                        if let Some(&(_, next)) = chars.peek()
+                            && next == quote
+                        {
+                            name.push(quote);
+                            chars.next();
+                        } else {
+                            terminated = true;
+                            break;
+                        }
+                    } else {
+                        name.push(ch);
+                    }
+                }
+                if !terminated {
+                    return Err(RqlError::UnterminatedIdentifier { text: format!("{}{}", quote, name), pos: start });
+                }
+                if name.is_empty() {
+                    return Err(RqlError::SyntaxError {
+                        msg: "Empty quoted identifier".into(),
+                        pos: Some(start),
+                        token: None,
+                    });
+                }
+                tokens.push(Token::Identifier(name));
+            }
             c if c.is_ascii_digit() => {
                 let mut num = String::new();
-                while let Some(&ch) = chars.peek() {
-                    if ch.is_ascii_digit() {
-                        num.push(ch);
+                read_digits(&mut chars, &mut num);
+                // Optional fractional part; the '.' must be followed by at least one digit
+                let mut is_float = false;
+                if let Some(&(_, '.')) = chars.peek() {
+                    chars.next();
+                    num.push('.');
+                    is_float = true;
+                    if !read_digits(&mut chars, &mut num) {
+                        return Err(malformed_number(&num, start));
+                    }
+                }
+                // Optional exponent: e or E, an optional sign, then digits
+                if let Some(&(_, e @ ('e' | 'E'))) = chars.peek() {
+                    chars.next();
+                    num.push(e);
+                    is_float = true;
+                    if let Some(&(_, sign @ ('+' | '-'))) = chars.peek() {
                         chars.next();
-                    } else {
-                        break;
+                        num.push(sign);
+                    }
+                    if !read_digits(&mut chars, &mut num) {
+                        return Err(malformed_number(&num, start));
                     }
                 }
-                let parsed = num.parse::<i32>().expect("Invalid integer");
-                tokens.push(Token::Int(parsed));
+                if is_float {
+                    let parsed = num.parse::<f64>().ok().filter(|f| f.is_finite()).ok_or_else(|| {
+                        RqlError::SyntaxError {
+                            msg: format!("Float literal out of range: {}", num),
+                            pos: Some(start),
+                            token: None,
+                        }
+                    })?;
+                    tokens.push(Token::Float(parsed));
+                } else {
+                    let parsed = match num.parse::<i32>() {
+                        Ok(parsed) => parsed,
+                        // -2147483648 is in range although 2147483648 isn't, so a unary minus
+                        // before it becomes part of the literal
+                        Err(_) if num == "2147483648" && ends_with_unary_minus(&tokens) => {
+                            tokens.pop();
+                            i32::MIN
+                        }
+                        Err(_) => {
+                            return Err(RqlError::SyntaxError {
+                                msg: format!("Integer literal out of range: {}", num),
+                                pos: Some(start),
+                                token: None,
+                            });
+                        }
+                    };
+                    tokens.push(Token::Int(parsed));
+                }
             }
             c if c.is_ascii_alphabetic() || c == '_' => {
                 let mut word = String::new();
-                while let Some(&ch) = chars.peek() {
+                while let Some(&(_, ch)) = chars.peek() {
                     if ch.is_ascii_alphanumeric() || ch == '_' {
                         word.push(ch);
                         chars.next();
@@ -136,6 +1083,9 @@ pub fn tokenize(input: &str) -> Vec<Token> {
                         break;
                     }
                 }
+                if word.len() > limits.max_identifier_len {
+                    return Err(too_long("Identifier", limits.max_identifier_len, start));
+                }
                 match word.to_uppercase().as_str() {
                     "INSERT" => tokens.push(Token::Insert),
                     "INTO" => tokens.push(Token::Into),
@@ -148,315 +1098,2054 @@ pub fn tokenize(input: &str) -> Vec<Token> {
                     "UPDATE" => tokens.push(Token::Update),
                     "SET" => tokens.push(Token::Set),
                     "WHERE" => tokens.push(Token::Where),
+                    "SHOW" => tokens.push(Token::Show),
+                    "TABLES" => tokens.push(Token::Tables),
+                    "ORDER" => tokens.push(Token::Order),
+                    "BY" => tokens.push(Token::By),
+                    "ASC" => tokens.push(Token::Asc),
+                    "DESC" => tokens.push(Token::Desc),
+                    "GROUP" => tokens.push(Token::Group),
+                    "HAVING" => tokens.push(Token::Having),
+                    "DISTINCT" => tokens.push(Token::Distinct),
                     _ => tokens.push(Token::Identifier(word)),
                 }
             }
             _ => {
-                panic!("Unexpected character: {}", c);
+                return Err(RqlError::SyntaxError {
+                    msg: format!("Unexpected character: {}", c),
+                    pos: Some(start),
+                    token: None,
+                });
             }
         }
+        if tokens.len() > offsets.len() {
+            offsets.push(start);
+        }
     }
 
-    tokens
+    Ok(tokens.into_iter().zip(offsets).collect())
 }
 
-// --- Parser ---
-pub fn parse(tokens: &[Token]) -> Result<Statement, String> {
-    return match tokens.first() {
-        Some(Token::Insert) => parse_insert(tokens),
-        Some(Token::Select) => parse_select(tokens),
-        Some(Token::Create) => parse_create(tokens),
-        Some(Token::Delete) => parse_delete(tokens),
-        Some(Token::Update) => parse_update(tokens),
-        _ => Err("Unknown or unsupported statement".into()),
-    };
+// Appends a run of ASCII digits to `num`, returning whether there was at least one
+fn read_digits(chars: &mut std::iter::Peekable<std::str::CharIndices>, num: &mut String) -> bool {
+    let len = num.len();
+    while let Some(&(_, ch)) = chars.peek() {
+        if !ch.is_ascii_digit() {
+            break;
+        }
+        num.push(ch);
+        chars.next();
+    }
+    num.len() > len
 }
-// Parses: CREATE TABLE table_name (col1, col2, ...);
-pub fn parse_create(tokens: &[Token]) -> Result<Statement, String> {
-    let mut iter = tokens.iter().peekable();
 
-    match iter.next() {
-        Some(Token::Create) => {}
-        _ => return Err("Expected 'CREATE'".into()),
+// Whether the last token is a `-` that negates what follows rather than subtracting it
+fn ends_with_unary_minus(tokens: &[Token]) -> bool {
+    match tokens {
+        [.., operand, Token::Minus] => !matches!(
+            operand,
+            Token::Int(_) | Token::Float(_) | Token::String(_) | Token::Identifier(_) | Token::RightParen
+        ),
+        [Token::Minus] => true,
+        _ => false,
+    }
+}
+
+fn too_long(what: &str, limit: usize, pos: usize) -> RqlError {
+    RqlError::SyntaxError { msg: format!("{} is longer than {} bytes", what, limit), pos: Some(pos), token: None }
+}
+
+fn malformed_number(num: &str, pos: usize) -> RqlError {
+    RqlError::SyntaxError { msg: format!("Malformed number: {}", num), pos: Some(pos), token: None }
+}
+
+// A statement containing `?` placeholders, bound to values each time it runs.
+// Bound values are spliced in as tokens, so they are never re-tokenized as SQL.
+#[derive(Debug, Clone)]
+pub struct PreparedStatement {
+    tokens: Vec<Token>,
+    placeholders: Vec<usize>, // token index of each `?`, in order
+}
+
+impl PreparedStatement {
+    pub fn new(sql: &str) -> Result<Self, RqlError> {
+        let tokens = tokenize(sql)?;
+        let placeholders = tokens
+            .iter()
+            .enumerate()
+            .filter(|(_, tok)| matches!(tok, Token::Placeholder))
+            .map(|(i, _)| i)
+            .collect();
+        let prepared = PreparedStatement { tokens, placeholders };
+
+        // Surface syntax errors now rather than on first execution
+        let dummy = vec![Value::Int(0); prepared.parameter_count()];
+        prepared.bind(&dummy)?;
+        Ok(prepared)
+    }
+
+    pub fn parameter_count(&self) -> usize {
+        self.placeholders.len()
+    }
+
+    // Substitute `params` for the placeholders and parse the result
+    pub fn bind(&self, params: &[Value]) -> Result<Statement, RqlError> {
+        if params.len() != self.placeholders.len() {
+            return Err(RqlError::execution(format!(
+                "expected {} parameters, got {}",
+                self.placeholders.len(),
+                params.len()
+            )));
+        }
+        let mut tokens = Vec::with_capacity(self.tokens.len());
+        for (i, tok) in self.tokens.iter().enumerate() {
+            match self.placeholders.iter().position(|pos| *pos == i) {
+                Some(n) => tokens.extend(value_tokens(&params[n])?),
+                None => tokens.push(tok.clone()),
+            }
+        }
+        parse(&tokens)
+    }
+}
+
+// The literal tokens a parameter value stands for
+fn value_tokens(value: &Value) -> Result<Vec<Token>, RqlError> {
+    match value {
+        Value::Int(i) if *i < 0 => match i.checked_neg() {
+            Some(n) => Ok(vec![Token::Minus, Token::Int(n)]),
+            // i32::MIN has no positive counterpart to negate, so it goes in as it is
+            None => Ok(vec![Token::Int(*i)]),
+        },
+        Value::Int(i) => Ok(vec![Token::Int(*i)]),
+        Value::Float(f) if !f.is_finite() => Err(RqlError::TypeMismatch(format!("Parameter {} is not finite", f))),
+        Value::Float(f) if *f < 0.0 => Ok(vec![Token::Minus, Token::Float(-*f)]),
+        Value::Float(f) => Ok(vec![Token::Float(*f)]),
+        Value::Str(s) => Ok(vec![Token::String(s.clone())]),
+        Value::Date(d) => Ok(vec![Token::Identifier("DATE".into()), Token::String(d.clone())]),
+        Value::Null => Ok(vec![Token::Identifier("NULL".into())]),
+        other => Err(RqlError::TypeMismatch(format!("Unsupported parameter value: {:?}", other))),
+    }
+}
+
+// --- Parser ---
+// Deepest parenthesis nesting a statement may use; the parser recurses once per level
+const MAX_NESTING_DEPTH: usize = 100;
+
+pub fn parse(tokens: &[Token]) -> Result<Statement, RqlError> {
+    let mut depth = 0usize;
+    for (i, tok) in tokens.iter().enumerate() {
+        match tok {
+            Token::LeftParen => depth += 1,
+            Token::RightParen => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        if depth > MAX_NESTING_DEPTH {
+            return Err(RqlError::SyntaxError {
+                msg: format!("Statement is nested too deeply (more than {} levels of parentheses)", MAX_NESTING_DEPTH),
+                pos: None,
+                token: Some(i),
+            });
+        }
+    }
+
+    match tokens.first() {
+        Some(Token::Insert) => parse_insert(tokens),
+        Some(Token::Select) => parse_select(tokens),
+        Some(Token::Create) => parse_create(tokens),
+        Some(Token::Delete) => parse_delete(tokens),
+        Some(Token::Update) => parse_update(tokens),
+        Some(Token::Show) => parse_show(tokens),
+        Some(Token::Desc) => parse_describe(tokens),
+        Some(Token::Identifier(kw)) if kw.to_uppercase() == "DESCRIBE" => parse_describe(tokens),
+        Some(Token::Identifier(kw)) if kw.to_uppercase() == "EXPLAIN" => parse_explain(tokens),
+        Some(Token::Identifier(kw)) if kw.to_uppercase() == "IMPORT" => parse_import(tokens),
+        Some(Token::Identifier(kw)) if kw.to_uppercase() == "TRUNCATE" => parse_truncate(tokens),
+        Some(Token::Identifier(kw)) if kw.to_uppercase() == "VACUUM" => parse_vacuum(tokens),
+        Some(Token::Identifier(kw)) if kw.to_uppercase() == "CHECKSUM" => parse_checksum(tokens),
+        Some(Token::Identifier(kw)) if kw.to_uppercase() == "ALTER" => parse_alter(tokens),
+        Some(Token::Identifier(kw)) if kw.to_uppercase() == "COPY" => parse_copy(tokens),
+        Some(Token::Identifier(kw)) if kw.to_uppercase() == "DUMP" => parse_dump(tokens),
+        Some(Token::Identifier(kw)) if kw.to_uppercase() == "EXPORT" => parse_export(tokens),
+        Some(Token::Identifier(kw)) if kw.to_uppercase() == "DIFF" => parse_diff(tokens),
+        Some(Token::Identifier(kw)) if kw.to_uppercase() == "MERGE" => parse_merge(tokens),
+        Some(Token::Identifier(kw)) if ["BEGIN", "COMMIT", "ROLLBACK"].contains(&kw.to_uppercase().as_str()) => {
+            parse_transaction(tokens)
+        }
+        _ => Err(RqlError::SyntaxError { msg: "Unknown or unsupported statement".into(), pos: None, token: Some(0) }),
+    }
+}
+
+// The tokens of one statement, remembering the index of the last one handed out (the length of
+// the statement once they run out)
+struct Tracked<'a> {
+    iter: std::slice::Iter<'a, Token>,
+    len: usize,
+    reached: &'a Cell<usize>,
+}
+
+impl<'a> Iterator for Tracked<'a> {
+    type Item = &'a Token;
+
+    fn next(&mut self) -> Option<&'a Token> {
+        self.reached.set(self.len - self.iter.len());
+        self.iter.next()
+    }
+}
+
+// Runs a statement parser over `tokens`, and points a syntax error it returns at the token the
+// parser last read or peeked at, which is the one it choked on
+fn located<T>(
+    tokens: &[Token],
+    parse: impl FnOnce(std::iter::Peekable<Tracked<'_>>) -> Result<T, RqlError>,
+) -> Result<T, RqlError> {
+    let reached = Cell::new(0);
+    let iter = Tracked { iter: tokens.iter(), len: tokens.len(), reached: &reached };
+    parse(iter.peekable()).map_err(|e| match e {
+        RqlError::SyntaxError { msg, pos, token: None } => {
+            RqlError::SyntaxError { msg, pos, token: Some(reached.get()) }
+        }
+        e => e,
+    })
+}
+impl Statement {
+    // Statements that only read, which `Database::execute_read` can run through a shared reference
+    pub fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            Statement::Select(_)
+                | Statement::Union(_)
+                | Statement::Describe(_)
+                | Statement::ShowTables
+                | Statement::Checksum
+                | Statement::Explain(_)
+                | Statement::ExplainAnalyze(_)
+                | Statement::DryRun(_)
+                | Statement::Dump(_)
+                | Statement::Export(_)
+                | Statement::Diff(_)
+        )
+    }
+
+    // The statement's leading keywords, for messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            Statement::Insert(_) => "INSERT",
+            Statement::Select(_) => "SELECT",
+            Statement::Union(_) => "UNION",
+            Statement::Create(_) => "CREATE TABLE",
+            Statement::Delete(_) => "DELETE",
+            Statement::Update(_) => "UPDATE",
+            Statement::Describe(_) => "DESCRIBE",
+            Statement::ShowTables => "SHOW TABLES",
+            Statement::Explain(_) | Statement::DryRun(_) => "EXPLAIN",
+            Statement::ExplainAnalyze(_) => "EXPLAIN ANALYZE",
+            Statement::Import(_) => "IMPORT",
+            Statement::CreateIndex(_) => "CREATE INDEX",
+            Statement::Truncate(_) => "TRUNCATE",
+            Statement::Vacuum => "VACUUM",
+            Statement::Begin => "BEGIN",
+            Statement::Commit => "COMMIT",
+            Statement::Rollback => "ROLLBACK",
+            Statement::AlterTable(_) => "ALTER TABLE",
+            Statement::Copy(_) => "COPY",
+            Statement::Dump(_) => "DUMP",
+            Statement::Export(_) => "EXPORT",
+            Statement::Diff(_) => "DIFF",
+            Statement::Merge(_) => "MERGE",
+            Statement::Checksum => "CHECKSUM",
+        }
+    }
+}
+
+// A token in a position where only a name can appear (table names, column definitions,
+// SET targets, GROUP BY / ORDER BY keys). Keywords are accepted there as lowercase names,
+// so `CREATE TABLE t (from INT)` works; elsewhere such a name has to be quoted: "from".
+fn name_token(tok: &Token) -> Option<String> {
+    let keyword = match tok {
+        Token::Identifier(name) => return Some(name.clone()),
+        Token::Insert => "insert",
+        Token::Into => "into",
+        Token::Values => "values",
+        Token::Select => "select",
+        Token::From => "from",
+        Token::Delete => "delete",
+        Token::Create => "create",
+        Token::Update => "update",
+        Token::Set => "set",
+        Token::Where => "where",
+        Token::Show => "show",
+        Token::Tables => "tables",
+        Token::Order => "order",
+        Token::By => "by",
+        Token::Asc => "asc",
+        Token::Desc => "desc",
+        Token::Group => "group",
+        Token::Having => "having",
+        Token::Distinct => "distinct",
+        _ => return None,
+    };
+    Some(keyword.to_string())
+}
+
+// Splits a token stream into statements on top-level semicolons, dropping empty ones
+pub fn split_statements(tokens: &[Token]) -> Vec<&[Token]> {
+    statement_ranges(tokens).into_iter().map(|r| &tokens[r]).collect()
+}
+
+// Token index ranges of each non-empty statement, semicolons excluded
+pub fn statement_ranges(tokens: &[Token]) -> Vec<std::ops::Range<usize>> {
+    let mut ranges = vec![];
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, tok) in tokens.iter().enumerate() {
+        match tok {
+            Token::LeftParen => depth += 1,
+            Token::RightParen => depth = depth.saturating_sub(1),
+            Token::Semicolon if depth == 0 => {
+                if i > start {
+                    ranges.push(start..i);
+                }
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < tokens.len() {
+        ranges.push(start..tokens.len());
+    }
+    ranges
+}
+
+// Parses every statement in a semicolon-separated token stream
+pub fn parse_program(tokens: &[Token]) -> Result<Vec<Statement>, RqlError> {
+    split_statements(tokens).into_iter().filter_map(|tokens| parse_optional(tokens).transpose()).collect()
+}
+
+// Like `parse`, but a blank statement (no tokens, or only semicolons) is Ok(None) rather than an
+// error, so callers can skip it
+pub fn parse_optional(tokens: &[Token]) -> Result<Option<Statement>, RqlError> {
+    if tokens.iter().all(|tok| matches!(tok, Token::Semicolon)) {
+        return Ok(None);
+    }
+    parse(tokens).map(Some)
+}
+
+// Parses: DESCRIBE table_name; (or DESC table_name;)
+pub fn parse_describe(tokens: &[Token]) -> Result<Statement, RqlError> {
+    located(tokens, |mut iter| {
+        iter.next(); // DESCRIBE / DESC
+
+        let table_name = match iter.next().and_then(name_token) {
+            Some(name) => name,
+            _ => return Err(RqlError::syntax("Expected table name after 'DESCRIBE'")),
+        };
+
+        match iter.next() {
+            Some(Token::Semicolon) | None => {}
+            Some(tok) => return Err(RqlError::syntax(format!("Unexpected token after table name: {:?}", tok))),
+        }
+
+        Ok(Statement::Describe(DescribeStatement { table_name }))
+    })
+}
+
+// Parses: EXPLAIN SELECT ...; EXPLAIN DELETE ...; EXPLAIN UPDATE ...; EXPLAIN ANALYZE SELECT ...;
+pub fn parse_explain(tokens: &[Token]) -> Result<Statement, RqlError> {
+    let analyze = is_keyword(tokens.get(1).as_ref(), "ANALYZE");
+    let skipped = if analyze { 2 } else { 1 };
+    let statement = parse(&tokens[skipped..]).map_err(|e| match e {
+        // Count the EXPLAIN (and ANALYZE) tokens too
+        RqlError::SyntaxError { msg, pos, token } => {
+            RqlError::SyntaxError { msg, pos, token: token.map(|t| t + skipped) }
+        }
+        e => e,
+    })?;
+    if analyze {
+        return match statement {
+            Statement::Select(select_stmt) => Ok(Statement::ExplainAnalyze(select_stmt)),
+            _ => Err(RqlError::SyntaxError {
+                msg: "EXPLAIN ANALYZE only supports SELECT statements".into(),
+                pos: None,
+                token: Some(2),
+            }),
+        };
+    }
+    match statement {
+        Statement::Select(select_stmt) => Ok(Statement::Explain(select_stmt)),
+        statement @ (Statement::Delete(_) | Statement::Update(_)) => Ok(Statement::DryRun(Box::new(statement))),
+        _ => Err(RqlError::SyntaxError {
+            msg: "EXPLAIN only supports SELECT, DELETE and UPDATE statements".into(),
+            pos: None,
+            token: Some(1),
+        }),
+    }
+}
+
+// Parses: TRUNCATE [TABLE] table_name;
+pub fn parse_truncate(tokens: &[Token]) -> Result<Statement, RqlError> {
+    located(tokens, |mut iter| {
+        iter.next(); // TRUNCATE
+
+        if let Some(Token::Identifier(kw)) = iter.peek()
+            && kw.to_uppercase() == "TABLE"
+        {
+            iter.next();
+        }
+
+        let table_name = match iter.next().and_then(name_token) {
+            Some(name) => name,
+            _ => return Err(RqlError::syntax("Expected table name after 'TRUNCATE TABLE'")),
+        };
+
+        match iter.next() {
+            Some(Token::Semicolon) | None => {}
+            Some(tok) => return Err(RqlError::syntax(format!("Unexpected token after table name: {:?}", tok))),
+        }
+
+        Ok(Statement::Truncate(TruncateStatement { table_name }))
+    })
+}
+
+// Parses: ALTER TABLE table_name ADD CONSTRAINT [name] UNIQUE (col, ...) | CHECK (condition);
+pub fn parse_alter(tokens: &[Token]) -> Result<Statement, RqlError> {
+    located(tokens, |mut iter| {
+        iter.next(); // ALTER
+
+        if !is_keyword(iter.next().as_ref(), "TABLE") {
+            return Err(RqlError::syntax("Expected 'TABLE' after 'ALTER'"));
+        }
+        let table_name = match iter.next().and_then(name_token) {
+            Some(name) => name,
+            _ => return Err(RqlError::syntax("Expected table name after 'ALTER TABLE'")),
+        };
+        if !is_keyword(iter.next().as_ref(), "ADD") || !is_keyword(iter.next().as_ref(), "CONSTRAINT") {
+            return Err(RqlError::syntax("Expected 'ADD CONSTRAINT' after table name"));
+        }
+
+        let constraint_name = if is_keyword(iter.peek(), "UNIQUE") || is_keyword(iter.peek(), "CHECK") {
+            None
+        } else {
+            match iter.next().and_then(name_token) {
+                Some(name) => Some(name),
+                None => {
+                    return Err(RqlError::syntax("Expected a constraint name, UNIQUE or CHECK after 'ADD CONSTRAINT'"));
+                }
+            }
+        };
+
+        let kind = iter.next();
+        if !matches!(iter.next(), Some(Token::LeftParen)) {
+            return Err(RqlError::syntax("Expected '(' after UNIQUE or CHECK"));
+        }
+        let constraint = if is_keyword(kind.as_ref(), "UNIQUE") {
+            let mut columns: Vec<String> = vec![];
+            loop {
+                match iter.next().and_then(name_token) {
+                    Some(col) if columns.contains(&col) => {
+                        return Err(RqlError::syntax(format!("Column '{}' appears twice in the constraint", col)));
+                    }
+                    Some(col) => columns.push(col),
+                    None => return Err(RqlError::syntax("Expected column name in UNIQUE (...)")),
+                }
+                match iter.next() {
+                    Some(Token::Comma) => continue,
+                    Some(Token::RightParen) => break,
+                    _ => return Err(RqlError::syntax("Expected ',' or ')' after constraint column")),
+                }
+            }
+            TableConstraint::Unique(columns)
+        } else if is_keyword(kind.as_ref(), "CHECK") {
+            let condition = parse_condition(&mut iter, false)?;
+            if !matches!(iter.next(), Some(Token::RightParen)) {
+                return Err(RqlError::syntax("Expected ')' after CHECK condition"));
+            }
+            if has_subquery(&condition) {
+                return Err(RqlError::syntax("A CHECK condition cannot contain a subquery"));
+            }
+            TableConstraint::Check(condition)
+        } else {
+            return Err(RqlError::syntax("Expected UNIQUE or CHECK after 'ADD CONSTRAINT'"));
+        };
+
+        match iter.next() {
+            Some(Token::Semicolon) | None => {}
+            Some(tok) => return Err(RqlError::syntax(format!("Unexpected token after constraint: {:?}", tok))),
+        }
+        Ok(Statement::AlterTable(AlterTableStatement { table_name, constraint_name, constraint }))
+    })
+}
+
+// Whether a condition contains `IN (SELECT ...)` anywhere outside CASE expressions
+fn has_subquery(cond: &Condition) -> bool {
+    match cond {
+        Condition::InSelect { .. } => true,
+        Condition::And(left, right) | Condition::Or(left, right) => has_subquery(left) || has_subquery(right),
+        _ => false,
+    }
+}
+
+// Parses: VACUUM;
+pub fn parse_vacuum(tokens: &[Token]) -> Result<Statement, RqlError> {
+    located(tokens, |mut iter| {
+        iter.next(); // VACUUM
+        match iter.next() {
+            Some(Token::Semicolon) | None => Ok(Statement::Vacuum),
+            Some(tok) => Err(RqlError::syntax(format!("Unexpected token after VACUUM: {:?}", tok))),
+        }
+    })
+}
+
+// Parses: CHECKSUM;
+pub fn parse_checksum(tokens: &[Token]) -> Result<Statement, RqlError> {
+    located(tokens, |mut iter| {
+        iter.next(); // CHECKSUM
+        match iter.next() {
+            Some(Token::Semicolon) | None => Ok(Statement::Checksum),
+            Some(tok) => Err(RqlError::syntax(format!("Unexpected token after CHECKSUM: {:?}", tok))),
+        }
+    })
+}
+
+// Parses: IMPORT table_name FROM 'file.csv';
+pub fn parse_import(tokens: &[Token]) -> Result<Statement, RqlError> {
+    located(tokens, |mut iter| {
+        iter.next(); // IMPORT
+
+        let table_name = match iter.next().and_then(name_token) {
+            Some(name) => name,
+            _ => return Err(RqlError::syntax("Expected table name after 'IMPORT'")),
+        };
+
+        match iter.next() {
+            Some(Token::From) => {}
+            _ => return Err(RqlError::syntax("Expected 'FROM' after table name")),
+        }
+
+        let path = match iter.next() {
+            Some(Token::String(path)) => path.clone(),
+            _ => return Err(RqlError::syntax("Expected a quoted file path after 'FROM'")),
+        };
+
+        match iter.next() {
+            Some(Token::Semicolon) | None => {}
+            Some(tok) => return Err(RqlError::syntax(format!("Unexpected token after file path: {:?}", tok))),
+        }
+
+        Ok(Statement::Import(ImportStatement { table_name, path }))
+    })
+}
+
+// Parses: EXPORT table_name TO 'file.csv';
+pub fn parse_export(tokens: &[Token]) -> Result<Statement, RqlError> {
+    located(tokens, |mut iter| {
+        iter.next(); // EXPORT
+
+        let table_name = match iter.next().and_then(name_token) {
+            Some(name) => name,
+            _ => return Err(RqlError::syntax("Expected table name after 'EXPORT'")),
+        };
+
+        if !is_keyword(iter.next().as_ref(), "TO") {
+            return Err(RqlError::syntax("Expected 'TO' after table name"));
+        }
+
+        let path = match iter.next() {
+            Some(Token::String(path)) => path.clone(),
+            _ => return Err(RqlError::syntax("Expected a quoted file path after 'TO'")),
+        };
+
+        match iter.next() {
+            Some(Token::Semicolon) | None => {}
+            Some(tok) => return Err(RqlError::syntax(format!("Unexpected token after file path: {:?}", tok))),
+        }
+
+        Ok(Statement::Export(ExportStatement { table_name, path }))
+    })
+}
+
+// Parses: COPY table_name FROM STDIN [DELIMITER 'c'];
+pub fn parse_copy(tokens: &[Token]) -> Result<Statement, RqlError> {
+    located(tokens, |mut iter| {
+        iter.next(); // COPY
+
+        let table_name = match iter.next().and_then(name_token) {
+            Some(name) => name,
+            _ => return Err(RqlError::syntax("Expected table name after 'COPY'")),
+        };
+        if !matches!(iter.next(), Some(Token::From)) {
+            return Err(RqlError::syntax("Expected 'FROM' after table name"));
+        }
+        if !is_keyword(iter.next().as_ref(), "STDIN") {
+            return Err(RqlError::syntax("Expected STDIN after 'FROM' (use IMPORT to load a file)"));
+        }
+
+        let mut delimiter = '\t';
+        if is_keyword(iter.peek(), "DELIMITER") {
+            iter.next();
+            let mut chars = match iter.next() {
+                Some(Token::String(text)) => text.chars(),
+                _ => return Err(RqlError::syntax("Expected a quoted character after DELIMITER")),
+            };
+            delimiter = match (chars.next(), chars.next()) {
+                (Some(c), None) if !matches!(c, '"' | '\n' | '\r') => c,
+                _ => return Err(RqlError::syntax("DELIMITER expects one character other than a quote or newline")),
+            };
+        }
+
+        match iter.next() {
+            Some(Token::Semicolon) | None => {}
+            Some(tok) => return Err(RqlError::syntax(format!("Unexpected token after COPY: {:?}", tok))),
+        }
+        Ok(Statement::Copy(CopyStatement { table_name, delimiter }))
+    })
+}
+
+// Parses: DUMP [table_name];
+pub fn parse_dump(tokens: &[Token]) -> Result<Statement, RqlError> {
+    located(tokens, |mut iter| {
+        iter.next(); // DUMP
+
+        let table_name = match iter.next() {
+            Some(Token::Semicolon) | None => return Ok(Statement::Dump(DumpStatement { table_name: None })),
+            Some(tok) => match name_token(tok) {
+                Some(name) => name,
+                None => return Err(RqlError::syntax(format!("Expected table name after 'DUMP', found {:?}", tok))),
+            },
+        };
+
+        match iter.next() {
+            Some(Token::Semicolon) | None => {}
+            Some(tok) => return Err(RqlError::syntax(format!("Unexpected token after table name: {:?}", tok))),
+        }
+        Ok(Statement::Dump(DumpStatement { table_name: Some(table_name) }))
+    })
+}
+
+// Parses: DIFF 'old.bin' 'new.bin';
+pub fn parse_diff(tokens: &[Token]) -> Result<Statement, RqlError> {
+    located(tokens, |mut iter| {
+        iter.next(); // DIFF
+
+        let old_path = match iter.next() {
+            Some(Token::String(path)) => path.clone(),
+            _ => return Err(RqlError::syntax("Expected a quoted file path after 'DIFF'")),
+        };
+        let new_path = match iter.next() {
+            Some(Token::String(path)) => path.clone(),
+            _ => return Err(RqlError::syntax("Expected a second quoted file path to compare with")),
+        };
+
+        match iter.next() {
+            Some(Token::Semicolon) | None => {}
+            Some(tok) => return Err(RqlError::syntax(format!("Unexpected token after file path: {:?}", tok))),
+        }
+
+        Ok(Statement::Diff(DiffStatement { old_path, new_path }))
+    })
+}
+
+// Parses: MERGE FROM 'other.bin' [ON CONFLICT ERROR | SKIP | OVERWRITE];
+pub fn parse_merge(tokens: &[Token]) -> Result<Statement, RqlError> {
+    located(tokens, |mut iter| {
+        iter.next(); // MERGE
+
+        match iter.next() {
+            Some(Token::From) => {}
+            _ => return Err(RqlError::syntax("Expected 'FROM' after 'MERGE'")),
+        }
+        let path = match iter.next() {
+            Some(Token::String(path)) => path.clone(),
+            _ => return Err(RqlError::syntax("Expected a quoted file path after 'MERGE FROM'")),
+        };
+
+        let mut on_conflict = OnConflict::default();
+        if is_keyword(iter.peek(), "ON") {
+            iter.next();
+            if !is_keyword(iter.next().as_ref(), "CONFLICT") {
+                return Err(RqlError::syntax("Expected 'CONFLICT' after 'ON'"));
+            }
+            on_conflict = match iter.next() {
+                Some(Token::Identifier(kw)) if kw.eq_ignore_ascii_case("ERROR") => OnConflict::Error,
+                Some(Token::Identifier(kw)) if kw.eq_ignore_ascii_case("SKIP") => OnConflict::Skip,
+                Some(Token::Identifier(kw)) if kw.eq_ignore_ascii_case("OVERWRITE") => OnConflict::Overwrite,
+                _ => return Err(RqlError::syntax("Expected ERROR, SKIP or OVERWRITE after 'ON CONFLICT'")),
+            };
+        }
+
+        match iter.next() {
+            Some(Token::Semicolon) | None => {}
+            Some(tok) => return Err(RqlError::syntax(format!("Unexpected token after MERGE: {:?}", tok))),
+        }
+
+        Ok(Statement::Merge(MergeStatement { path, on_conflict }))
+    })
+}
+
+// Parses: BEGIN [TRANSACTION]; COMMIT; ROLLBACK;
+pub fn parse_transaction(tokens: &[Token]) -> Result<Statement, RqlError> {
+    located(tokens, |mut iter| {
+        let statement = match iter.next() {
+            Some(Token::Identifier(kw)) => match kw.to_uppercase().as_str() {
+                "BEGIN" => Statement::Begin,
+                "COMMIT" => Statement::Commit,
+                "ROLLBACK" => Statement::Rollback,
+                _ => return Err(RqlError::syntax(format!("Unexpected '{}'", kw))),
+            },
+            _ => return Err(RqlError::syntax("Expected BEGIN, COMMIT or ROLLBACK")),
+        };
+
+        if let Some(Token::Identifier(kw)) = iter.peek()
+            && kw.to_uppercase() == "TRANSACTION"
+        {
+            iter.next();
+        }
+
+        match iter.next() {
+            Some(Token::Semicolon) | None => Ok(statement),
+            Some(tok) => Err(RqlError::syntax(format!("Unexpected token after {:?}: {:?}", statement, tok))),
+        }
+    })
+}
+
+// Parses: SHOW TABLES;
+pub fn parse_show(tokens: &[Token]) -> Result<Statement, RqlError> {
+    located(tokens, |mut iter| {
+        match iter.next() {
+            Some(Token::Show) => {}
+            _ => return Err(RqlError::syntax("Expected 'SHOW'")),
+        }
+
+        match iter.next() {
+            Some(Token::Tables) => {}
+            _ => return Err(RqlError::syntax("Expected 'TABLES' after 'SHOW'")),
+        }
+
+        match iter.next() {
+            Some(Token::Semicolon) | None => Ok(Statement::ShowTables),
+            Some(tok) => Err(RqlError::syntax(format!("Unexpected token after SHOW TABLES: {:?}", tok))),
+        }
+    })
+}
+
+// Parses: CREATE TABLE table_name (col1, col2, ...);
+pub fn parse_create(tokens: &[Token]) -> Result<Statement, RqlError> {
+    located(tokens, |mut iter| {
+        match iter.next() {
+            Some(Token::Create) => {}
+            _ => return Err(RqlError::syntax("Expected 'CREATE'")),
+        }
+
+        match iter.next() {
+            Some(Token::Identifier(kw)) if kw.to_uppercase() == "TABLE" => {},
+            Some(Token::Identifier(kw)) if kw.to_uppercase() == "INDEX" => return parse_create_index(&mut iter),
+            _ => return Err(RqlError::syntax("Expected 'TABLE' or 'INDEX' after 'CREATE'")),
+        }
+
+        let table_name = match iter.next().and_then(name_token) {
+            Some(name) => name,
+            _ => return Err(RqlError::syntax("Expected table name after 'TABLE'")),
+        };
+
+        match iter.next() {
+            Some(Token::LeftParen) => {}
+            _ => return Err(RqlError::syntax("Expected '(' after table name")),
+        }
+
+        let mut columns = vec![];
+        loop {
+            match iter.next() {
+                Some(Token::Comma) => continue,
+                Some(Token::RightParen) => break,
+                Some(tok) => match name_token(tok) {
+                    Some(col) => columns.push(parse_column_def(&col, &mut iter)?),
+                    None => return Err(RqlError::syntax(format!("Unexpected token in columns: {:?}", tok))),
+                },
+                None => return Err(RqlError::syntax("Unexpected end of input in columns")),
+            }
+        }
+
+        if let Some(Token::Semicolon) = iter.peek() {
+            iter.next(); // consume semicolon
+        }
+
+        Ok(Statement::Create(CreateTableStatement { table_name, columns }))
+    })
+}
+
+// Parses the rest of: CREATE INDEX index_name ON table_name(column);
+fn parse_create_index<'a, I>(iter: &mut std::iter::Peekable<I>) -> Result<Statement, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    let index_name = match iter.next().and_then(name_token) {
+        Some(name) => name,
+        _ => return Err(RqlError::syntax("Expected index name after 'INDEX'")),
+    };
+
+    match iter.next() {
+        Some(Token::Identifier(kw)) if kw.to_uppercase() == "ON" => {}
+        _ => return Err(RqlError::syntax("Expected 'ON' after index name")),
     }
 
+    let table_name = match iter.next().and_then(name_token) {
+        Some(name) => name,
+        _ => return Err(RqlError::syntax("Expected table name after 'ON'")),
+    };
+
     match iter.next() {
-        Some(Token::Identifier(kw)) if kw.to_uppercase() == "TABLE" => {},
-        _ => return Err("Expected 'TABLE' after 'CREATE'".into()),
+        Some(Token::LeftParen) => {}
+        _ => return Err(RqlError::syntax(format!("Expected '(column, ...)' after table name '{}'", table_name))),
+    }
+    let mut columns: Vec<String> = vec![];
+    loop {
+        match iter.next().and_then(name_token) {
+            Some(col) if columns.contains(&col) => {
+                return Err(RqlError::syntax(format!("Column '{}' appears twice in the index", col)));
+            }
+            Some(col) => columns.push(col),
+            None => return Err(RqlError::syntax("Expected column name in CREATE INDEX")),
+        }
+        match iter.next() {
+            Some(Token::Comma) => continue,
+            Some(Token::RightParen) => break,
+            _ => return Err(RqlError::syntax("Expected ',' or ')' after index column")),
+        }
+    }
+
+    // Optional USING BTREE | HASH (hash is the default)
+    let mut sorted = false;
+    if let Some(Token::Identifier(kw)) = iter.peek()
+        && kw.eq_ignore_ascii_case("USING")
+    {
+        iter.next();
+        match iter.next() {
+            Some(Token::Identifier(kind)) if kind.eq_ignore_ascii_case("BTREE") => sorted = true,
+            Some(Token::Identifier(kind)) if kind.eq_ignore_ascii_case("HASH") => {}
+            _ => return Err(RqlError::syntax("Expected BTREE or HASH after USING")),
+        }
+    }
+
+    match iter.next() {
+        Some(Token::Semicolon) | None => {}
+        Some(tok) => return Err(RqlError::syntax(format!("Unexpected token after CREATE INDEX: {:?}", tok))),
+    }
+
+    Ok(Statement::CreateIndex(CreateIndexStatement { index_name, table_name, columns, sorted }))
+}
+
+// Parses the optional type and REFERENCES clause following a column name
+fn parse_column_def<'a, I>(name: &str, iter: &mut std::iter::Peekable<I>) -> Result<ColumnDef, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    let mut column =
+        ColumnDef { name: name.to_string(), data_type: None, references: None, on_delete: OnDelete::Restrict };
+
+    while let Some(Token::Identifier(word)) = iter.peek() {
+        match word.to_uppercase().as_str() {
+            "INT" | "INTEGER" if column.data_type.is_none() => column.data_type = Some(DataType::Int),
+            "TEXT" | "VARCHAR" | "STRING" if column.data_type.is_none() => column.data_type = Some(DataType::Text),
+            "DATE" if column.data_type.is_none() => column.data_type = Some(DataType::Date),
+            "FLOAT" | "REAL" | "DOUBLE" if column.data_type.is_none() => column.data_type = Some(DataType::Float),
+            "REFERENCES" if column.references.is_none() => {
+                iter.next(); // consume REFERENCES
+                let parent = match iter.next().and_then(name_token) {
+                    Some(t) => t,
+                    _ => return Err(RqlError::syntax(format!("Expected table name after REFERENCES for column '{}'", name))),
+                };
+                let parent_column = match (iter.next(), iter.next().and_then(name_token), iter.next()) {
+                    (Some(Token::LeftParen), Some(c), Some(Token::RightParen)) => c,
+                    _ => return Err(RqlError::syntax(format!("Expected REFERENCES {}(column) for column '{}'", parent, name))),
+                };
+                column.references = Some((parent, parent_column));
+                if is_keyword(iter.peek(), "ON") {
+                    iter.next(); // consume ON
+                    if !matches!(iter.next(), Some(Token::Delete)) {
+                        return Err(RqlError::syntax(format!("Expected DELETE after ON for column '{}'", name)));
+                    }
+                    column.on_delete = match iter.next() {
+                        Some(Token::Identifier(action)) if action.eq_ignore_ascii_case("CASCADE") => OnDelete::Cascade,
+                        Some(Token::Identifier(action)) if action.eq_ignore_ascii_case("RESTRICT") => OnDelete::Restrict,
+                        _ => {
+                            return Err(RqlError::syntax(format!(
+                                "Expected CASCADE or RESTRICT after ON DELETE for column '{}'",
+                                name
+                            )))
+                        }
+                    };
+                }
+                continue;
+            }
+            _ => return Err(RqlError::syntax(format!("Unexpected '{}' in definition of column '{}'", word, name))),
+        }
+        iter.next(); // consume type name
+    }
+
+    Ok(column)
+}
+
+// Parses: DELETE FROM table_name WHERE condition;
+pub fn parse_delete(tokens: &[Token]) -> Result<Statement, RqlError> {
+    located(tokens, |mut iter| {
+        match iter.next() {
+            Some(Token::Delete) => {}
+            _ => return Err(RqlError::syntax("Expected 'DELETE'")),
+        }
+
+        match iter.next() {
+            Some(Token::From) => {}
+            _ => return Err(RqlError::syntax("Expected 'FROM' after 'DELETE'")),
+        }
+
+        let table_name = match iter.next().and_then(name_token) {
+            Some(name) => name,
+            _ => return Err(RqlError::syntax("Expected table name after 'FROM'")),
+        };
+
+        let condition = match iter.peek() {
+            Some(Token::Where) => {
+                iter.next(); // consume WHERE
+                Some(parse_condition(&mut iter, false)?)
+            }
+            // No WHERE: the executor refuses, reporting how many rows would have gone
+            Some(Token::Semicolon) | None => None,
+            Some(Token::Identifier(kw)) if kw.eq_ignore_ascii_case("LIMIT") => None,
+            _ => return Err(RqlError::syntax("Expected 'WHERE' after table name in DELETE")),
+        };
+        let limit = parse_limit(&mut iter)?;
+        match iter.next() {
+            Some(Token::Semicolon) | None => {}
+            Some(tok) => return Err(RqlError::syntax(format!("Unexpected token after DELETE condition: {:?}", tok))),
+        }
+
+        Ok(Statement::Delete(DeleteStatement { table_name, condition, limit }))
+    })
+}
+
+// Parses an optional `LIMIT n` (SELECT, DELETE and UPDATE)
+fn parse_limit<'a, I>(iter: &mut std::iter::Peekable<I>) -> Result<Option<usize>, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    if !is_keyword(iter.peek(), "LIMIT") {
+        return Ok(None);
+    }
+    iter.next(); // consume LIMIT
+    match iter.next() {
+        Some(Token::Int(n)) if *n >= 0 => Ok(Some(*n as usize)),
+        _ => Err(RqlError::syntax("Expected a non-negative row count after LIMIT")),
+    }
+}
+
+
+pub fn parse_insert(tokens: &[Token]) -> Result<Statement, RqlError> {
+    located(tokens, |mut iter| {
+        match iter.next() {
+            Some(Token::Insert) => {}
+            _ => return Err(RqlError::syntax("Expected 'INSERT'")),
+        }
+
+        match iter.next() {
+            Some(Token::Into) => {}
+            _ => return Err(RqlError::syntax("Expected 'INTO' after 'INSERT'")),
+        }
+
+        let table_name = match iter.next().and_then(name_token) {
+            Some(name) => name,
+            _ => return Err(RqlError::syntax("Expected table name after 'INTO'")),
+        };
+
+        let source = match iter.peek() {
+            Some(Token::Values) => {
+                iter.next();
+                // One or more parenthesised rows, separated by commas
+                let mut rows = vec![parse_insert_row(&mut iter)?];
+                while let Some(Token::Comma) = iter.peek() {
+                    iter.next();
+                    rows.push(parse_insert_row(&mut iter)?);
+                }
+                InsertSource::Values(rows)
+            }
+            Some(Token::Select) => {
+                let select_stmt = parse_select_body(&mut iter)?;
+                match iter.peek() {
+                    Some(Token::Semicolon) | None => {}
+                    Some(tok) => return Err(RqlError::syntax(format!("Unexpected token after SELECT: {:?}", tok))),
+                }
+                InsertSource::Select(Box::new(select_stmt))
+            }
+            _ => return Err(RqlError::syntax("Expected 'VALUES' or SELECT after the table name")),
+        };
+
+        if let Some(Token::Semicolon) = iter.peek() {
+            iter.next(); // consume semicolon
+        }
+        Ok(Statement::Insert(InsertStatement { table_name, source }))
+    })
+}
+
+// `(value, value, ...)` in an INSERT's VALUES list
+fn parse_insert_row<'a, I>(iter: &mut std::iter::Peekable<I>) -> Result<Vec<Value>, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    match iter.next() {
+        Some(Token::LeftParen) => {}
+        _ => return Err(RqlError::syntax("Expected '(' in VALUES")),
+    }
+
+    let mut values = vec![];
+
+    loop {
+        match iter.next() {
+            Some(Token::Int(i)) => values.push(Value::Int(*i)),
+            Some(Token::Float(f)) => values.push(Value::Float(*f)),
+            Some(Token::Minus) => match iter.next() {
+                Some(Token::Int(i)) => values.push(Value::Int(negate(*i)?)),
+                Some(Token::Float(f)) => values.push(Value::Float(-*f)),
+                _ => return Err(RqlError::syntax("Expected a number after '-' in VALUES")),
+            },
+            Some(Token::String(s)) => values.push(Value::Str(s.clone())),
+            Some(Token::Identifier(kw)) if kw.eq_ignore_ascii_case("DATE") => values.push(parse_date_literal(iter)?),
+            Some(Token::Identifier(kw)) if kw.eq_ignore_ascii_case("NULL") => values.push(Value::Null),
+            // Columns have no default values to fall back on
+            Some(Token::Identifier(kw)) if kw.eq_ignore_ascii_case("DEFAULT") => {
+                let msg = "DEFAULT is not supported, as columns have no defaults; write NULL or a value";
+                return Err(RqlError::syntax(msg));
+            }
+            Some(Token::Comma) => continue,
+            Some(Token::RightParen) => break,
+            Some(tok) => return Err(RqlError::syntax(format!("Unexpected token in VALUES: {:?}", tok))),
+            None => return Err(RqlError::syntax("Unexpected end of input in VALUES")),
+        }
     }
+    Ok(values)
+}
+pub fn parse_select(tokens: &[Token]) -> Result<Statement, RqlError> {
+    located(tokens, |mut iter| {
+        let select_stmt = parse_select_body(&mut iter)?;
 
-    let table_name = match iter.next() {
-        Some(Token::Identifier(name)) => name.clone(),
-        _ => return Err("Expected table name after 'TABLE'".into()),
-    };
+        // Further SELECTs joined with UNION / UNION ALL
+        let mut rest = vec![];
+        while let Some(Token::Identifier(kw)) = iter.peek()
+            && kw.eq_ignore_ascii_case("UNION")
+        {
+            let previous = rest.last().map_or(&select_stmt, |(_, s)| s);
+            if previous.limit.is_some() || previous.offset > 0 {
+                return Err(RqlError::syntax("LIMIT and OFFSET cannot be used with UNION"));
+            }
+            iter.next(); // consume UNION
+            let all = matches!(iter.peek(), Some(Token::Identifier(kw)) if kw.eq_ignore_ascii_case("ALL"));
+            if all {
+                iter.next();
+            }
+            if !matches!(iter.peek(), Some(Token::Select)) {
+                return Err(RqlError::syntax(format!("Expected SELECT after UNION{}", if all { " ALL" } else { "" })));
+            }
+            rest.push((all, parse_select_body(&mut iter)?));
+        }
+        if let Some((_, last)) = rest.last()
+            && (last.limit.is_some() || last.offset > 0)
+        {
+            return Err(RqlError::syntax("LIMIT and OFFSET cannot be used with UNION"));
+        }
+
+        match iter.next() {
+            Some(Token::Semicolon) | None => {}
+            Some(tok) => return Err(RqlError::syntax(format!("Unexpected token after SELECT: {:?}", tok))),
+        }
+
+        if rest.is_empty() {
+            Ok(Statement::Select(select_stmt))
+        } else {
+            Ok(Statement::Union(UnionStatement { first: select_stmt, rest }))
+        }
+    })
+}
 
+// Parses a SELECT up to (not including) whatever ends it: `;`, end of input, or the `)`
+// closing a subquery
+// The parenthesised column list of `* EXCEPT (a, b)`
+fn parse_except_list<'a, I>(iter: &mut std::iter::Peekable<I>) -> Result<Vec<String>, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
     match iter.next() {
         Some(Token::LeftParen) => {}
-        _ => return Err("Expected '(' after table name".into()),
+        _ => return Err(RqlError::syntax("Expected '(' after EXCEPT")),
     }
-
     let mut columns = vec![];
     loop {
         match iter.next() {
-            Some(Token::Identifier(col)) => columns.push(col.clone()),
+            Some(tok) => match name_token(tok) {
+                Some(name) => columns.push(qualified_name(iter, name)?),
+                None => return Err(RqlError::syntax(format!("Unexpected token in EXCEPT list: {:?}", tok))),
+            },
+            None => return Err(RqlError::syntax("Expected column name in EXCEPT list")),
+        }
+        match iter.next() {
             Some(Token::Comma) => continue,
-            Some(Token::RightParen) => break,
-            Some(tok) => return Err(format!("Unexpected token in columns: {:?}", tok)),
-            None => return Err("Unexpected end of input in columns".into()),
+            Some(Token::RightParen) => return Ok(columns),
+            _ => return Err(RqlError::syntax("Expected ',' or ')' in EXCEPT list")),
         }
     }
-
-    if let Some(Token::Semicolon) = iter.peek() {
-        iter.next(); // consume semicolon
-    }
-
-    Ok(Statement::Create(CreateTableStatement { table_name, columns }))
 }
 
-// Parses: DELETE FROM table_name WHERE condition;
-pub fn parse_delete(tokens: &[Token]) -> Result<Statement, String> {
-    let mut iter = tokens.iter().peekable();
+// One item of a SELECT list: `*`, `* EXCEPT (...)`, `table.*`, or an expression with an optional alias
+fn parse_select_item<'a, I>(iter: &mut std::iter::Peekable<I>) -> Result<SelectItem, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    let first = match iter.peek() {
+        Some(Token::Star) => {
+            iter.next();
+            if is_keyword(iter.peek(), "EXCEPT") {
+                iter.next();
+                return Ok(SelectItem::StarExcept(parse_except_list(iter)?));
+            }
+            return Ok(SelectItem::Star);
+        }
+        Some(Token::From) => return Err(RqlError::syntax("Expected a column list before 'FROM'")),
+        // A name may start `table.*` as well as an expression, so it is read here rather than by
+        // parse_operand
+        Some(Token::Identifier(name)) => {
+            let name = name.clone();
+            iter.next();
+            if !matches!(iter.peek(), Some(Token::Dot)) {
+                parse_named_operand(iter, name)?
+            } else {
+                iter.next(); // consume .
+                if matches!(iter.peek(), Some(Token::Star)) {
+                    iter.next();
+                    return Ok(SelectItem::TableStar(name));
+                }
+                Expr::Column(column_after_dot(iter, name)?)
+            }
+        }
+        Some(_) => parse_primary(iter)?,
+        None => return Err(RqlError::syntax("Unexpected end of input in SELECT")),
+    };
+    let expr = parse_binary_rest(iter, first, 1)?;
+    let alias = if is_keyword(iter.peek(), "AS") {
+        iter.next(); // consume AS
+        match iter.next() {
+            Some(Token::Identifier(alias)) => Some(alias.clone()),
+            _ => return Err(RqlError::syntax(format!("Expected a column alias after '{} AS'", expr))),
+        }
+    } else {
+        None
+    };
+    Ok(SelectItem::Expr(expr, alias))
+}
 
+fn parse_select_body<'a, I>(iter: &mut std::iter::Peekable<I>) -> Result<SelectStatement, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
     match iter.next() {
-        Some(Token::Delete) => {}
-        _ => return Err("Expected 'DELETE'".into()),
+        Some(Token::Select) => {}
+        _ => return Err(RqlError::syntax("Expected 'SELECT'")),
     }
 
-    match iter.next() {
-    Some(Token::From) => {},
-    _ => return Err("Expected 'FROM' after 'DELETE'".into()),
-}
+    let mut items = vec![];
 
-    let table_name = match iter.next() {
-        Some(Token::Identifier(name)) => name.clone(),
-        _ => return Err("Expected table name after 'FROM'".into()),
+    loop {
+        items.push(parse_select_item(iter)?);
+        match iter.next() {
+            Some(Token::Comma) => continue,
+            Some(Token::From) => break,
+            Some(tok) => return Err(RqlError::syntax(format!("Unexpected token in SELECT: {:?}", tok))),
+            None => return Err(RqlError::syntax("Unexpected end of input in SELECT")),
+        }
+    }
+
+    let table_name = match iter.next().and_then(name_token) {
+        Some(name) => name,
+        _ => return Err(RqlError::syntax("Expected table name after 'FROM'")),
     };
 
-    let condition = match iter.next() {
-        Some(Token::Where) => {
-            // Collect everything until semicolon as condition string
-            let mut cond = String::new();
-            while let Some(tok) = iter.next() {
-                match tok {
-                    Token::Semicolon => break,
-                    Token::Identifier(s) => {
-                        if !cond.is_empty() { cond.push(' '); }
-                        cond.push_str(s);
-                    }
-                    Token::Equals => {
-                        cond.push_str(" = ");
-                    }
-                    Token::String(s) => {
-                        if !cond.is_empty() { cond.push(' '); }
-                        cond.push_str(&format!("'{}'", s));
-                    }
-                    Token::Int(i) => {
-                        if !cond.is_empty() { cond.push(' '); }
-                        cond.push_str(&i.to_string());
-                    }
-                    Token::Star => {
-                        if !cond.is_empty() { cond.push(' '); }
-                        cond.push('*');
-                    }
-                    Token::Comma => cond.push(','),
-                    _ => {}
-                }
+    let table_alias = parse_table_alias(iter)?;
+
+    let mut joins = vec![];
+    loop {
+        let cross = if is_keyword(iter.peek(), "CROSS") {
+            true
+        } else if is_keyword(iter.peek(), "INNER") || is_keyword(iter.peek(), "JOIN") {
+            false
+        } else {
+            break;
+        };
+        let first = iter.next(); // CROSS, INNER or JOIN
+        if !is_keyword(first.as_ref(), "JOIN") && !is_keyword(iter.next().as_ref(), "JOIN") {
+            return Err(RqlError::syntax(format!("Expected JOIN after {}", if cross { "CROSS" } else { "INNER" })));
+        }
+        let table_name = match iter.next().and_then(name_token) {
+            Some(name) => name,
+            _ => return Err(RqlError::syntax("Expected table name after 'JOIN'")),
+        };
+        let alias = parse_table_alias(iter)?;
+        let has_on = is_keyword(iter.peek(), "ON");
+        let kind = match (cross, has_on) {
+            (true, true) => return Err(RqlError::syntax("CROSS JOIN does not take an ON clause")),
+            (true, false) => JoinKind::Cross,
+            (false, true) => {
+                iter.next(); // consume ON
+                JoinKind::Inner(parse_condition(iter, false)?)
+            }
+            (false, false) => {
+                return Err(RqlError::syntax(format!(
+                    "Expected ON after 'JOIN {}' (use CROSS JOIN to pair every row)",
+                    table_name
+                )))
             }
-            cond
+        };
+        joins.push(Join { table_name, alias, kind });
+    }
+
+    let condition = match iter.peek() {
+        Some(Token::Where) => {
+            iter.next(); // consume WHERE
+            Some(parse_condition(iter, false)?)
         }
-        Some(Token::Identifier(kw)) if kw.to_uppercase() == "WHERE" => {
-            // Backwards compatibility
-            let mut cond = String::new();
-            while let Some(tok) = iter.next() {
-                match tok {
-                    Token::Semicolon => break,
-                    Token::Identifier(s) => {
-                        if !cond.is_empty() { cond.push(' '); }
-                        cond.push_str(s);
-                    }
-                    Token::Equals => {
-                        cond.push_str(" = ");
-                    }
-                    Token::String(s) => {
-                        if !cond.is_empty() { cond.push(' '); }
-                        cond.push_str(&format!("'{}'", s));
-                    }
-                    Token::Int(i) => {
-                        if !cond.is_empty() { cond.push(' '); }
-                        cond.push_str(&i.to_string());
-                    }
-                    Token::Star => {
-                        if !cond.is_empty() { cond.push(' '); }
-                        cond.push('*');
+        _ => None,
+    };
+
+    let mut group_by = vec![];
+    if let Some(Token::Group) = iter.peek() {
+        iter.next(); // consume GROUP
+        match iter.next() {
+            Some(Token::By) => {}
+            _ => return Err(RqlError::syntax("Expected 'BY' after 'GROUP'")),
+        }
+        loop {
+            match iter.next() {
+                // GROUP BY 2: the column the second SELECT item names
+                Some(Token::Int(n)) => match &items[item_position(*n, items.len(), "GROUP BY")?] {
+                    SelectItem::Expr(Expr::Column(name), _) => group_by.push(name.clone()),
+                    item => {
+                        return Err(RqlError::syntax(format!("GROUP BY {} must refer to a column, not {}", n, item)));
                     }
-                    Token::Comma => cond.push(','),
-                    _ => {}
+                },
+                Some(tok) => match name_token(tok) {
+                    Some(name) => group_by.push(qualified_name(iter, name)?),
+                    None => return Err(RqlError::syntax(format!("Unexpected token in GROUP BY: {:?}", tok))),
+                },
+                None => return Err(RqlError::syntax("Expected column name in GROUP BY")),
+            }
+            match iter.peek() {
+                Some(Token::Comma) => {
+                    iter.next();
                 }
+                _ => break,
             }
-            cond
         }
-        _ => return Err("Expected 'WHERE' after table name in DELETE".into()),
+    }
+
+    let having = match iter.peek() {
+        Some(Token::Having) => {
+            iter.next(); // consume HAVING
+            Some(parse_condition(iter, true)?)
+        }
+        _ => None,
+    };
+
+    let order_by = match iter.peek() {
+        Some(Token::Order) => {
+            iter.next(); // consume ORDER
+            parse_order_by(iter, &mut items)?
+        }
+        _ => vec![],
     };
 
-    Ok(Statement::Delete(DeleteStatement { table_name, condition }))
+    let limit = parse_limit(iter)?;
+    let offset = if is_keyword(iter.peek(), "OFFSET") {
+        iter.next(); // consume OFFSET
+        match iter.next() {
+            Some(Token::Int(n)) if *n >= 0 => *n as usize,
+            _ => return Err(RqlError::syntax("Expected a non-negative row count after OFFSET")),
+        }
+    } else {
+        0
+    };
+
+    Ok(SelectStatement { table_name, table_alias, joins, items, condition, group_by, having, order_by, limit, offset })
 }
 
+// The index of the SELECT item a GROUP BY or ORDER BY position such as `2` stands for
+fn item_position(n: i32, count: usize, clause: &str) -> Result<usize, RqlError> {
+    match usize::try_from(n) {
+        Ok(position) if (1..=count).contains(&position) => Ok(position - 1),
+        _ => Err(RqlError::syntax(format!(
+            "{} position {} is out of range; the SELECT list has {} item{}",
+            clause,
+            n,
+            count,
+            if count == 1 { "" } else { "s" }
+        ))),
+    }
+}
 
-pub fn parse_insert(tokens: &[Token]) -> Result<Statement, String> {
-    let mut iter = tokens.iter().peekable();
+// Parses an expression over + - * / and || with the usual precedence
+fn parse_expr<'a, I>(iter: &mut std::iter::Peekable<I>) -> Result<Expr, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    parse_binary(iter, 1)
+}
 
-    match iter.next() {
-        Some(Token::Insert) => {}
-        _ => return Err("Expected 'INSERT'".into()),
+// Precedence climbing: parse operators binding at least as tightly as min_prec
+fn parse_binary<'a, I>(iter: &mut std::iter::Peekable<I>, min_prec: u8) -> Result<Expr, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    let left = parse_primary(iter)?;
+    parse_binary_rest(iter, left, min_prec)
+}
+
+// Continues a binary expression whose first operand has already been parsed
+fn parse_binary_rest<'a, I>(iter: &mut std::iter::Peekable<I>, mut left: Expr, min_prec: u8) -> Result<Expr, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    loop {
+        let op = match iter.peek() {
+            Some(Token::Plus) => BinaryOp::Add,
+            Some(Token::Minus) => BinaryOp::Sub,
+            Some(Token::Star) => BinaryOp::Mul,
+            Some(Token::Slash) => BinaryOp::Div,
+            Some(Token::Concat) => BinaryOp::Concat,
+            _ => return Ok(left),
+        };
+        if op.precedence() < min_prec {
+            return Ok(left);
+        }
+        iter.next(); // consume operator
+        let right = parse_binary(iter, op.precedence() + 1)?;
+        left = Expr::Binary { op, left: Box::new(left), right: Box::new(right) };
     }
+}
 
-    match iter.next() {
-        Some(Token::Into) => {}
-        _ => return Err("Expected 'INTO' after 'INSERT'".into()),
+// Parses a parenthesised expression, a negative number, or a plain operand
+fn parse_primary<'a, I>(iter: &mut std::iter::Peekable<I>) -> Result<Expr, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    match iter.peek() {
+        Some(Token::LeftParen) => {
+            iter.next(); // consume (
+            let expr = parse_expr(iter)?;
+            match iter.next() {
+                Some(Token::RightParen) => Ok(expr),
+                _ => Err(RqlError::syntax("Expected ')' to close expression")),
+            }
+        }
+        Some(Token::Minus) => {
+            iter.next(); // consume -
+            match iter.next() {
+                Some(Token::Int(i)) => Ok(Expr::Literal(Value::Int(negate(*i)?))),
+                Some(Token::Float(f)) => Ok(Expr::Literal(Value::Float(-*f))),
+                _ => Err(RqlError::syntax("Expected a number after '-'")),
+            }
+        }
+        _ => parse_operand(iter),
     }
+}
 
-    let table_name = match iter.next() {
+// Parses a column reference, a literal, or an aggregate call like COUNT(*) / SUM(col)
+fn parse_operand<'a, I>(iter: &mut std::iter::Peekable<I>) -> Result<Expr, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    let name = match iter.next() {
         Some(Token::Identifier(name)) => name.clone(),
-        _ => return Err("Expected table name after 'INTO'".into()),
+        Some(Token::Int(i)) => return Ok(Expr::Literal(Value::Int(*i))),
+        Some(Token::Float(f)) => return Ok(Expr::Literal(Value::Float(*f))),
+        Some(Token::String(s)) => return Ok(Expr::Literal(Value::Str(s.clone()))),
+        Some(tok) => {
+            return Err(match name_token(tok) {
+                Some(keyword) => RqlError::syntax(format!(
+                    "'{}' is a keyword; write \"{}\" to use it as a column name",
+                    keyword, keyword
+                )),
+                None => RqlError::syntax(format!("Unexpected token in expression: {:?}", tok)),
+            })
+        }
+        None => return Err(RqlError::syntax("Unexpected end of input in expression")),
     };
+    parse_named_operand(iter, name)
+}
 
-    match iter.next() {
-        Some(Token::Values) => {}
-        _ => return Err("Expected 'VALUES' keyword".into()),
+// The rest of an operand that starts with `name`: a DATE literal, NULL, CASE, a function call or a
+// (possibly qualified) column
+fn parse_named_operand<'a, I>(iter: &mut std::iter::Peekable<I>, name: String) -> Result<Expr, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    if name.eq_ignore_ascii_case("DATE") && matches!(iter.peek(), Some(Token::String(_))) {
+        return Ok(Expr::Literal(parse_date_literal(iter)?));
     }
 
-    match iter.next() {
-        Some(Token::LeftParen) => {}
-        _ => return Err("Expected '(' after 'VALUES'".into()),
+    if name.eq_ignore_ascii_case("NULL") {
+        return Ok(Expr::Literal(Value::Null));
     }
 
-    let mut values = vec![];
+    if name.eq_ignore_ascii_case("CASE") && is_keyword(iter.peek(), "WHEN") {
+        return parse_case(iter);
+    }
+
+    if let Some(Token::LeftParen) = iter.peek()
+        && let Some(func) = scalar_function(&name)
+    {
+        iter.next(); // consume (
+        return Ok(Expr::Function { func, args: parse_call_args(iter, func)? });
+    }
+
+    if let Some(Token::LeftParen) = iter.peek() {
+        let func = match name.to_uppercase().as_str() {
+            "COUNT" => AggregateFunc::Count,
+            "SUM" => AggregateFunc::Sum,
+            "MIN" => AggregateFunc::Min,
+            "MAX" => AggregateFunc::Max,
+            "GROUP_CONCAT" => AggregateFunc::GroupConcat,
+            "AVG" => AggregateFunc::Avg,
+            _ => return Err(RqlError::syntax(format!("Unknown function: {}", name))),
+        };
+        iter.next(); // consume (
+        let distinct = matches!(iter.peek(), Some(Token::Distinct));
+        if distinct {
+            iter.next(); // consume DISTINCT
+        }
+        let arg = match iter.peek() {
+            Some(Token::Star) if func == AggregateFunc::Count && !distinct => {
+                iter.next();
+                None
+            }
+            Some(_) => Some(Box::new(parse_expr(iter)?)),
+            None => return Err(RqlError::syntax(format!("Unexpected end of input in {}()", func))),
+        };
+        if let Some(arg) = &arg
+            && arg.contains_aggregate()
+        {
+            return Err(RqlError::syntax(format!("Aggregate calls cannot be nested: {}({})", func, arg)));
+        }
+        let separator = match iter.peek() {
+            Some(Token::Comma) if func == AggregateFunc::GroupConcat => {
+                iter.next(); // consume ,
+                match iter.next() {
+                    Some(Token::String(s)) => Some(s.clone()),
+                    _ => return Err(RqlError::syntax("GROUP_CONCAT's separator must be a quoted string")),
+                }
+            }
+            _ => None,
+        };
+        match iter.next() {
+            Some(Token::RightParen) => {}
+            _ => return Err(RqlError::syntax(format!("Expected ')' to close {}(", func))),
+        }
+        return Ok(Expr::Aggregate { func, arg, distinct, separator });
+    }
+
+    Ok(Expr::Column(qualified_name(iter, name)?))
+}
 
+fn scalar_function(name: &str) -> Option<ScalarFunc> {
+    match name.to_uppercase().as_str() {
+        "CONCAT" => Some(ScalarFunc::Concat),
+        "COALESCE" => Some(ScalarFunc::Coalesce),
+        "NULLIF" => Some(ScalarFunc::NullIf),
+        "LENGTH" => Some(ScalarFunc::Length),
+        "UPPER" => Some(ScalarFunc::Upper),
+        "LOWER" => Some(ScalarFunc::Lower),
+        "TRIM" => Some(ScalarFunc::Trim),
+        "SUBSTR" | "SUBSTRING" => Some(ScalarFunc::Substr),
+        "ABS" => Some(ScalarFunc::Abs),
+        "ROUND" => Some(ScalarFunc::Round),
+        "MOD" => Some(ScalarFunc::Mod),
+        _ => None,
+    }
+}
+
+// Parses the comma-separated arguments of a scalar function call up to the closing ), checking
+// there are as many as the function takes
+fn parse_call_args<'a, I>(iter: &mut std::iter::Peekable<I>, func: ScalarFunc) -> Result<Vec<Expr>, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    let mut args = vec![parse_expr(iter)?];
     loop {
         match iter.next() {
-            Some(Token::Int(i)) => values.push(Value::Int(*i)),
-            Some(Token::String(s)) => values.push(Value::Str(s.clone())),
-            Some(Token::Comma) => continue,
+            Some(Token::Comma) => args.push(parse_expr(iter)?),
             Some(Token::RightParen) => break,
-            Some(tok) => return Err(format!("Unexpected token in VALUES: {:?}", tok)),
-            None => return Err("Unexpected end of input in VALUES".into()),
+            _ => return Err(RqlError::syntax(format!("Expected ',' or ')' in {}(", func))),
+        }
+    }
+    let (min, max) = func.arity();
+    if args.len() < min || max.is_some_and(|max| args.len() > max) {
+        let expected = match max {
+            Some(max) if max == min => min.to_string(),
+            Some(max) => format!("{} to {}", min, max),
+            None => format!("at least {}", min),
+        };
+        let noun = if max == Some(1) { "argument" } else { "arguments" };
+        return Err(RqlError::syntax(format!("{} takes {} {}, not {}", func, expected, noun, args.len())));
+    }
+    Ok(args)
+}
+
+// An optional alias after a table name in FROM or JOIN: `employees e` or `employees AS e`
+fn parse_table_alias<'a, I>(iter: &mut std::iter::Peekable<I>) -> Result<Option<String>, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    if is_keyword(iter.peek(), "AS") {
+        iter.next(); // consume AS
+        return match iter.next() {
+            Some(Token::Identifier(alias)) => Ok(Some(alias.clone())),
+            _ => Err(RqlError::syntax("Expected an alias after AS")),
+        };
+    }
+    match iter.peek() {
+        Some(Token::Identifier(word))
+            if !["CROSS", "INNER", "JOIN", "ON", "UNION", "LEFT", "RIGHT", "FULL", "OUTER", "LIMIT", "OFFSET"]
+                .iter()
+                .any(|kw| word.eq_ignore_ascii_case(kw)) =>
+        {
+            let alias = word.clone();
+            iter.next();
+            Ok(Some(alias))
+        }
+        _ => Ok(None),
+    }
+}
+
+// Parses the rest of CASE WHEN cond THEN result [WHEN ...] [ELSE result] END
+fn parse_case<'a, I>(iter: &mut std::iter::Peekable<I>) -> Result<Expr, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    let mut branches = vec![];
+    while is_keyword(iter.peek(), "WHEN") {
+        iter.next(); // consume WHEN
+        // Aggregates are allowed here; the enclosing clause decides whether it accepts them
+        let cond = parse_condition(iter, true)?;
+        if !is_keyword(iter.next().as_ref(), "THEN") {
+            return Err(RqlError::syntax(format!("Expected THEN after 'WHEN {}'", cond)));
         }
+        branches.push((cond, parse_expr(iter)?));
     }
+    let otherwise = if is_keyword(iter.peek(), "ELSE") {
+        iter.next(); // consume ELSE
+        Some(Box::new(parse_expr(iter)?))
+    } else {
+        None
+    };
+    if !is_keyword(iter.next().as_ref(), "END") {
+        return Err(RqlError::syntax("Expected END to close CASE"));
+    }
+    Ok(Expr::Case { branches, otherwise })
+}
 
-    if let Some(Token::Semicolon) = iter.peek() {
-        iter.next(); // consume semicolon
+// Completes `table.column` when a name is followed by a dot; otherwise returns the name as is
+fn qualified_name<'a, I>(iter: &mut std::iter::Peekable<I>, name: String) -> Result<String, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    if !matches!(iter.peek(), Some(Token::Dot)) {
+        return Ok(name);
     }
-     Ok(Statement::Insert(InsertStatement { table_name, values }))
-    
+    iter.next(); // consume .
+    column_after_dot(iter, name)
 }
-pub fn parse_select(tokens: &[Token]) -> Result<Statement, String> {
-    let mut iter = tokens.iter().peekable();
 
+// `table.column` once the dot after `table` has been read
+fn column_after_dot<'a, I>(iter: &mut I, name: String) -> Result<String, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    match iter.next().and_then(name_token) {
+        Some(column) => Ok(format!("{}.{}", name, column)),
+        None => Err(RqlError::syntax(format!("Expected a column name after '{}.'", name))),
+    }
+}
+
+// Parses the quoted part of a DATE 'YYYY-MM-DD' literal
+fn parse_date_literal<'a, I>(iter: &mut I) -> Result<Value, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
     match iter.next() {
-        Some(Token::Select) => {}
-        _ => return Err("Expected 'SELECT'".into()),
+        Some(Token::String(text)) => parse_date(text),
+        _ => Err(RqlError::syntax("Expected a quoted date after DATE, e.g. DATE '2024-01-31'")),
     }
+}
 
-    let mut values = vec![];
+// Parses: BY col1 [ASC|DESC] [NULLS FIRST|LAST], col2 [ASC|DESC] [NULLS FIRST|LAST], ...
+// A key may also be the 1-based position of a SELECT item (`ORDER BY 2`), which sorts by that item:
+// by its alias, its column, or, for an unnamed expression, by the expression itself, which is then
+// given its own text as alias so the sort can find it (its header stays the same)
+fn parse_order_by<'a, I>(iter: &mut std::iter::Peekable<I>, items: &mut [SelectItem]) -> Result<Vec<OrderKey>, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    match iter.next() {
+        Some(Token::By) => {}
+        _ => return Err(RqlError::syntax("Expected 'BY' after 'ORDER'")),
+    }
 
+    let mut keys = vec![];
     loop {
-        match iter.next() {
-            Some(Token::Star) => values.push(Value::Star),
-            Some(Token::Identifier(name)) => values.push(Value::Identifier(name.clone())),
-            Some(Token::Comma) => continue,
-            Some(Token::From) => break,
-            Some(tok) => return Err(format!("Unexpected token in SELECT: {:?}", tok)),
-            None => return Err("Unexpected end of input in SELECT".into()),
+        let column = match iter.next() {
+            Some(Token::Int(n)) => {
+                let count = items.len();
+                match &mut items[item_position(*n, count, "ORDER BY")?] {
+                    SelectItem::Expr(_, Some(alias)) => alias.clone(),
+                    SelectItem::Expr(Expr::Column(name), None) => name.clone(),
+                    SelectItem::Expr(expr, alias @ None) => alias.insert(expr.to_string()).clone(),
+                    item @ (SelectItem::Star | SelectItem::StarExcept(_) | SelectItem::TableStar(_)) => {
+                        let msg = format!("ORDER BY {} refers to {}; name the column instead", n, item);
+                        return Err(RqlError::syntax(msg));
+                    }
+                }
+            }
+            Some(tok) => match name_token(tok) {
+                Some(name) => qualified_name(iter, name)?,
+                None => return Err(RqlError::syntax(format!("Unexpected token in ORDER BY: {:?}", tok))),
+            },
+            None => return Err(RqlError::syntax("Expected column name in ORDER BY")),
+        };
+        let descending = match iter.peek() {
+            Some(Token::Asc) => {
+                iter.next();
+                false
+            }
+            Some(Token::Desc) => {
+                iter.next();
+                true
+            }
+            _ => false,
+        };
+        let nulls_first = if is_keyword(iter.peek(), "NULLS") {
+            iter.next();
+            let next = iter.next();
+            if is_keyword(next.as_ref(), "FIRST") {
+                Some(true)
+            } else if is_keyword(next.as_ref(), "LAST") {
+                Some(false)
+            } else {
+                return Err(RqlError::syntax("Expected FIRST or LAST after NULLS"));
+            }
+        } else {
+            None
+        };
+        keys.push(OrderKey { column, descending, nulls_first });
+
+        match iter.peek() {
+            Some(Token::Comma) => {
+                iter.next();
+            }
+            _ => return Ok(keys),
         }
     }
+}
+
+// Parses a WHERE/HAVING condition: predicates combined with AND and OR (AND binds tighter),
+// grouped with parentheses. Each predicate is
+//   expression <op> expression, where <op> is one of = != <> < <= > >=, or
+//   expression IN (literal, ...) / expression IN (SELECT ...).
+// HAVING conditions may use an aggregate on the left, e.g. COUNT(*) > 1.
+fn parse_condition<'a, I>(iter: &mut std::iter::Peekable<I>, allow_aggregates: bool) -> Result<Condition, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    match parse_or(iter, allow_aggregates)? {
+        ConditionPart::Condition(cond) => Ok(cond),
+        ConditionPart::Expr(expr) => Err(RqlError::syntax(format!("Expected comparison operator after '{}'", expr))),
+    }
+}
 
-    let table_name = match iter.next() {
-        Some(Token::Identifier(name)) => name.clone(),
-        _ => return Err("Expected table name after 'FROM'".into()),
-    };
+// A parenthesised group in a condition can hold a whole condition, `(a = 1 OR b = 2)`, or just
+// an arithmetic operand, `(price + 1) > 5`; which one is only known once it has been parsed.
+enum ConditionPart {
+    Condition(Condition),
+    Expr(Expr),
+}
 
-    if let Some(Token::Semicolon) = iter.peek() {
-        iter.next(); // consume semicolon
+impl ConditionPart {
+    fn into_condition(self) -> Result<Condition, RqlError> {
+        match self {
+            ConditionPart::Condition(cond) => Ok(cond),
+            ConditionPart::Expr(expr) => {
+                Err(RqlError::syntax(format!("Expected comparison operator after '{}'", expr)))
+            }
+        }
     }
+}
 
-    Ok(Statement::Select(SelectStatement { table_name, values }))
+// An aggregate in a condition other than HAVING: rows are filtered before any group exists
+fn aggregate_in_where(expr: &Expr) -> RqlError {
+    RqlError::syntax(format!(
+        "Aggregates can't be used in WHERE: {}; filter groups with HAVING instead, after GROUP BY",
+        expr
+    ))
 }
 
-// Parses: UPDATE table_name SET col0 = value WHERE condition;
-pub fn parse_update(tokens: &[Token]) -> Result<Statement, String> {
-    let mut iter = tokens.iter().peekable();
+fn is_keyword(tok: Option<&&Token>, keyword: &str) -> bool {
+    matches!(tok, Some(Token::Identifier(kw)) if kw.eq_ignore_ascii_case(keyword))
+}
 
+// Parses a condition on its own, such as the stored text of a CHECK constraint
+pub fn parse_condition_text(sql: &str) -> Result<Condition, RqlError> {
+    let tokens = tokenize(sql)?;
+    let mut iter = tokens.iter().peekable();
+    let condition = parse_condition(&mut iter, false)?;
     match iter.next() {
-        Some(Token::Update) => {}
-        _ => return Err("Expected 'UPDATE'".into()),
+        None => Ok(condition),
+        Some(tok) => Err(RqlError::syntax(format!("Unexpected token after condition: {:?}", tok))),
     }
+}
 
-    let table_name = match iter.next() {
-        Some(Token::Identifier(name)) => name.clone(),
-        _ => return Err("Expected table name after 'UPDATE'".into()),
+// condition OR condition ...
+fn parse_or<'a, I>(iter: &mut std::iter::Peekable<I>, allow_aggregates: bool) -> Result<ConditionPart, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    let first = parse_and(iter, allow_aggregates)?;
+    if !is_keyword(iter.peek(), "OR") {
+        return Ok(first);
+    }
+    let mut cond = first.into_condition()?;
+    while is_keyword(iter.peek(), "OR") {
+        iter.next(); // consume OR
+        let right = parse_and(iter, allow_aggregates)?.into_condition()?;
+        cond = Condition::Or(Box::new(cond), Box::new(right));
+    }
+    Ok(ConditionPart::Condition(cond))
+}
+
+// predicate AND predicate ...
+fn parse_and<'a, I>(iter: &mut std::iter::Peekable<I>, allow_aggregates: bool) -> Result<ConditionPart, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    let first = parse_predicate(iter, allow_aggregates)?;
+    if !is_keyword(iter.peek(), "AND") {
+        return Ok(first);
+    }
+    let mut cond = first.into_condition()?;
+    while is_keyword(iter.peek(), "AND") {
+        iter.next(); // consume AND
+        let right = parse_predicate(iter, allow_aggregates)?.into_condition()?;
+        cond = Condition::And(Box::new(cond), Box::new(right));
+    }
+    Ok(ConditionPart::Condition(cond))
+}
+
+// A single comparison, IN, BETWEEN, LIKE or IS DISTINCT FROM test, a parenthesised condition, or
+// (inside parentheses) a bare operand
+fn parse_predicate<'a, I>(iter: &mut std::iter::Peekable<I>, allow_aggregates: bool) -> Result<ConditionPart, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    let left = if let Some(Token::LeftParen) = iter.peek() {
+        iter.next(); // consume (
+        let inner = parse_or(iter, allow_aggregates)?;
+        match iter.next() {
+            Some(Token::RightParen) => {}
+            Some(tok) => return Err(RqlError::syntax(format!("Expected ')' in condition, found {:?}", tok))),
+            None => return Err(RqlError::syntax("Expected ')' in condition")),
+        }
+        match inner {
+            ConditionPart::Condition(cond) => return Ok(ConditionPart::Condition(cond)),
+            // A parenthesised operand may be followed by more arithmetic: (a + 1) * 2 > 5
+            ConditionPart::Expr(expr) => parse_binary_rest(iter, expr, 1)?,
+        }
+    } else {
+        parse_expr(iter)?
+    };
+    if left.contains_aggregate() && !allow_aggregates {
+        return Err(aggregate_in_where(&left));
+    }
+
+    // NOT may only introduce IN, BETWEEN, LIKE or ILIKE here
+    let negated = is_keyword(iter.peek(), "NOT");
+    if negated {
+        iter.next(); // consume NOT
+        if !["IN", "BETWEEN", "LIKE", "ILIKE"].iter().any(|kw| is_keyword(iter.peek(), kw)) {
+            return Err(RqlError::syntax("Expected IN, BETWEEN, LIKE or ILIKE after NOT"));
+        }
+    }
+
+    if is_keyword(iter.peek(), "IN") {
+        iter.next(); // consume IN
+        return Ok(ConditionPart::Condition(parse_in(iter, left, negated)?));
+    }
+
+    if is_keyword(iter.peek(), "BETWEEN") {
+        iter.next(); // consume BETWEEN
+        return Ok(ConditionPart::Condition(parse_between(iter, left, negated, allow_aggregates)?));
+    }
+
+    if is_keyword(iter.peek(), "LIKE") || is_keyword(iter.peek(), "ILIKE") {
+        let ignore_case = is_keyword(iter.next().as_ref(), "ILIKE");
+        return Ok(ConditionPart::Condition(parse_like(iter, left, ignore_case, negated)?));
+    }
+
+    if is_keyword(iter.peek(), "IS") {
+        iter.next(); // consume IS
+        let negated = is_keyword(iter.peek(), "NOT");
+        if negated {
+            iter.next(); // consume NOT
+        }
+        if !matches!(iter.next(), Some(Token::Distinct)) {
+            return Err(RqlError::syntax("Expected DISTINCT FROM after IS"));
+        }
+        if !matches!(iter.next(), Some(Token::From)) {
+            return Err(RqlError::syntax("Expected FROM after IS DISTINCT"));
+        }
+        // NULL is only a value here, where comparing with it is meaningful
+        let right = if is_keyword(iter.peek(), "NULL") {
+            iter.next(); // consume NULL
+            Expr::Literal(Value::Null)
+        } else {
+            parse_expr(iter)?
+        };
+        if right.contains_aggregate() && !allow_aggregates {
+            return Err(aggregate_in_where(&right));
+        }
+        return Ok(ConditionPart::Condition(Condition::Distinct { left, right, negated }));
+    }
+
+    let op = match iter.peek() {
+        Some(Token::Equals) => CompareOp::Eq,
+        Some(Token::NotEquals) => CompareOp::NotEq,
+        Some(Token::Less) => CompareOp::Lt,
+        Some(Token::LessEquals) => CompareOp::LtEq,
+        Some(Token::Greater) => CompareOp::Gt,
+        Some(Token::GreaterEquals) => CompareOp::GtEq,
+        // No operator: only valid as an operand inside parentheses, which the caller checks
+        _ => return Ok(ConditionPart::Expr(left)),
     };
+    iter.next(); // consume operator
+
+    let right = parse_expr(iter)?;
+    if right.contains_aggregate() && !allow_aggregates {
+        return Err(aggregate_in_where(&right));
+    }
+    Ok(ConditionPart::Condition(Condition::Comparison { left, op, right }))
+}
+
+// An integer literal after `-`; only -(-2147483648) is out of range
+fn negate(i: i32) -> Result<i32, RqlError> {
+    i.checked_neg().ok_or_else(|| RqlError::syntax(format!("Integer literal out of range: -({})", i)))
+}
 
+// The literal on the right of a condition: a number (possibly negative), a string or a DATE
+fn parse_condition_literal<'a, I>(iter: &mut std::iter::Peekable<I>) -> Result<Value, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
     match iter.next() {
-        Some(Token::Set) => {}
-        _ => return Err("Expected 'SET' after table name".into()),
+        Some(Token::Int(i)) => Ok(Value::Int(*i)),
+        Some(Token::Float(f)) => Ok(Value::Float(*f)),
+        Some(Token::Minus) => match iter.next() {
+            Some(Token::Int(i)) => Ok(Value::Int(negate(*i)?)),
+            Some(Token::Float(f)) => Ok(Value::Float(-*f)),
+            _ => Err(RqlError::syntax("Expected a number after '-' in condition")),
+        },
+        Some(Token::String(s)) => Ok(Value::Str(s.clone())),
+        Some(Token::Identifier(kw)) if kw.eq_ignore_ascii_case("DATE") => parse_date_literal(iter),
+        Some(tok) => Err(RqlError::syntax(format!("Unexpected token in condition: {:?}", tok))),
+        None => Err(RqlError::syntax("Unexpected end of input in condition")),
+    }
+}
+
+// Parses the part after [NOT] IN: `(SELECT ...)` or a parenthesised list of literals
+fn parse_in<'a, I>(iter: &mut std::iter::Peekable<I>, left: Expr, negated: bool) -> Result<Condition, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    match iter.next() {
+        Some(Token::LeftParen) => {}
+        _ => return Err(RqlError::syntax("Expected '(' after IN")),
+    }
+
+    if let Some(Token::Select) = iter.peek() {
+        let subquery = parse_select_body(iter)?;
+        match iter.next() {
+            Some(Token::RightParen) => {}
+            Some(tok) => return Err(RqlError::syntax(format!("Unexpected token in subquery: {:?}", tok))),
+            None => return Err(RqlError::syntax("Expected ')' after subquery")),
+        }
+        return Ok(Condition::InSelect { left, subquery: Box::new(subquery), negated });
     }
 
-    // Collect SET clause until WHERE
-    let mut set_clause = String::new();
+    let mut values = vec![];
     loop {
+        values.push(parse_condition_literal(iter)?);
         match iter.next() {
-            Some(Token::Where) => break,
-            Some(Token::Identifier(kw)) if kw.to_uppercase() == "WHERE" => break,
-            Some(Token::Identifier(s)) => {
-                if !set_clause.is_empty() { set_clause.push(' '); }
-                set_clause.push_str(s);
-            }
-            Some(Token::Equals) => {
-                set_clause.push_str(" = ");
-            }
-            Some(Token::String(s)) => {
-                if !set_clause.is_empty() { set_clause.push(' '); }
-                set_clause.push_str(&format!("'{}'", s));
-            }
-            Some(Token::Int(i)) => {
-                if !set_clause.is_empty() { set_clause.push(' '); }
-                set_clause.push_str(&i.to_string());
+            Some(Token::Comma) => continue,
+            Some(Token::RightParen) => break,
+            Some(tok) => return Err(RqlError::syntax(format!("Unexpected token in IN list: {:?}", tok))),
+            None => return Err(RqlError::syntax("Expected ')' after IN list")),
+        }
+    }
+    // INTs and FLOATs mix freely, since they compare numerically; numbers and text never match
+    let is_number = |v: &Value| matches!(v, Value::Int(_) | Value::Float(_));
+    if let (Some(number), Some(other)) =
+        (values.iter().find(|v| is_number(v)), values.iter().find(|v| !is_number(v)))
+    {
+        let (number, other) = (Expr::Literal(number.clone()), Expr::Literal(other.clone()));
+        return Err(RqlError::syntax(format!("IN list mixes numbers and other values: {} and {}", number, other)));
+    }
+    Ok(Condition::InList { left, values, negated })
+}
+
+// Parses the part after [NOT] BETWEEN: `low AND high`, both inclusive. It becomes
+// `left >= low AND left <= high`, or `left < low OR left > high` when negated, so the bounds are
+// compared like any other operands (an INT with a FLOAT numerically).
+fn parse_between<'a, I>(
+    iter: &mut std::iter::Peekable<I>,
+    left: Expr,
+    negated: bool,
+    allow_aggregates: bool,
+) -> Result<Condition, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    let low = parse_expr(iter)?;
+    if !is_keyword(iter.peek(), "AND") {
+        return Err(RqlError::syntax("Expected AND between the bounds of BETWEEN"));
+    }
+    iter.next(); // consume AND
+    let high = parse_expr(iter)?;
+    for bound in [&low, &high] {
+        if bound.contains_aggregate() && !allow_aggregates {
+            return Err(aggregate_in_where(bound));
+        }
+    }
+    let compare = |op, right| Box::new(Condition::Comparison { left: left.clone(), op, right });
+    Ok(if negated {
+        Condition::Or(compare(CompareOp::Lt, low), compare(CompareOp::Gt, high))
+    } else {
+        Condition::And(compare(CompareOp::GtEq, low), compare(CompareOp::LtEq, high))
+    })
+}
+
+// Parses the part after [NOT] LIKE or ILIKE: 'pattern' [ESCAPE 'c']
+fn parse_like<'a, I>(
+    iter: &mut std::iter::Peekable<I>,
+    left: Expr,
+    ignore_case: bool,
+    negated: bool,
+) -> Result<Condition, RqlError>
+where
+    I: Iterator<Item = &'a Token>,
+{
+    let keyword = if ignore_case { "ILIKE" } else { "LIKE" };
+    let pattern = match iter.next() {
+        Some(Token::String(s)) => s.clone(),
+        _ => return Err(RqlError::syntax(format!("Expected a quoted pattern after {}", keyword))),
+    };
+
+    let escape = if is_keyword(iter.peek(), "ESCAPE") {
+        iter.next(); // consume ESCAPE
+        let mut chars = match iter.next() {
+            Some(Token::String(s)) => s.chars(),
+            _ => return Err(RqlError::syntax("Expected a quoted character after ESCAPE")),
+        };
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => return Err(RqlError::syntax("ESCAPE expects exactly one character")),
+        }
+    } else {
+        None
+    };
+
+    // The escape character may only precede %, _ or itself
+    if let Some(esc) = escape {
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c == esc && !matches!(chars.next(), Some(next) if next == '%' || next == '_' || next == esc) {
+                return Err(RqlError::syntax(format!(
+                    "In {} pattern '{}', the escape character '{}' must be followed by %, _ or itself",
+                    keyword, pattern, esc
+                )));
             }
-            Some(Token::Comma) => set_clause.push(','),
-            Some(Token::Semicolon) => return Ok(Statement::Update(UpdateStatement { 
-                table_name, 
-                set_clause, 
-                condition: String::new() 
-            })),
-            Some(tok) => return Err(format!("Unexpected token in SET clause: {:?}", tok)),
-            None => return Err("Unexpected end of input in SET clause".into()),
         }
     }
 
-    // Collect WHERE condition until semicolon
-    let mut condition = String::new();
-    while let Some(tok) = iter.next() {
-        match tok {
-            Token::Semicolon => break,
-            Token::Identifier(s) => {
-                if !condition.is_empty() { condition.push(' '); }
-                condition.push_str(s);
+    Ok(Condition::Like { left, pattern, escape, ignore_case, negated })
+}
+
+// Parses: UPDATE table_name SET col0 = value, col1 = value WHERE condition;
+pub fn parse_update(tokens: &[Token]) -> Result<Statement, RqlError> {
+    located(tokens, |mut iter| {
+        match iter.next() {
+            Some(Token::Update) => {}
+            _ => return Err(RqlError::syntax("Expected 'UPDATE'")),
+        }
+
+        let table_name = match iter.next().and_then(name_token) {
+            Some(name) => name,
+            _ => return Err(RqlError::syntax("Expected table name after 'UPDATE'")),
+        };
+
+        match iter.next() {
+            Some(Token::Set) => {}
+            _ => return Err(RqlError::syntax("Expected 'SET' after table name")),
+        }
+
+        // Parse comma-separated assignments until WHERE (or end of statement)
+        let mut assignments = vec![];
+        loop {
+            let n = assignments.len() + 1;
+            let column = match iter.next() {
+                Some(tok) => match name_token(tok) {
+                    Some(name) => name,
+                    None => return Err(RqlError::syntax(format!("Malformed assignment #{} in SET clause: expected column name, found {:?}", n, tok))),
+                },
+                None => return Err(RqlError::syntax(format!("Malformed assignment #{} in SET clause: unexpected end of input", n))),
+            };
+            match iter.next() {
+                Some(Token::Equals) => {}
+                _ => return Err(RqlError::syntax(format!("Malformed assignment #{} in SET clause: expected '=' after '{}'", n, column))),
             }
-            Token::Equals => {
-                condition.push_str(" = ");
+            // The new value may be computed from the row's current values, e.g. `stock - 1`
+            let value = match iter.peek() {
+                Some(Token::Comma | Token::Where | Token::Semicolon) | None => {
+                    return Err(RqlError::syntax(format!("Malformed assignment #{} in SET clause: expected a value for '{}'", n, column)))
+                }
+                Some(_) => parse_expr(&mut iter)?,
+            };
+            if value.contains_aggregate() {
+                return Err(RqlError::syntax(format!("Aggregate {} is not allowed in SET clause", value)));
             }
-            Token::String(s) => {
-                if !condition.is_empty() { condition.push(' '); }
-                condition.push_str(&format!("'{}'", s));
+            assignments.push((column, value));
+
+            match iter.peek() {
+                Some(Token::Comma) => {
+                    iter.next();
+                }
+                _ => break,
             }
-            Token::Int(i) => {
-                if !condition.is_empty() { condition.push(' '); }
-                condition.push_str(&i.to_string());
+        }
+
+        // As in DELETE, a missing WHERE is left for the executor to refuse
+        let condition = match iter.peek() {
+            Some(Token::Where) => {
+                iter.next(); // consume WHERE
+                Some(parse_condition(&mut iter, false)?)
             }
-            Token::Comma => condition.push(','),
-            _ => {}
+            Some(Token::Semicolon) | None => None,
+            Some(Token::Identifier(kw)) if kw.eq_ignore_ascii_case("LIMIT") => None,
+            Some(tok) => return Err(RqlError::syntax(format!("Unexpected token in SET clause: {:?}", tok))),
+        };
+        let limit = parse_limit(&mut iter)?;
+        match iter.next() {
+            Some(Token::Semicolon) | None => {}
+            Some(tok) => return Err(RqlError::syntax(format!("Unexpected token after UPDATE condition: {:?}", tok))),
         }
-    }
 
-    Ok(Statement::Update(UpdateStatement { table_name, set_clause, condition }))
+        Ok(Statement::Update(UpdateStatement { table_name, set_clause: assignments, condition, limit }))
+    })
 }
\ No newline at end of file
@@ -0,0 +1,225 @@
+use crate::executor::QueryResult;
+use crate::parser::Value;
+use unicode_width::UnicodeWidthStr;
+
+// Display settings for result tables
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    // Shown for NULL (and missing) cells, so they can be told apart from an empty string
+    pub null_text: String,
+    pub border_style: BorderStyle,
+    // Decimal places FLOATs are rounded to; trailing zeros are then dropped, so 0.1 + 0.2 shows as 0.3
+    pub float_precision: usize,
+    // Show FLOATs as 1.5e20 instead of writing out every digit
+    pub scientific: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            null_text: String::from("NULL"),
+            border_style: BorderStyle::Ascii,
+            float_precision: 6,
+            scientific: false,
+        }
+    }
+}
+
+// How table borders are drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BorderStyle {
+    Ascii,    // +---+ rules around the header and the body
+    Markdown, // | a | b | with a |---| separator row, ready to paste into docs
+    Compact,  // no borders, columns separated by two spaces
+}
+
+impl std::str::FromStr for BorderStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ascii" => Ok(BorderStyle::Ascii),
+            "markdown" => Ok(BorderStyle::Markdown),
+            "compact" => Ok(BorderStyle::Compact),
+            other => Err(format!("unknown style '{}' (expected ascii, markdown or compact)", other)),
+        }
+    }
+}
+
+// Render a single cell for display
+pub fn format_value(value: &Value, options: &RenderOptions) -> String {
+    match value {
+        Value::Int(i) => i.to_string(),
+        Value::Float(f) => format_float(*f, options),
+        Value::Str(s) => s.clone(),
+        Value::Date(d) => d.clone(),
+        _ => options.null_text.clone(),
+    }
+}
+
+// A FLOAT rounded to `float_precision` decimal places, without trailing zeros but always with a
+// decimal point, so 2.0 doesn't look like an integer. A value that rounds to zero shows as 0.0
+// (never -0.0).
+fn format_float(f: f64, options: &RenderOptions) -> String {
+    if !f.is_finite() {
+        return f.to_string();
+    }
+    let precision = options.float_precision;
+    let text = if options.scientific { format!("{:.*e}", precision, f) } else { format!("{:.*}", precision, f) };
+    let (mantissa, exponent) = match text.split_once('e') {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+        None => (text.as_str(), None),
+    };
+    let mut mantissa = mantissa.to_string();
+    if mantissa.contains('.') {
+        mantissa.truncate(mantissa.trim_end_matches('0').len());
+    }
+    if mantissa.ends_with('.') || !mantissa.contains('.') {
+        mantissa = format!("{}.0", mantissa.trim_end_matches('.'));
+    }
+    if mantissa.trim_start_matches('-').chars().all(|c| c == '0' || c == '.') {
+        mantissa = mantissa.trim_start_matches('-').to_string();
+    }
+    match exponent {
+        Some(exponent) => format!("{}e{}", mantissa, exponent),
+        None => mantissa,
+    }
+}
+
+// How the REPL displays result sets
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+}
+
+// Convert a cell to JSON: numbers as numbers, NULL as null, everything else as a string
+fn json_value(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Int(i) => serde_json::Value::from(*i),
+        Value::Float(f) => serde_json::Value::from(*f),
+        Value::Null => serde_json::Value::Null,
+        other => serde_json::Value::String(format_value(other, &RenderOptions::default())),
+    }
+}
+
+// Render a result set as a JSON array with one object per row, keyed by column name
+pub fn to_json(result: &QueryResult) -> String {
+    let rows: Vec<serde_json::Value> = result
+        .rows
+        .iter()
+        .map(|row| {
+            let object = result
+                .columns
+                .iter()
+                .zip(row)
+                .map(|(column, value)| (column.clone(), json_value(value)))
+                .collect();
+            serde_json::Value::Object(object)
+        })
+        .collect();
+    serde_json::Value::Array(rows).to_string()
+}
+
+// Pretty-print a result set as a table in the configured border style
+pub fn print_table(result: &QueryResult, options: &RenderOptions) {
+    print!("{}", render_table(result, options));
+}
+
+// Render a result set as a table, one line per row plus borders.
+// Widths are measured in terminal columns, so accented and wide (e.g. CJK) text lines up.
+pub fn render_table(result: &QueryResult, options: &RenderOptions) -> String {
+    let style = options.border_style;
+    // A pipe inside a Markdown cell would end the cell early
+    let cell_text = |text: String| match style {
+        BorderStyle::Markdown => text.replace('|', "\\|"),
+        _ => text,
+    };
+    let headers: Vec<String> = result.columns.iter().cloned().map(cell_text).collect();
+    // A row shorter than the header is padded with NULL cells
+    let rows: Vec<Vec<String>> = result
+        .rows
+        .iter()
+        .map(|row| {
+            (0..headers.len())
+                .map(|i| row.get(i).map_or_else(|| options.null_text.clone(), |v| format_value(v, options)))
+                .map(cell_text)
+                .collect()
+        })
+        .collect();
+
+    // compute column widths
+    let mut widths = headers.iter().map(|h| h.width()).collect::<Vec<usize>>();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.width());
+        }
+    }
+
+    let mut out = String::new();
+    match style {
+        BorderStyle::Ascii => {
+            let rule = horizontal_rule(&widths, '+');
+            out.push_str(&rule);
+            out.push_str(&format_row(&headers, &widths, style));
+            out.push_str(&rule);
+            for row in &rows {
+                out.push_str(&format_row(row, &widths, style));
+            }
+            out.push_str(&rule);
+        }
+        BorderStyle::Markdown => {
+            out.push_str(&format_row(&headers, &widths, style));
+            out.push_str(&horizontal_rule(&widths, '|'));
+            for row in &rows {
+                out.push_str(&format_row(row, &widths, style));
+            }
+        }
+        BorderStyle::Compact => {
+            out.push_str(&format_row(&headers, &widths, style));
+            for row in &rows {
+                out.push_str(&format_row(row, &widths, style));
+            }
+        }
+    }
+    out
+}
+
+// A `+-----+---+` (or `|-----|---|`) line spanning every column
+fn horizontal_rule(widths: &[usize], corner: char) -> String {
+    let mut rule = String::from(corner);
+    for w in widths {
+        rule.push_str(&"-".repeat(*w + 2));
+        rule.push(corner);
+    }
+    rule.push('\n');
+    rule
+}
+
+// One line of cells, each padded to its column's display width
+fn format_row(cells: &[String], widths: &[usize], style: BorderStyle) -> String {
+    let mut line = String::new();
+    for (i, (cell, width)) in cells.iter().zip(widths).enumerate() {
+        let pad = " ".repeat(width.saturating_sub(cell.width()));
+        match style {
+            BorderStyle::Compact => {
+                if i > 0 {
+                    line.push_str("  ");
+                }
+                line.push_str(cell);
+                line.push_str(&pad);
+            }
+            _ => {
+                line.push_str(if i == 0 { "| " } else { " | " });
+                line.push_str(cell);
+                line.push_str(&pad);
+            }
+        }
+    }
+    match style {
+        BorderStyle::Compact => line.truncate(line.trim_end().len()),
+        _ => line.push_str(" |"),
+    }
+    line.push('\n');
+    line
+}
@@ -0,0 +1,91 @@
+use crate::error::RqlError;
+use crate::parser::Value;
+
+// Builds a value of an embedder's type from one result row, so query results can be read as
+// structs (`QueryResult::rows_as`). Implementations usually read the row with `RowValues`:
+//
+//     impl FromRow for User {
+//         fn from_row(row: &[Value]) -> Result<Self, RqlError> {
+//             Ok(User { id: row.get_int(0)?, name: row.get_str(1)?.to_string(), age: row.get_opt_int(2)? })
+//         }
+//     }
+pub trait FromRow: Sized {
+    fn from_row(row: &[Value]) -> Result<Self, RqlError>;
+}
+
+// Typed access to the values of a row by position. The plain getters fail on NULL, which suits
+// NOT NULL columns; the `get_opt_` ones return None for it. Every getter fails if the position is
+// past the end of the row or the value has another type; `get_float` also accepts an INT.
+pub trait RowValues {
+    fn value(&self, idx: usize) -> Result<&Value, RqlError>;
+
+    fn is_null(&self, idx: usize) -> Result<bool, RqlError> {
+        Ok(*self.value(idx)? == Value::Null)
+    }
+
+    fn get_int(&self, idx: usize) -> Result<i32, RqlError> {
+        required(idx, self.get_opt_int(idx)?)
+    }
+
+    fn get_float(&self, idx: usize) -> Result<f64, RqlError> {
+        required(idx, self.get_opt_float(idx)?)
+    }
+
+    fn get_str(&self, idx: usize) -> Result<&str, RqlError> {
+        required(idx, self.get_opt_str(idx)?)
+    }
+
+    // A DATE value as YYYY-MM-DD
+    fn get_date(&self, idx: usize) -> Result<&str, RqlError> {
+        required(idx, self.get_opt_date(idx)?)
+    }
+
+    fn get_opt_int(&self, idx: usize) -> Result<Option<i32>, RqlError> {
+        match self.value(idx)? {
+            Value::Int(n) => Ok(Some(*n)),
+            Value::Null => Ok(None),
+            other => Err(wrong_type(idx, "INT", other)),
+        }
+    }
+
+    fn get_opt_float(&self, idx: usize) -> Result<Option<f64>, RqlError> {
+        match self.value(idx)? {
+            Value::Float(f) => Ok(Some(*f)),
+            Value::Int(n) => Ok(Some(f64::from(*n))),
+            Value::Null => Ok(None),
+            other => Err(wrong_type(idx, "FLOAT", other)),
+        }
+    }
+
+    fn get_opt_str(&self, idx: usize) -> Result<Option<&str>, RqlError> {
+        match self.value(idx)? {
+            Value::Str(s) => Ok(Some(s)),
+            Value::Null => Ok(None),
+            other => Err(wrong_type(idx, "TEXT", other)),
+        }
+    }
+
+    fn get_opt_date(&self, idx: usize) -> Result<Option<&str>, RqlError> {
+        match self.value(idx)? {
+            Value::Date(d) => Ok(Some(d)),
+            Value::Null => Ok(None),
+            other => Err(wrong_type(idx, "DATE", other)),
+        }
+    }
+}
+
+impl RowValues for [Value] {
+    fn value(&self, idx: usize) -> Result<&Value, RqlError> {
+        self.get(idx).ok_or_else(|| {
+            RqlError::execution(format!("Column {} is out of range; the row has {} values", idx, self.len()))
+        })
+    }
+}
+
+fn required<T>(idx: usize, value: Option<T>) -> Result<T, RqlError> {
+    value.ok_or_else(|| RqlError::TypeMismatch(format!("Column {} is NULL", idx)))
+}
+
+fn wrong_type(idx: usize, expected: &str, found: &Value) -> RqlError {
+    RqlError::TypeMismatch(format!("Column {} is not {}: found {:?}", idx, expected, found))
+}
@@ -0,0 +1,56 @@
+use std::sync::{PoisonError, RwLock};
+
+use crate::error::RqlError;
+use crate::executor::{Database, ExecResult};
+use crate::parser::{Statement, parse, tokenize};
+
+// A `Database` that can be queried from several threads at once (e.g. behind a socket).
+// Reads run concurrently under the read lock; anything that changes data takes the write lock.
+// A transaction opened through `write_query` is visible to every thread until it ends.
+pub struct SharedDatabase {
+    inner: RwLock<Database>,
+}
+
+impl SharedDatabase {
+    pub fn new(db: Database) -> Self {
+        SharedDatabase { inner: RwLock::new(db) }
+    }
+
+    // Run a read-only statement (SELECT, UNION, DESCRIBE, SHOW TABLES, EXPLAIN) under the read lock
+    pub fn read_query(&self, sql: &str) -> Result<ExecResult, RqlError> {
+        let statement = parse_one(sql)?;
+        if !statement.is_read_only() {
+            return Err(RqlError::execution("read_query only runs read-only statements; use write_query"));
+        }
+        self.inner.read().unwrap_or_else(PoisonError::into_inner).execute_read(statement)
+    }
+
+    // Run any statement under the write lock
+    pub fn write_query(&self, sql: &str) -> Result<ExecResult, RqlError> {
+        let statement = parse_one(sql)?;
+        self.inner.write().unwrap_or_else(PoisonError::into_inner).execute(statement)
+    }
+
+    // Run a statement under whichever lock it needs
+    pub fn query(&self, sql: &str) -> Result<ExecResult, RqlError> {
+        let statement = parse_one(sql)?;
+        if statement.is_read_only() {
+            self.inner.read().unwrap_or_else(PoisonError::into_inner).execute_read(statement)
+        } else {
+            self.inner.write().unwrap_or_else(PoisonError::into_inner).execute(statement)
+        }
+    }
+
+    // Write the database to `path`; readers may continue while it saves
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner).save(path)
+    }
+
+    pub fn into_inner(self) -> Database {
+        self.inner.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn parse_one(sql: &str) -> Result<Statement, RqlError> {
+    parse(&tokenize(sql)?)
+}
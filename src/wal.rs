@@ -0,0 +1,172 @@
+use std::fs::File;
+use std::io::Write;
+
+use crate::executor::{Database, QueryOptions};
+use crate::parser::{Statement, parse_program, tokenize};
+
+// Write-ahead log kept next to a database file (`database.bin.wal`). Every statement that
+// changes data is appended as it runs, so the full snapshot only has to be rewritten now and
+// then; after a crash, `replay` re-applies the statements logged since the last snapshot.
+//
+// Layout: "RQWL", the checksum of the snapshot the log follows (u64 little-endian), then one
+// entry per statement: its SQL text as UTF-8, preceded by the byte length (u32 little-endian).
+// An entry starting with `.options` instead records the session options (`.ci`, `.nulls`) in
+// effect from there on, since the same SQL can match different rows under different options.
+
+const LOG_MAGIC: [u8; 4] = *b"RQWL";
+const HEADER_LEN: usize = 12;
+const OPTIONS_ENTRY: &str = ".options";
+
+pub struct WriteAheadLog {
+    file: File,
+    entries: usize,
+}
+
+// Path of the log that belongs to the database file at `db_path`
+pub fn log_path(db_path: &str) -> String {
+    format!("{}.wal", db_path)
+}
+
+impl WriteAheadLog {
+    // Starts an empty log following the current snapshot at `db_path`, replacing any old log.
+    // Call `replay` first, or the statements in the old log are lost.
+    pub fn create(db_path: &str) -> std::io::Result<Self> {
+        let mut header = LOG_MAGIC.to_vec();
+        header.extend_from_slice(&snapshot_checksum(db_path)?.to_le_bytes());
+        let mut file = File::create(log_path(db_path))?;
+        file.write_all(&header)?;
+        file.sync_all()?;
+        Ok(WriteAheadLog { file, entries: 0 })
+    }
+
+    // Appends one statement and flushes it to disk before returning
+    pub fn append(&mut self, sql: &str) -> std::io::Result<()> {
+        self.write_entry(sql)?;
+        self.entries += 1;
+        Ok(())
+    }
+
+    // Records the options the statements logged after this one run with. It isn't counted in
+    // `entries`, as it changes no data.
+    pub fn append_options(&mut self, options: &QueryOptions) -> std::io::Result<()> {
+        let ci = if options.ignore_case { "on" } else { "off" };
+        self.write_entry(&format!("{} ci={} nulls={}", OPTIONS_ENTRY, ci, options.null_order))
+    }
+
+    // Statements logged since the log was created
+    pub fn entries(&self) -> usize {
+        self.entries
+    }
+
+    fn write_entry(&mut self, text: &str) -> std::io::Result<()> {
+        let len = u32::try_from(text.len()).map_err(|_| std::io::Error::other("statement too long to log"))?;
+        let mut entry = len.to_le_bytes().to_vec();
+        entry.extend_from_slice(text.as_bytes());
+        // A single write, so a crash leaves at most one incomplete entry at the end
+        self.file.write_all(&entry)?;
+        self.file.sync_data()
+    }
+}
+
+// Applies the statements logged after the snapshot `db` was loaded from, returning how many
+// there were. A log written before the snapshot was last saved is ignored, since the snapshot
+// already holds its changes. An entry cut short by a crash ends the log, and a transaction
+// that never committed is rolled back.
+pub fn replay(db: &mut Database, db_path: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    let path = log_path(db_path);
+    let data = match std::fs::read(&path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    // A crash while the log was being created can leave it short of a full header
+    if data.len() < HEADER_LEN {
+        return Ok(0);
+    }
+    if data[..4] != LOG_MAGIC {
+        return Err(format!("{} is not a write-ahead log", path).into());
+    }
+    let snapshot = u64::from_le_bytes(data[4..HEADER_LEN].try_into()?);
+    if snapshot != snapshot_checksum(db_path)? {
+        return Ok(0);
+    }
+
+    // Every logged statement succeeded when it ran, including INSERTs that created a table. The
+    // log starts out with the default options, and the caller's own are put back afterwards.
+    let implicit_tables = std::mem::replace(&mut db.implicit_tables, true);
+    let options = std::mem::take(&mut db.options);
+    let outcome = replay_entries(db, &data[HEADER_LEN..], &path);
+    db.implicit_tables = implicit_tables;
+    db.options = options;
+    outcome
+}
+
+fn replay_entries(db: &mut Database, mut rest: &[u8], path: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut replayed = 0;
+    while rest.len() >= 4 {
+        let len = u32::from_le_bytes(rest[..4].try_into()?) as usize;
+        let Some(sql) = rest.get(4..4 + len).and_then(|bytes| std::str::from_utf8(bytes).ok()) else {
+            break;
+        };
+        rest = &rest[4 + len..];
+        if let Some(settings) = sql.strip_prefix(OPTIONS_ENTRY) {
+            db.options = parse_options(settings).map_err(|e| format!("Replaying '{}' from {}: {}", sql, path, e))?;
+            continue;
+        }
+        for statement in parse_program(&tokenize(sql)?)? {
+            db.execute(statement).map_err(|e| format!("Replaying '{}' from {}: {}", sql, path, e))?;
+        }
+        replayed += 1;
+    }
+    if db.in_transaction() {
+        db.execute(Statement::Rollback)?;
+    }
+    Ok(replayed)
+}
+
+// Reads the `ci=on nulls=first` settings of an options entry
+fn parse_options(settings: &str) -> Result<QueryOptions, String> {
+    let mut options = QueryOptions::default();
+    for setting in settings.split_whitespace() {
+        match setting.split_once('=') {
+            Some(("ci", "on")) => options.ignore_case = true,
+            Some(("ci", "off")) => options.ignore_case = false,
+            Some(("nulls", order)) => options.null_order = order.parse()?,
+            _ => return Err(format!("Unknown option '{}'", setting)),
+        }
+    }
+    Ok(options)
+}
+
+// FNV-1a hash of the snapshot file (0 if there is none yet), identifying which snapshot a
+// log follows
+fn snapshot_checksum(db_path: &str) -> std::io::Result<u64> {
+    let data = match std::fs::read(db_path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let mut hash = Fnv1a::default();
+    hash.write_all(&data)?;
+    Ok(hash.0)
+}
+
+// 64-bit FNV-1a hash of the bytes written to it, so data can be hashed as it is serialized
+pub(crate) struct Fnv1a(pub u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Write for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.0 = bytes.iter().fold(self.0, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3));
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,22 @@
+mod common;
+
+use common::{database, rows, run};
+use rustql_lavanya::error::RqlError;
+use rustql_lavanya::parser::Value;
+
+#[test]
+fn sum_past_i32_max_is_an_error() {
+    let mut db = database("CREATE TABLE t (a INT); INSERT INTO t VALUES (2147483647), (1);");
+    let err = run(&mut db, "SELECT SUM(a) FROM t;").unwrap_err();
+    assert!(matches!(&err, RqlError::ExecutionError(msg) if msg.contains("outside the INT range")), "{:?}", err);
+    // Summing as FLOAT gives the exact total
+    assert_eq!(rows(&mut db, "SELECT SUM(a * 1.0) FROM t;"), vec![vec![Value::Float(2147483648.0)]]);
+}
+
+#[test]
+fn multiplication_overflow_is_an_error() {
+    let mut db = database("CREATE TABLE t (a INT, b INT); INSERT INTO t VALUES (65536, 65536);");
+    let err = run(&mut db, "SELECT a * b FROM t;").unwrap_err();
+    assert_eq!(err, RqlError::ExecutionError("Integer overflow evaluating 65536 * 65536".to_string()));
+    assert_eq!(rows(&mut db, "SELECT a + b FROM t;"), vec![vec![Value::Int(131072)]]);
+}
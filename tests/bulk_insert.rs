@@ -0,0 +1,34 @@
+mod common;
+
+use common::{column, database, rows};
+use rustql_lavanya::executor::ExecResult;
+use rustql_lavanya::parser::Value;
+use std::time::{Duration, Instant};
+
+const ROWS: i32 = 100_000;
+
+#[test]
+fn insert_many_loads_100k_rows() {
+    let mut db = database(
+        "CREATE TABLE t (id INT, name TEXT, score FLOAT);
+         CREATE INDEX idx_id ON t(id);
+         CREATE INDEX idx_score ON t(score) USING BTREE;
+         ALTER TABLE t ADD CONSTRAINT uq_id UNIQUE (id);",
+    );
+    let batch: Vec<Vec<Value>> = (0..ROWS)
+        .map(|i| vec![Value::Int(i), Value::Str(format!("user{}", i)), Value::Float(f64::from(i) / 2.0)])
+        .collect();
+
+    let started = Instant::now();
+    let result = db.insert_many("t", batch).unwrap();
+    let elapsed = started.elapsed();
+    println!("insert_many: {} rows in {:?}", ROWS, elapsed);
+
+    assert_eq!(result, ExecResult::Inserted { count: ROWS as usize, first_id: 1 });
+    // A generous bound that still catches per-row index rebuilds or quadratic UNIQUE checks
+    assert!(elapsed < Duration::from_secs(30), "took {:?}", elapsed);
+    assert_eq!(column(&mut db, "SELECT COUNT(*) FROM t;"), vec![Value::Int(ROWS)]);
+    // Both indexes were brought up to date
+    assert_eq!(column(&mut db, "SELECT name FROM t WHERE id = 76543;"), vec![Value::Str("user76543".into())]);
+    assert_eq!(rows(&mut db, "SELECT id FROM t WHERE score > 49999.0;").len(), 1);
+}
@@ -0,0 +1,34 @@
+use std::process::{Command, Output};
+
+// Runs the command-line binary with `args`, keeping the database in memory
+fn rustql(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rustql_lavanya")).arg("--no-persist").args(args).output().unwrap()
+}
+
+// A script file of its own for each test
+fn script(name: &str, text: &str) -> String {
+    let path = std::env::temp_dir().join(format!("rustql_cli_{}_{}.sql", name, std::process::id()));
+    std::fs::write(&path, text).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn file_with_crlf_line_endings_runs() {
+    let path = script("crlf", "CREATE TABLE t (a INT);\r\nINSERT INTO t VALUES (1);\r\nSELECT a FROM t;\r\n");
+    let output = rustql(&["--file", &path]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("line 3: OK"), "{}", stdout);
+    assert!(!stdout.contains("Unexpected character"), "{}", stdout);
+}
+
+#[test]
+fn command_with_a_script_is_refused() {
+    let path = script("combined", "CREATE TABLE t (a INT);\n");
+    for flag in ["--file", "--init"] {
+        let output = rustql(&["-c", "SELECT 1;", flag, &path]);
+        assert_eq!(output.status.code(), Some(2));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(&format!("-c cannot be combined with {}", flag)), "{}", stderr);
+    }
+}
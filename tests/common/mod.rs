@@ -0,0 +1,35 @@
+// Helpers shared by the integration tests; each test binary uses only some of them
+#![allow(dead_code)]
+
+use rustql_lavanya::error::RqlError;
+use rustql_lavanya::executor::{Database, ExecResult};
+use rustql_lavanya::parser::{Value, parse_program, tokenize};
+
+// Runs every statement in `sql` in turn, returning the last one's result or the first error
+pub fn run(db: &mut Database, sql: &str) -> Result<ExecResult, RqlError> {
+    let mut result = ExecResult::RowsAffected(0);
+    for statement in parse_program(&tokenize(sql)?)? {
+        result = db.execute(statement)?;
+    }
+    Ok(result)
+}
+
+// A new in-memory database with `sql` already run
+pub fn database(sql: &str) -> Database {
+    let mut db = Database::new();
+    run(&mut db, sql).unwrap_or_else(|e| panic!("setup failed: {}", e));
+    db
+}
+
+// The rows a SELECT returns
+pub fn rows(db: &mut Database, sql: &str) -> Vec<Vec<Value>> {
+    match run(db, sql) {
+        Ok(ExecResult::Rows(result)) => result.rows,
+        other => panic!("expected rows from '{}', got {:?}", sql, other),
+    }
+}
+
+// The first column of each row a SELECT returns
+pub fn column(db: &mut Database, sql: &str) -> Vec<Value> {
+    rows(db, sql).into_iter().map(|row| row.into_iter().next().unwrap_or(Value::Null)).collect()
+}
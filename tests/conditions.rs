@@ -0,0 +1,61 @@
+mod common;
+
+use common::{column, database, run};
+use rustql_lavanya::error::RqlError;
+use rustql_lavanya::parser::Value;
+
+const PRICES: &str = "
+    CREATE TABLE p (id INT, price FLOAT, qty INT);
+    INSERT INTO p VALUES (1, 10.0, 10), (2, 2.5, 2), (3, 7.0, 7);
+";
+
+#[test]
+fn int_column_matches_float_list_entry() {
+    let mut db = database(PRICES);
+    assert_eq!(column(&mut db, "SELECT id FROM p WHERE qty IN (10.0, 3.5);"), vec![Value::Int(1)]);
+    assert_eq!(column(&mut db, "SELECT id FROM p WHERE qty IN (10, 2.0);"), vec![Value::Int(1), Value::Int(2)]);
+}
+
+#[test]
+fn float_column_matches_int_list_entry() {
+    let mut db = database(PRICES);
+    assert_eq!(column(&mut db, "SELECT id FROM p WHERE price IN (10, 7);"), vec![Value::Int(1), Value::Int(3)]);
+    assert_eq!(column(&mut db, "SELECT id FROM p WHERE price NOT IN (10, 7);"), vec![Value::Int(2)]);
+}
+
+#[test]
+fn between_with_mixed_bounds() {
+    let mut db = database(PRICES);
+    assert_eq!(column(&mut db, "SELECT id FROM p WHERE qty BETWEEN 2.5 AND 10;"), vec![Value::Int(1), Value::Int(3)]);
+    assert_eq!(column(&mut db, "SELECT id FROM p WHERE price BETWEEN 2 AND 7;"), vec![Value::Int(2), Value::Int(3)]);
+    assert_eq!(column(&mut db, "SELECT id FROM p WHERE qty NOT BETWEEN 2 AND 7.5;"), vec![Value::Int(1)]);
+}
+
+#[test]
+fn in_list_mixing_numbers_and_text_is_rejected() {
+    let mut db = database(PRICES);
+    for sql in ["SELECT id FROM p WHERE qty IN (1, 'a');", "SELECT id FROM p WHERE price IN ('a', 2.5);"] {
+        let err = run(&mut db, sql).unwrap_err();
+        assert!(matches!(&err, RqlError::SyntaxError { msg, .. } if msg.starts_with("IN list mixes")), "{:?}", err);
+    }
+}
+
+#[test]
+fn int_and_float_compare_numerically() {
+    let mut db = database(PRICES);
+    assert_eq!(column(&mut db, "SELECT id FROM p WHERE price = qty;"), vec![Value::Int(1), Value::Int(3)]);
+    assert_eq!(column(&mut db, "SELECT id FROM p WHERE qty = 7.0;"), vec![Value::Int(3)]);
+    assert_eq!(column(&mut db, "SELECT id FROM p WHERE qty < 2.5;"), vec![Value::Int(2)]);
+    assert_eq!(column(&mut db, "SELECT id FROM p WHERE price > 7;"), vec![Value::Int(1)]);
+    assert_eq!(Value::Int(2).compare_with(&Value::Float(2.5), false).unwrap(), std::cmp::Ordering::Less);
+}
+
+#[test]
+fn text_against_a_number_is_a_type_error() {
+    let mut db = database("CREATE TABLE t (n INT, s TEXT); INSERT INTO t VALUES (10, '10');");
+    for sql in ["SELECT n FROM t WHERE s = 10;", "SELECT n FROM t WHERE n < '5';", "SELECT n FROM t WHERE s = n;"] {
+        let err = run(&mut db, sql).unwrap_err();
+        assert!(matches!(&err, RqlError::TypeMismatch(msg) if msg.starts_with("Cannot compare")), "{}: {:?}", sql, err);
+    }
+    assert!(Value::Str("10".to_string()).compare_with(&Value::Int(10), false).is_err());
+}
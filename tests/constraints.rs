@@ -0,0 +1,18 @@
+mod common;
+
+use common::{column, database, run};
+use rustql_lavanya::executor::ExecResult;
+use rustql_lavanya::parser::Value;
+
+#[test]
+fn check_constraint_on_a_quoted_column() {
+    let mut db = database("CREATE TABLE t (\"my col\" INT); ALTER TABLE t ADD CONSTRAINT c CHECK (\"my col\" > 0);");
+    assert!(run(&mut db, "INSERT INTO t VALUES (-1);").is_err());
+    run(&mut db, "INSERT INTO t VALUES (5);").unwrap();
+
+    // The dump quotes the name, so it loads back with the constraint in force
+    let Ok(ExecResult::Message(dump)) = run(&mut db, "DUMP;") else { panic!("DUMP returns its SQL") };
+    let mut copy = database(&dump);
+    assert!(run(&mut copy, "INSERT INTO t VALUES (-1);").is_err());
+    assert_eq!(column(&mut copy, "SELECT \"my col\" FROM t;"), vec![Value::Int(5)]);
+}
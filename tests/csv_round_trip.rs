@@ -0,0 +1,62 @@
+mod common;
+
+use common::{database, rows, run};
+use rustql_lavanya::parser::Value;
+
+// Pieces text values are built from: the characters CSV has to quote or escape, text that looks
+// like a number, and multibyte text
+const PIECES: [&str; 14] = ["", "a", ",", "\"", "\"\"", "\n", "\r\n", " ", "007", "42", "-1.5", "NULL", "日本", "\\"];
+
+// A small deterministic generator (xorshift), so a failure can be reproduced from its seed
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self, below: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % below as u64) as usize
+    }
+
+    fn text(&mut self) -> Value {
+        match self.next(6) {
+            0 => Value::Null,
+            1 => Value::Str(String::new()),
+            _ => Value::Str((0..1 + self.next(4)).map(|_| PIECES[self.next(PIECES.len())]).collect()),
+        }
+    }
+
+    fn row(&mut self, id: i32) -> Vec<Value> {
+        let number = match self.next(4) {
+            0 => Value::Null,
+            1 => Value::Int(self.next(1000) as i32 - 500),
+            _ => Value::Float((self.next(20_000) as f64 - 10_000.0) / 8.0),
+        };
+        let date = match self.next(3) {
+            0 => Value::Null,
+            _ => Value::Date(format!("20{:02}-{:02}-{:02}", self.next(100), 1 + self.next(12), 1 + self.next(28))),
+        };
+        vec![Value::Int(id), self.text(), self.text(), number, date]
+    }
+}
+
+const SCHEMA: &str = "CREATE TABLE t (id INT, a TEXT, b, n, d DATE);";
+
+#[test]
+fn export_then_import_gives_back_the_same_rows() {
+    for seed in 1..=20u64 {
+        let mut rng = Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        let mut db = database(SCHEMA);
+        let original: Vec<Vec<Value>> = (1..=50).map(|id| rng.row(id)).collect();
+        db.insert_many("t", original.clone()).unwrap();
+
+        let path = std::env::temp_dir().join(format!("rustql_csv_{}_{}.csv", std::process::id(), seed));
+        let path = path.to_string_lossy().into_owned();
+        run(&mut db, &format!("EXPORT t TO '{}';", path)).unwrap();
+        let mut copy = database(SCHEMA);
+        run(&mut copy, &format!("IMPORT t FROM '{}';", path)).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(rows(&mut copy, "SELECT * FROM t;"), original, "seed {}", seed);
+    }
+}
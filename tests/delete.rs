@@ -0,0 +1,31 @@
+mod common;
+
+use common::{column, database, run};
+use rustql_lavanya::error::RqlError;
+use rustql_lavanya::executor::ExecResult;
+use rustql_lavanya::parser::Value;
+
+#[test]
+fn column_to_column_comparison_reads_both_columns() {
+    // Row 3 holds the text 'b' in column a: the old parser compared a with that literal instead
+    let mut db = database(
+        "CREATE TABLE t (id INT, a TEXT, b TEXT); INSERT INTO t VALUES (1, 'x', 'x'), (2, 'x', 'y'), (3, 'b', 'z');",
+    );
+    assert_eq!(run(&mut db, "DELETE FROM t WHERE a = b;").unwrap(), ExecResult::RowsAffected(1));
+    assert_eq!(column(&mut db, "SELECT id FROM t;"), vec![Value::Int(2), Value::Int(3)]);
+}
+
+#[test]
+fn column_to_column_inequality() {
+    let mut db =
+        database("CREATE TABLE t (id INT, lo INT, hi INT); INSERT INTO t VALUES (1, 1, 5), (2, 6, 3), (3, 4, 4);");
+    assert_eq!(run(&mut db, "DELETE FROM t WHERE lo >= hi;").unwrap(), ExecResult::RowsAffected(2));
+    assert_eq!(column(&mut db, "SELECT id FROM t;"), vec![Value::Int(1)]);
+}
+
+#[test]
+fn misspelled_where_column_deletes_nothing() {
+    let mut db = database("CREATE TABLE t (id INT); INSERT INTO t VALUES (1), (2);");
+    assert!(matches!(run(&mut db, "DELETE FROM t WHERE idd = 1;"), Err(RqlError::ColumnNotFound(_))));
+    assert_eq!(column(&mut db, "SELECT id FROM t;"), vec![Value::Int(1), Value::Int(2)]);
+}
@@ -0,0 +1,26 @@
+mod common;
+
+use common::database;
+
+#[test]
+fn reordered_columns_are_reported_as_moved() {
+    let old = database("CREATE TABLE t (a INT, b INT);");
+    let new = database("CREATE TABLE t (b INT, a INT);");
+
+    let diff = old.diff(&new);
+    assert_eq!(diff.changed_tables[0].moved_columns, vec![("a".to_string(), 1, 2), ("b".to_string(), 2, 1)]);
+    assert_eq!(
+        diff.to_string(),
+        "Table 't' changed:\n  column 'a' moved from position 1 to 2\n  column 'b' moved from position 2 to 1"
+    );
+}
+
+#[test]
+fn added_column_does_not_move_the_others() {
+    let old = database("CREATE TABLE t (a INT, b INT);");
+    let new = database("CREATE TABLE t (id INT, a INT, b INT);");
+
+    let diff = old.diff(&new);
+    assert!(diff.changed_tables[0].moved_columns.is_empty());
+    assert_eq!(diff.to_string(), "Table 't' changed:\n  column 'id' added (INT)");
+}
@@ -0,0 +1,25 @@
+mod common;
+
+use common::{column, database, run};
+use rustql_lavanya::executor::{Database, ExecResult};
+use rustql_lavanya::parser::Value;
+
+// The SQL DUMP writes for the whole database
+fn dump(db: &mut Database) -> String {
+    match run(db, "DUMP;") {
+        Ok(ExecResult::Message(sql)) => sql,
+        other => panic!("expected the dump, got {:?}", other),
+    }
+}
+
+#[test]
+fn smallest_int_survives_a_dump() {
+    let mut db = database("CREATE TABLE t (a INT); INSERT INTO t VALUES (-2147483647);");
+    run(&mut db, "UPDATE t SET a = a - 1 WHERE 1 = 1;").unwrap();
+    let sql = dump(&mut db);
+    assert!(sql.contains("(-2147483648)"), "{}", sql);
+
+    let mut copy = database(&sql);
+    assert_eq!(column(&mut copy, "SELECT a FROM t;"), vec![Value::Int(i32::MIN)]);
+    assert_eq!(column(&mut copy, "SELECT a FROM t WHERE a = -2147483648;"), vec![Value::Int(i32::MIN)]);
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::{column, database, run};
+use rustql_lavanya::error::RqlError;
+use rustql_lavanya::executor::ExecResult;
+use rustql_lavanya::parser::Value;
+
+const SCHEMA: &str = "
+    CREATE TABLE users (id INT, name TEXT);
+    CREATE TABLE orders (id INT, user_id INT REFERENCES users(id));
+    INSERT INTO users VALUES (1, 'ann'), (2, 'bob');
+    INSERT INTO orders VALUES (10, 1);
+";
+
+#[test]
+fn update_child_to_missing_parent_is_rejected() {
+    let mut db = database(SCHEMA);
+    let err = run(&mut db, "UPDATE orders SET user_id = 5 WHERE id = 10;").unwrap_err();
+    assert!(matches!(err, RqlError::ConstraintViolation(_)), "{:?}", err);
+    assert_eq!(column(&mut db, "SELECT user_id FROM orders;"), vec![Value::Int(1)]);
+}
+
+#[test]
+fn update_child_to_existing_parent_succeeds() {
+    let mut db = database(SCHEMA);
+    run(&mut db, "UPDATE orders SET user_id = 2 WHERE id = 10;").unwrap();
+    assert_eq!(column(&mut db, "SELECT user_id FROM orders;"), vec![Value::Int(2)]);
+}
+
+#[test]
+fn update_of_referenced_parent_key_is_rejected() {
+    let mut db = database(SCHEMA);
+    let err = run(&mut db, "UPDATE users SET id = 3 WHERE id = 1;").unwrap_err();
+    assert!(matches!(err, RqlError::ConstraintViolation(_)), "{:?}", err);
+    assert_eq!(column(&mut db, "SELECT id FROM users;"), vec![Value::Int(1), Value::Int(2)]);
+}
+
+#[test]
+fn update_of_unreferenced_parent_key_succeeds() {
+    let mut db = database(SCHEMA);
+    run(&mut db, "UPDATE users SET id = 3 WHERE id = 2;").unwrap();
+    // Other columns of a referenced row may still change
+    run(&mut db, "UPDATE users SET name = 'amy' WHERE id = 1;").unwrap();
+    assert_eq!(column(&mut db, "SELECT id FROM users;"), vec![Value::Int(1), Value::Int(3)]);
+}
+
+#[test]
+fn truncate_of_referenced_parent_is_restricted() {
+    let mut db = database(SCHEMA);
+    let err = run(&mut db, "TRUNCATE TABLE users;").unwrap_err();
+    assert!(matches!(err, RqlError::ConstraintViolation(_)), "{:?}", err);
+    assert_eq!(column(&mut db, "SELECT id FROM users;"), vec![Value::Int(1), Value::Int(2)]);
+}
+
+#[test]
+fn truncate_cascades_like_delete() {
+    let mut db = database(
+        "CREATE TABLE users (id INT);
+         CREATE TABLE orders (id INT, user_id INT REFERENCES users(id) ON DELETE CASCADE);
+         CREATE TABLE items (order_id INT REFERENCES orders(id) ON DELETE CASCADE);
+         CREATE TABLE notes (text TEXT);
+         INSERT INTO users VALUES (1), (2);
+         INSERT INTO orders VALUES (10, 1), (11, 2);
+         INSERT INTO items VALUES (10), (11);
+         INSERT INTO notes VALUES ('kept');",
+    );
+    assert_eq!(run(&mut db, "TRUNCATE TABLE users;").unwrap(), ExecResult::RowsAffected(2));
+    for table in ["users", "orders", "items"] {
+        assert!(column(&mut db, &format!("SELECT * FROM {};", table)).is_empty(), "{}", table);
+    }
+    assert_eq!(column(&mut db, "SELECT * FROM notes;"), vec![Value::Str("kept".to_string())]);
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use common::{database, rows, run};
+use rustql_lavanya::executor::Database;
+use rustql_lavanya::parser::Value;
+
+// The text values a SELECT returns, row by row
+fn texts(db: &mut Database, sql: &str) -> Vec<String> {
+    rows(db, sql)
+        .into_iter()
+        .flatten()
+        .map(|value| match value {
+            Value::Str(s) => s,
+            other => panic!("expected text from '{}', got {:?}", sql, other),
+        })
+        .collect()
+}
+
+#[test]
+fn substr_counts_characters_not_bytes() {
+    let mut db = database("CREATE TABLE t (s TEXT); INSERT INTO t VALUES ('日本語テキスト'), ('héllo');");
+    assert_eq!(texts(&mut db, "SELECT SUBSTR(s, 2, 3) FROM t;"), vec!["本語テ", "éll"]);
+    assert_eq!(texts(&mut db, "SELECT SUBSTR(s, 2) FROM t;"), vec!["本語テキスト", "éllo"]);
+    assert_eq!(rows(&mut db, "SELECT LENGTH(s) FROM t;"), vec![vec![Value::Int(7)], vec![Value::Int(5)]]);
+}
+
+#[test]
+fn substr_out_of_range_is_cut_short() {
+    let mut db = database("CREATE TABLE t (s TEXT); INSERT INTO t VALUES ('héllo');");
+    for (call, expected) in [
+        // Positions before 1 count towards the length
+        ("SUBSTR(s, 0, 2)", "h"),
+        ("SUBSTR(s, -1, 3)", "h"),
+        ("SUBSTR(s, -5, 2)", ""),
+        // Past the end
+        ("SUBSTR(s, 4, 100)", "lo"),
+        ("SUBSTR(s, 10)", ""),
+        ("SUBSTR(s, 2147483647, 2147483647)", ""),
+        ("SUBSTR(s, 3, 0)", ""),
+    ] {
+        assert_eq!(texts(&mut db, &format!("SELECT {} FROM t;", call)), vec![expected], "{}", call);
+    }
+    assert!(run(&mut db, "SELECT SUBSTR(s, 2, -1) FROM t;").is_err());
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use common::{column, database, run};
+use rustql_lavanya::executor::Database;
+use rustql_lavanya::parser::Value;
+
+const USERS: &str = "
+    CREATE TABLE users (id INT, name TEXT);
+    INSERT INTO users VALUES (1, 'ann'), (5, 'bob'), (7, 'cy');
+    CREATE INDEX idx_id ON users(id);
+";
+
+// Whether a step of the EXPLAIN output for `sql` mentions `text`
+fn plan_mentions(db: &mut Database, sql: &str, text: &str) -> bool {
+    column(db, &format!("EXPLAIN {}", sql)).iter().any(|step| matches!(step, Value::Str(s) if s.contains(text)))
+}
+
+#[test]
+fn literal_on_either_side_uses_the_index() {
+    let mut db = database(USERS);
+    for cond in ["id = 5", "5 = id"] {
+        let sql = format!("SELECT name FROM users WHERE {};", cond);
+        assert_eq!(column(&mut db, &sql), vec![Value::Str("bob".to_string())], "{}", cond);
+        assert!(plan_mentions(&mut db, &sql, "index lookup via 'idx_id'"), "{}", cond);
+    }
+}
+
+#[test]
+fn flipped_range_comparison_uses_the_index() {
+    let mut db =
+        database("CREATE TABLE t (n INT); INSERT INTO t VALUES (1), (4), (9); CREATE INDEX idx_n ON t(n) USING BTREE;");
+    let expected = vec![Value::Int(4), Value::Int(9)];
+    assert_eq!(column(&mut db, "SELECT n FROM t WHERE n > 2;"), expected);
+    assert_eq!(column(&mut db, "SELECT n FROM t WHERE 2 < n;"), expected);
+    assert!(plan_mentions(&mut db, "SELECT n FROM t WHERE 2 < n;", "index range scan"));
+}
+
+const MIXED: &str = "
+    CREATE TABLE t (i INT, f FLOAT, d DATE, u);
+    INSERT INTO t VALUES (1, 1.0, '2024-01-01', 3), (2, 2.5, '2024-02-10', 3.0), (3, 3, '2024-03-15', 7),
+        (4, 4.5, '2024-02-10', 7.5), (5, NULL, NULL, NULL);
+";
+
+// Conditions whose literals match stored values of another type: INT against FLOAT (also in the
+// untyped column u, which holds both) and text against DATE
+const LOOKUPS: [&str; 16] = [
+    "i = 2",
+    "i = 2.0",
+    "i > 1.5",
+    "i <= 3",
+    "f = 3",
+    "f = 3.0",
+    "f >= 2",
+    "f < 3",
+    "d = '2024-02-10'",
+    "d = DATE '2024-02-10'",
+    "d > '2024-01-31'",
+    "d <= DATE '2024-02-10'",
+    "u = 3",
+    "u = 3.0",
+    "u = 7.0",
+    "u > 3",
+];
+
+#[test]
+fn indexed_lookups_match_a_scan() {
+    let mut scan = database(MIXED);
+    for using in ["", " USING BTREE"] {
+        let mut indexed = database(MIXED);
+        for col in ["i", "f", "d", "u"] {
+            run(&mut indexed, &format!("CREATE INDEX idx_{} ON t({}){};", col, col, using)).unwrap();
+        }
+        for cond in LOOKUPS {
+            let sql = format!("SELECT i FROM t WHERE {} ORDER BY i;", cond);
+            assert_eq!(column(&mut indexed, &sql), column(&mut scan, &sql), "{}{}", cond, using);
+        }
+        assert!(plan_mentions(&mut indexed, "SELECT i FROM t WHERE f = 3;", "index"), "{}", using);
+    }
+}
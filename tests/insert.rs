@@ -0,0 +1,54 @@
+mod common;
+
+use common::{column, database, rows, run};
+use rustql_lavanya::error::RqlError;
+use rustql_lavanya::executor::{Database, ExecResult};
+use rustql_lavanya::parser::Value;
+
+fn arity_error(expected: usize, got: usize) -> RqlError {
+    RqlError::ExecutionError(format!("expected {} values, got {}", expected, got))
+}
+
+#[test]
+fn too_few_values_are_rejected() {
+    let mut db = database("CREATE TABLE t (a INT, b INT);");
+    assert_eq!(run(&mut db, "INSERT INTO t VALUES (1);").unwrap_err(), arity_error(2, 1));
+    assert!(rows(&mut db, "SELECT * FROM t;").is_empty());
+}
+
+#[test]
+fn too_many_values_are_rejected() {
+    let mut db = database("CREATE TABLE t (a INT, b INT);");
+    assert_eq!(run(&mut db, "INSERT INTO t VALUES (1, 2, 3);").unwrap_err(), arity_error(2, 3));
+    assert!(rows(&mut db, "SELECT * FROM t;").is_empty());
+}
+
+#[test]
+fn one_bad_row_rejects_the_whole_insert() {
+    let mut db = database("CREATE TABLE t (a INT, b INT);");
+    assert_eq!(run(&mut db, "INSERT INTO t VALUES (1, 2), (3);").unwrap_err(), arity_error(2, 1));
+    assert!(rows(&mut db, "SELECT * FROM t;").is_empty());
+}
+
+#[test]
+fn implicit_table_takes_rows_of_any_width() {
+    let mut db = Database::new();
+    db.implicit_tables = true;
+    run(&mut db, "INSERT INTO z VALUES (1); INSERT INTO z VALUES (1, 2, 3);").unwrap();
+    assert_eq!(
+        rows(&mut db, "SELECT * FROM z;"),
+        vec![vec![Value::Int(1), Value::Null, Value::Null], vec![Value::Int(1), Value::Int(2), Value::Int(3)]]
+    );
+}
+
+#[test]
+fn implicit_table_headers_follow_the_widest_row() {
+    let mut db = Database::new();
+    db.implicit_tables = true;
+    // The widest row is neither the first nor the last
+    run(&mut db, "INSERT INTO z VALUES (1); INSERT INTO z VALUES (2, 'b', 3); INSERT INTO z VALUES (4, 'd');").unwrap();
+    let Ok(ExecResult::Rows(result)) = run(&mut db, "SELECT * FROM z;") else { panic!("SELECT returns rows") };
+    assert_eq!(result.columns, vec!["col0", "col1", "col2"]);
+    assert!(result.rows.iter().all(|row| row.len() == 3));
+    assert_eq!(column(&mut db, "SELECT col2 FROM z;"), vec![Value::Null, Value::Int(3), Value::Null]);
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use common::{database, rows, run};
+use rustql_lavanya::error::RqlError;
+use rustql_lavanya::executor::ExecResult;
+use rustql_lavanya::parser::Value;
+
+const SHOP: &str = "
+    CREATE TABLE u (id INT, name TEXT);
+    CREATE TABLE o (id INT, uid INT);
+    INSERT INTO u VALUES (1, 'ann'), (2, 'bob');
+    INSERT INTO o VALUES (9, 1), (8, 2);
+";
+
+#[test]
+fn name_in_both_tables_is_ambiguous() {
+    let mut db = database(SHOP);
+    for sql in [
+        "SELECT id FROM u JOIN o ON u.id = o.uid;",
+        "SELECT name FROM u JOIN o ON u.id = o.uid WHERE id = 9;",
+        "SELECT name FROM u JOIN o ON u.id = o.uid ORDER BY id;",
+    ] {
+        let err = run(&mut db, sql).unwrap_err();
+        assert!(matches!(err, RqlError::AmbiguousColumn { .. }), "{}: {:?}", sql, err);
+        assert!(err.to_string().contains("u.id, o.id"), "{}: {}", sql, err);
+    }
+}
+
+#[test]
+fn qualified_name_resolves() {
+    let mut db = database(SHOP);
+    assert_eq!(
+        rows(&mut db, "SELECT o.id, u.id, name FROM u JOIN o ON u.id = o.uid WHERE o.id = 9;"),
+        vec![vec![Value::Int(9), Value::Int(1), Value::Str("ann".into())]]
+    );
+}
+
+#[test]
+fn table_star_mixes_with_explicit_columns() {
+    let mut db = database(SHOP);
+    let Ok(ExecResult::Rows(result)) =
+        run(&mut db, "SELECT name, o.*, u.id FROM u JOIN o ON u.id = o.uid WHERE o.id = 8;")
+    else {
+        panic!("SELECT returns rows");
+    };
+    assert_eq!(result.columns, vec!["name", "o.id", "o.uid", "u.id"]);
+    assert_eq!(result.rows, vec![vec![Value::Str("bob".into()), Value::Int(8), Value::Int(2), Value::Int(2)]]);
+}
+
+#[test]
+fn table_star_of_an_unknown_table_is_an_error() {
+    let mut db = database(SHOP);
+    assert!(run(&mut db, "SELECT x.*, name FROM u JOIN o ON u.id = o.uid;").is_err());
+}
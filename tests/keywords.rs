@@ -0,0 +1,25 @@
+mod common;
+
+use common::{column, database, rows, run};
+use rustql_lavanya::parser::Value;
+
+#[test]
+fn keyword_as_a_column_name_in_create_table() {
+    let mut db = database("CREATE TABLE t (from INT, set TEXT); INSERT INTO t VALUES (1, 'x');");
+    assert_eq!(rows(&mut db, "SELECT * FROM t;"), vec![vec![Value::Int(1), Value::Str("x".to_string())]]);
+    // Where a keyword could also be read as syntax, it takes double quotes
+    run(&mut db, "UPDATE t SET \"from\" = 2 WHERE \"from\" = 1;").unwrap();
+    assert_eq!(column(&mut db, "SELECT \"from\" FROM t;"), vec![Value::Int(2)]);
+}
+
+#[test]
+fn keyword_as_a_table_name() {
+    let mut db = database("CREATE TABLE values (a INT); INSERT INTO values VALUES (1);");
+    assert_eq!(column(&mut db, "SELECT a FROM values;"), vec![Value::Int(1)]);
+}
+
+#[test]
+fn unquoted_keyword_in_an_expression_is_still_syntax() {
+    let mut db = database("CREATE TABLE t (from INT);");
+    assert!(run(&mut db, "SELECT from FROM t;").is_err());
+}
@@ -0,0 +1,24 @@
+mod common;
+
+use common::{database, rows};
+use rustql_lavanya::parser::{OnConflict, Value};
+
+#[test]
+fn merge_refuses_columns_in_another_order() {
+    let mut db = database("CREATE TABLE t (a INT, b INT);");
+    let other = database("CREATE TABLE t (b INT, a INT); INSERT INTO t VALUES (1, 2);");
+
+    let err = db.merge(other, OnConflict::Error).unwrap_err().to_string();
+    assert!(err.contains("column 'a' moved from position 1 to 2"), "{}", err);
+    assert!(rows(&mut db, "SELECT * FROM t;").is_empty());
+}
+
+#[test]
+fn merge_appends_rows_of_the_same_layout() {
+    let mut db = database("CREATE TABLE t (a INT, b INT); INSERT INTO t VALUES (1, 2);");
+    let other = database("CREATE TABLE t (a INT, b INT); INSERT INTO t VALUES (3, 4);");
+
+    let report = db.merge(other, OnConflict::Error).unwrap();
+    assert_eq!(report.rows_added, 1);
+    assert_eq!(rows(&mut db, "SELECT b FROM t WHERE a = 3;"), vec![vec![Value::Int(4)]]);
+}
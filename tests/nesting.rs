@@ -0,0 +1,34 @@
+mod common;
+
+use common::{column, database, run};
+use rustql_lavanya::parser::Value;
+
+// The parser's limit on nested parentheses
+const LIMIT: usize = 100;
+
+fn nested(inner: &str, depth: usize) -> String {
+    format!("{}{}{}", "(".repeat(depth), inner, ")".repeat(depth))
+}
+
+#[test]
+fn nesting_up_to_the_limit_parses() {
+    let mut db = database("CREATE TABLE t (a INT); INSERT INTO t VALUES (1), (2);");
+    let expr = format!("SELECT {} FROM t;", nested("a + 1", LIMIT));
+    assert_eq!(column(&mut db, &expr), vec![Value::Int(2), Value::Int(3)]);
+    let cond = format!("SELECT a FROM t WHERE {};", nested("a = 2", LIMIT));
+    assert_eq!(column(&mut db, &cond), vec![Value::Int(2)]);
+}
+
+#[test]
+fn nesting_past_the_limit_is_a_syntax_error() {
+    let mut db = database("CREATE TABLE t (a INT);");
+    for sql in [
+        format!("SELECT {} FROM t;", nested("a", LIMIT + 1)),
+        format!("SELECT a FROM t WHERE {};", nested("a = 2", LIMIT + 1)),
+        // Far too deep to parse by recursion, but refused before the parser recurses
+        format!("SELECT {} FROM t;", nested("a", 100_000)),
+    ] {
+        let err = run(&mut db, &sql).unwrap_err();
+        assert!(err.to_string().contains("nested too deeply"), "{}", err);
+    }
+}
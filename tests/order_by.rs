@@ -0,0 +1,39 @@
+mod common;
+
+use common::{column, database};
+use rustql_lavanya::parser::Value;
+
+const STAFF: &str = "
+    CREATE TABLE staff (id INT, dept TEXT, salary INT, age INT);
+    INSERT INTO staff VALUES
+        (1, 'ops', 100, 30),
+        (2, 'dev', NULL, 40),
+        (3, 'dev', 200, 25),
+        (4, 'ops', 100, 50),
+        (5, 'dev', 200, 35),
+        (6, 'ops', NULL, 20);
+";
+
+fn ids(sql: &str) -> Vec<i32> {
+    let mut db = database(STAFF);
+    column(&mut db, sql)
+        .into_iter()
+        .map(|v| match v {
+            Value::Int(n) => n,
+            other => panic!("unexpected id {:?}", other),
+        })
+        .collect()
+}
+
+#[test]
+fn each_key_has_its_own_direction_and_null_placement() {
+    // dept ascending; within a dept salary descending with NULLs first; ties broken by age descending
+    let sql = "SELECT id FROM staff ORDER BY dept ASC, salary DESC NULLS FIRST, age DESC;";
+    assert_eq!(ids(sql), vec![2, 5, 3, 6, 4, 1]);
+}
+
+#[test]
+fn null_placement_is_per_key() {
+    let sql = "SELECT id FROM staff ORDER BY dept DESC, salary ASC NULLS LAST, age ASC;";
+    assert_eq!(ids(sql), vec![1, 4, 6, 3, 5, 2]);
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use common::{column, database};
+use rustql_lavanya::executor::ExecResult;
+use rustql_lavanya::parser::Value;
+
+#[test]
+fn null_parameter_binds() {
+    let mut db = database("CREATE TABLE t (a INT, b TEXT);");
+    let insert = db.prepare("INSERT INTO t VALUES (?, ?);").unwrap();
+    db.execute_prepared(&insert, &[Value::Int(1), Value::Null]).unwrap();
+    assert_eq!(column(&mut db, "SELECT b FROM t;"), vec![Value::Null]);
+}
+
+#[test]
+fn smallest_int_parameter_binds() {
+    let mut db = database("CREATE TABLE t (a INT);");
+    let insert = db.prepare("INSERT INTO t VALUES (?);").unwrap();
+    db.execute_prepared(&insert, &[Value::Int(i32::MIN)]).unwrap();
+    db.execute_prepared(&insert, &[Value::Int(-5)]).unwrap();
+    assert_eq!(column(&mut db, "SELECT a FROM t;"), vec![Value::Int(i32::MIN), Value::Int(-5)]);
+
+    let select = db.prepare("SELECT a FROM t WHERE a = ?;").unwrap();
+    let Ok(ExecResult::Rows(found)) = db.execute_prepared(&select, &[Value::Int(i32::MIN)]) else {
+        panic!("SELECT returns rows");
+    };
+    assert_eq!(found.rows, vec![vec![Value::Int(i32::MIN)]]);
+}
+
+#[test]
+fn infinite_float_parameter_is_refused() {
+    let mut db = database("CREATE TABLE t (x FLOAT);");
+    let insert = db.prepare("INSERT INTO t VALUES (?);").unwrap();
+    for value in [f64::NEG_INFINITY, f64::INFINITY, f64::NAN] {
+        assert!(db.execute_prepared(&insert, &[Value::Float(value)]).is_err());
+    }
+    db.execute_prepared(&insert, &[Value::Float(-1.5)]).unwrap();
+    assert_eq!(column(&mut db, "SELECT x FROM t;"), vec![Value::Float(-1.5)]);
+}
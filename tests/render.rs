@@ -0,0 +1,37 @@
+use rustql_lavanya::executor::QueryResult;
+use rustql_lavanya::parser::Value;
+use rustql_lavanya::render::{RenderOptions, render_table};
+use unicode_width::UnicodeWidthStr;
+
+// Rows of (id, name), numbered from 1
+fn names(cells: &[&str]) -> QueryResult {
+    QueryResult {
+        columns: vec!["id".to_string(), "name".to_string()],
+        rows: (1..)
+            .zip(cells)
+            .map(|(id, name)| vec![Value::Int(id), Value::Str(name.to_string())])
+            .collect(),
+    }
+}
+
+#[test]
+fn wide_and_multibyte_text_lines_up() {
+    let table = render_table(&names(&["Zoë", "日本語", "ab"]), &RenderOptions::default());
+    let expected = "\
++----+--------+
+| id | name   |
++----+--------+
+| 1  | Zoë    |
+| 2  | 日本語 |
+| 3  | ab     |
++----+--------+
+";
+    assert_eq!(table, expected);
+}
+
+#[test]
+fn every_line_has_the_same_display_width() {
+    let table = render_table(&names(&["naïve café", "🦀🦀", "中文 and ascii", ""]), &RenderOptions::default());
+    let widths: Vec<usize> = table.lines().map(|line| line.width()).collect();
+    assert!(widths.iter().all(|w| *w == widths[0]), "{:?}\n{}", widths, table);
+}
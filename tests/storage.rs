@@ -0,0 +1,68 @@
+mod common;
+
+use common::{database, run};
+use rustql_lavanya::executor::{Database, ExecResult};
+
+const SHOP: &str = "
+    CREATE TABLE users (id INT, name TEXT, joined DATE, score FLOAT);
+    INSERT INTO users VALUES (1, 'O''Brien', '2024-01-31', 0.1), (2, 'Zoë 日本\nline two', NULL, -2.5e10),
+        (3, '', '2023-12-01', NULL);
+    CREATE INDEX idx_name ON users(name);
+    CREATE INDEX idx_joined ON users(joined) USING BTREE;
+    ALTER TABLE users ADD CONSTRAINT uq_id UNIQUE (id);
+    ALTER TABLE users ADD CONSTRAINT CHECK (score < 100.0);
+    CREATE TABLE orders (id INT, user_id INT REFERENCES users(id), total INT);
+    INSERT INTO orders VALUES (10, 1, -2147483647), (11, 2, 2147483647);
+";
+
+// A database file path of its own for each test
+fn fresh_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("rustql_storage_{}_{}", std::process::id(), name));
+    let path = path.to_string_lossy().into_owned();
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+// The SQL DUMP writes, covering rows, indexes and constraints
+fn dump(db: &mut Database) -> String {
+    match run(db, "DUMP;") {
+        Ok(ExecResult::Message(sql)) => sql,
+        other => panic!("expected the dump, got {:?}", other),
+    }
+}
+
+fn round_trip(file_name: &str) -> (Database, Database, String) {
+    let db = database(SHOP);
+    let path = fresh_path(file_name);
+    db.save(&path).unwrap();
+    let loaded = Database::load(&path).unwrap();
+    (db, loaded, path)
+}
+
+#[test]
+fn bincode_file_round_trips() {
+    let (mut db, mut loaded, path) = round_trip("shop.bin");
+    assert!(serde_json::from_slice::<serde_json::Value>(&std::fs::read(&path).unwrap()).is_err());
+    assert_eq!(loaded.checksum(), db.checksum());
+    assert_eq!(loaded.table_names(), vec!["users", "orders"]);
+    assert_eq!(dump(&mut loaded), dump(&mut db));
+}
+
+#[test]
+fn json_file_round_trips() {
+    let (mut db, mut loaded, path) = round_trip("shop.json");
+    assert!(serde_json::from_slice::<serde_json::Value>(&std::fs::read(&path).unwrap()).is_ok());
+    assert_eq!(loaded.checksum(), db.checksum());
+    assert_eq!(loaded.table_names(), vec!["users", "orders"]);
+    assert_eq!(dump(&mut loaded), dump(&mut db));
+}
+
+#[test]
+fn loaded_constraints_are_enforced() {
+    for file_name in ["enforced.bin", "enforced.json"] {
+        let (_, mut loaded, _) = round_trip(file_name);
+        assert!(run(&mut loaded, "INSERT INTO users VALUES (1, 'dup', NULL, 1.0);").is_err(), "{}", file_name);
+        assert!(run(&mut loaded, "INSERT INTO users VALUES (4, 'big', NULL, 500.0);").is_err(), "{}", file_name);
+        assert!(run(&mut loaded, "INSERT INTO orders VALUES (12, 99, 1);").is_err(), "{}", file_name);
+    }
+}
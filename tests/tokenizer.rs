@@ -0,0 +1,56 @@
+use rustql_lavanya::error::RqlError;
+use rustql_lavanya::parser::{Token, parse, tokenize};
+
+#[test]
+fn unterminated_string_is_reported_where_it_opens() {
+    let err = tokenize("SELECT * FROM t WHERE name = 'ab").unwrap_err();
+    assert_eq!(err, RqlError::UnterminatedString { text: "'ab".to_string(), pos: 29 });
+    assert!(err.is_unterminated());
+}
+
+#[test]
+fn string_ending_in_a_backslash_is_unterminated() {
+    let err = tokenize("INSERT INTO t VALUES ('ab\\").unwrap_err();
+    assert!(matches!(err, RqlError::UnterminatedString { pos: 22, .. }), "{:?}", err);
+}
+
+#[test]
+fn unterminated_quoted_identifier() {
+    for (sql, text) in [("SELECT \"full name", "\"full name"), ("SELECT `a", "`a")] {
+        let err = tokenize(sql).unwrap_err();
+        assert_eq!(err, RqlError::UnterminatedIdentifier { text: text.to_string(), pos: 7 });
+    }
+}
+
+#[test]
+fn unterminated_block_comment() {
+    for sql in ["SELECT 1 /* note", "SELECT 1 /* note *", "SELECT 1 /*/"] {
+        assert_eq!(tokenize(sql).unwrap_err(), RqlError::UnterminatedComment { pos: 9 }, "{}", sql);
+    }
+}
+
+#[test]
+fn block_comments_are_skipped() {
+    let tokens = tokenize("SELECT /* all */ a /**/ FROM t; /* trailing */").unwrap();
+    assert_eq!(tokens.len(), 5);
+    assert!(matches!(tokens.last(), Some(Token::Semicolon)));
+    assert!(matches!(tokenize("SELECT 6 / 2").unwrap()[2], Token::Slash));
+}
+
+#[test]
+fn other_malformed_input_is_a_plain_syntax_error() {
+    for sql in ["SELECT 1 | 2", "SELECT 1.", "SELECT #"] {
+        let err = tokenize(sql).unwrap_err();
+        assert!(matches!(err, RqlError::SyntaxError { .. }), "{}: {:?}", sql, err);
+        assert!(!err.is_unterminated());
+    }
+}
+
+#[test]
+fn minus_folds_into_the_smallest_int_literal() {
+    assert!(matches!(tokenize("VALUES (-2147483648)").unwrap()[..], [_, Token::LeftParen, Token::Int(i32::MIN), _]));
+    // Subtracting it is still out of range, as is negating it again
+    assert!(tokenize("a - 2147483648").is_err());
+    let err = parse(&tokenize("SELECT * FROM t WHERE a = - -2147483648;").unwrap()).unwrap_err();
+    assert!(err.to_string().contains("out of range"), "{}", err);
+}
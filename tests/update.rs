@@ -0,0 +1,69 @@
+mod common;
+
+use common::{database, rows, run};
+use rustql_lavanya::error::RqlError;
+use rustql_lavanya::parser::Value;
+
+#[test]
+fn assignments_read_the_row_as_it_was() {
+    let mut db = database("CREATE TABLE t (id INT, a INT, b INT); INSERT INTO t VALUES (1, 1, 2), (2, 3, 4);");
+    run(&mut db, "UPDATE t SET a = b, b = a WHERE id = 1;").unwrap();
+    assert_eq!(
+        rows(&mut db, "SELECT a, b FROM t;"),
+        vec![vec![Value::Int(2), Value::Int(1)], vec![Value::Int(3), Value::Int(4)]]
+    );
+    // A later assignment still sees the old value of a column set before it
+    run(&mut db, "UPDATE t SET a = a + 10, b = a WHERE id = 2;").unwrap();
+    assert_eq!(rows(&mut db, "SELECT a, b FROM t WHERE id = 2;"), vec![vec![Value::Int(13), Value::Int(3)]]);
+}
+
+#[test]
+fn misspelled_column_changes_nothing() {
+    let mut db = database("CREATE TABLE t (id INT, a INT); INSERT INTO t VALUES (1, 1), (2, 2);");
+    let before = rows(&mut db, "SELECT * FROM t;");
+    let misspelled = [
+        "UPDATE t SET a = 5 WHERE idd = 1;",
+        "UPDATE t SET aa = 5 WHERE id = 1;",
+        "UPDATE t SET a = b WHERE id = 1;",
+    ];
+    for sql in misspelled {
+        assert!(matches!(run(&mut db, sql), Err(RqlError::ColumnNotFound(_))), "{}", sql);
+    }
+    assert_eq!(rows(&mut db, "SELECT * FROM t;"), before);
+}
+
+const TYPED: &str = "
+    CREATE TABLE t (id INT, n INT, f FLOAT, d DATE, s TEXT);
+    INSERT INTO t VALUES (1, 1, 1.0, '2024-01-01', 'a');
+";
+
+#[test]
+fn assignments_of_the_column_type_are_stored() {
+    let mut db = database(TYPED);
+    run(&mut db, "UPDATE t SET n = n + 1, f = 3, d = '2024-05-06', s = 'x' WHERE id = 1;").unwrap();
+    assert_eq!(
+        rows(&mut db, "SELECT n, f, d, s FROM t;"),
+        vec![vec![Value::Int(2), Value::Float(3.0), Value::Date("2024-05-06".into()), Value::Str("x".into())]]
+    );
+    // NULL fits a column of any type
+    run(&mut db, "UPDATE t SET n = NULL, d = NULL WHERE id = 1;").unwrap();
+    assert_eq!(rows(&mut db, "SELECT n, d FROM t;"), vec![vec![Value::Null, Value::Null]]);
+}
+
+#[test]
+fn assignment_of_another_type_names_the_column() {
+    let mut db = database(TYPED);
+    let before = rows(&mut db, "SELECT * FROM t;");
+    for (sql, expected) in [
+        ("UPDATE t SET n = 'abc' WHERE id = 1;", "Column 'n' expects INT, got 'abc'"),
+        ("UPDATE t SET n = 2.5 WHERE id = 1;", "Column 'n' expects INT, got 2.5"),
+        ("UPDATE t SET f = 'x' WHERE id = 1;", "Column 'f' expects FLOAT"),
+        ("UPDATE t SET s = 5 WHERE id = 1;", "Column 's' expects TEXT"),
+    ] {
+        let err = run(&mut db, sql).unwrap_err();
+        assert!(matches!(err, RqlError::TypeMismatch(_)), "{}: {:?}", sql, err);
+        assert!(err.to_string().contains(expected), "{}: {}", sql, err);
+    }
+    assert!(run(&mut db, "UPDATE t SET d = 'soon' WHERE id = 1;").is_err());
+    assert_eq!(rows(&mut db, "SELECT * FROM t;"), before);
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use common::{column, database};
+use rustql_lavanya::executor::{Database, NullOrder, QueryOptions};
+use rustql_lavanya::parser::Value;
+use rustql_lavanya::wal::{self, WriteAheadLog};
+
+// A database file path of its own for each test, with no snapshot or log left from an earlier run
+fn fresh_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("rustql_wal_{}_{}.bin", name, std::process::id()));
+    let path = path.to_string_lossy().into_owned();
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(wal::log_path(&path));
+    path
+}
+
+// Saves a snapshot holding table t with one row, then starts its log
+fn snapshot(path: &str) -> WriteAheadLog {
+    database("CREATE TABLE t (a INT); INSERT INTO t VALUES (1);").save(path).unwrap();
+    WriteAheadLog::create(path).unwrap()
+}
+
+fn reopen(path: &str) -> (Database, usize) {
+    let mut db = Database::load(path).unwrap();
+    let replayed = wal::replay(&mut db, path).unwrap();
+    (db, replayed)
+}
+
+#[test]
+fn replay_applies_logged_statements_over_the_snapshot() {
+    let path = fresh_path("replay");
+    let mut log = snapshot(&path);
+    log.append("INSERT INTO t VALUES (2);").unwrap();
+    log.append("UPDATE t SET a = 10 WHERE a = 1;").unwrap();
+
+    let (mut db, replayed) = reopen(&path);
+    assert_eq!(replayed, 2);
+    assert_eq!(column(&mut db, "SELECT a FROM t;"), vec![Value::Int(10), Value::Int(2)]);
+}
+
+#[test]
+fn truncated_last_entry_ends_the_log() {
+    let path = fresh_path("truncated");
+    let mut log = snapshot(&path);
+    log.append("INSERT INTO t VALUES (2);").unwrap();
+    log.append("INSERT INTO t VALUES (3);").unwrap();
+    drop(log);
+    // A crash in the middle of writing the second entry
+    let data = std::fs::read(wal::log_path(&path)).unwrap();
+    std::fs::write(wal::log_path(&path), &data[..data.len() - 5]).unwrap();
+
+    let (mut db, replayed) = reopen(&path);
+    assert_eq!(replayed, 1);
+    assert_eq!(column(&mut db, "SELECT a FROM t;"), vec![Value::Int(1), Value::Int(2)]);
+}
+
+#[test]
+fn log_for_another_snapshot_is_ignored() {
+    let path = fresh_path("mismatch");
+    let mut log = snapshot(&path);
+    log.append("INSERT INTO t VALUES (2);").unwrap();
+    drop(log);
+    let stale = std::fs::read(wal::log_path(&path)).unwrap();
+    // The snapshot is rewritten (which already holds the logged row), and the old log comes back
+    database("CREATE TABLE t (a INT); INSERT INTO t VALUES (1), (2);").save(&path).unwrap();
+    std::fs::write(wal::log_path(&path), stale).unwrap();
+
+    let (mut db, replayed) = reopen(&path);
+    assert_eq!(replayed, 0);
+    assert_eq!(column(&mut db, "SELECT a FROM t;"), vec![Value::Int(1), Value::Int(2)]);
+}
+
+#[test]
+fn unfinished_transaction_is_rolled_back() {
+    let path = fresh_path("transaction");
+    let mut log = snapshot(&path);
+    log.append("INSERT INTO t VALUES (2);").unwrap();
+    log.append("BEGIN;").unwrap();
+    log.append("INSERT INTO t VALUES (3);").unwrap();
+
+    let (mut db, replayed) = reopen(&path);
+    assert_eq!(replayed, 3);
+    assert!(!db.in_transaction());
+    assert_eq!(column(&mut db, "SELECT a FROM t;"), vec![Value::Int(1), Value::Int(2)]);
+}
+
+#[test]
+fn logged_options_apply_to_the_statements_after_them() {
+    let path = fresh_path("options");
+    database("CREATE TABLE t (n TEXT); INSERT INTO t VALUES ('Bob'), ('amy');").save(&path).unwrap();
+    let mut log = WriteAheadLog::create(&path).unwrap();
+    // `.ci on`, then a DELETE that only matches 'Bob' ignoring case
+    log.append_options(&QueryOptions { ignore_case: true, ..QueryOptions::default() }).unwrap();
+    log.append("DELETE FROM t WHERE n = 'bob';").unwrap();
+    log.append_options(&QueryOptions { null_order: NullOrder::Last, ..QueryOptions::default() }).unwrap();
+
+    let (mut db, replayed) = reopen(&path);
+    assert_eq!(replayed, 1);
+    assert_eq!(column(&mut db, "SELECT n FROM t;"), vec![Value::Str("amy".into())]);
+    // The session that replays the log keeps its own options
+    assert_eq!(db.options, QueryOptions::default());
+}